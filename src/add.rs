@@ -0,0 +1,290 @@
+use std::{
+    fs::{self, File},
+    io::{BufWriter, Seek, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    archive::Archive,
+    events::{self, Event},
+    extended::{self, extended_layout},
+    format::{AosV2Entry, AosV2Hdr, FILENAME_SIZE, Layout},
+    lock,
+    pack::temp_path,
+    plan::DryRunPlan,
+    read_at::read_exact_at,
+    repack::copy_range,
+};
+
+/// 添加条目选项
+#[derive(Debug, Default)]
+pub struct AddOptions {
+    /// 存储在封包中的名称，为 `None` 时使用文件名
+    pub name: Option<String>,
+    /// 目录表布局，扩展格式的封包会自动使用扩展布局
+    pub layout: Layout,
+    /// 已存在同名条目时让其指向新数据，而不是报错；旧数据保留在原位置，但不再被引用
+    pub force: bool,
+    /// 只打印添加后的目录表与文件大小变化，不写入任何文件
+    pub dry_run: bool,
+}
+
+/// 向 .aos 文件追加一个条目
+///
+/// 新数据写在原数据区末尾，原有条目的偏移量与数据保持不变，目录表中保留字段、目录表与
+/// 数据区之间的填充以及尾部数据原样保留。插入目录表条目会使数据区整体后移，因此需要重写整个文件：
+/// 先写入同一目录下的临时文件，完成后再替换原文件，中途失败不会损坏原文件。
+pub fn add_entry(archive_path: &Path, file_path: &Path, options: &AddOptions) -> Result<()> {
+    let name = match &options.name {
+        Some(name) => name.clone(),
+        None => file_path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .context("文件名无效")?
+            .to_owned(),
+    };
+    if name.is_empty() || name.len() >= FILENAME_SIZE {
+        bail!(
+            "条目名称 '{name}' 的长度必须在 1 到 {} 字节之间",
+            FILENAME_SIZE - 1
+        );
+    }
+    if name.contains('\0') {
+        bail!("条目名称不能包含空字符");
+    }
+
+    // 预演不会替换原文件，共享锁即可
+    let mut file = if options.dry_run {
+        lock::open_shared(archive_path)?
+    } else {
+        lock::open_exclusive(archive_path)?
+    };
+    let is_extended = extended::is_extended(&AosV2Hdr::from_reader(&mut file)?);
+    file.rewind()?;
+    let layout = if is_extended {
+        extended_layout()
+    } else {
+        options.layout
+    };
+    let archive = Archive::from_reader_with_layout(&mut file, layout)?;
+    let file_len = file.metadata()?.len();
+    let base_offset = archive.base_offset()?;
+    let data_end = archive.data_end();
+    if base_offset + data_end > file_len {
+        bail!("数据区被截断，无法追加条目，请先运行 validate 检查");
+    }
+
+    let existing = archive
+        .entries
+        .iter()
+        .position(|entry| entry.raw_filename() == name.as_bytes());
+    if existing.is_some() && !options.force {
+        bail!("封包中已存在条目 '{name}'，可使用 --force 让其指向新数据");
+    }
+
+    let data =
+        fs::read(file_path).with_context(|| format!("无法读取文件: {}", file_path.display()))?;
+    let length = u32::try_from(data.len()).context(format!("文件 '{name}' 过大"))?;
+    let offset = u32::try_from(data_end).context("数据区超出 4 GiB，无法追加")?;
+    offset
+        .checked_add(length)
+        .context("数据区超出 4 GiB，无法追加")?;
+
+    if !options.dry_run {
+        progress!("正在向 {} 添加条目: {name}", archive_path.display());
+        Event {
+            event: "add",
+            file: Some(&name),
+            offset: Some(offset as u64),
+            length: Some(length as u64),
+            ..Default::default()
+        }
+        .emit();
+    }
+
+    let footer = archive.footer(file_len)?;
+    if let Some((_, footer_length)) = footer
+        && !options.dry_run
+    {
+        events::warn(
+            "footer_moved",
+            None,
+            &format!("最后一个条目之后的 {footer_length} 字节尾部数据将移到新条目的数据之后"),
+        );
+    }
+
+    // 原目录表按原始字节复制，保留各条目的保留字段与扩展格式的标记
+    let header_size = std::mem::size_of::<AosV2Hdr>();
+    let entry_size = layout.entry_size();
+    let mut toc = vec![0u8; archive.entries.len() * entry_size];
+    read_exact_at(&file, &mut toc, header_size as u64)?;
+    // 目录表之后到数据区起始之间的填充随数据区一起复制
+    let toc_end = (header_size + toc.len()) as u64;
+    let padding = base_offset - toc_end;
+
+    let mut header = archive.header;
+    match existing {
+        Some(index) => {
+            if !options.dry_run {
+                events::warn(
+                    "replace_entry",
+                    Some(&name),
+                    &format!("条目 '{name}' 已存在，将指向新数据，其原数据不再被引用"),
+                );
+            }
+            let start = index * entry_size + FILENAME_SIZE;
+            toc[start..start + 4].copy_from_slice(&offset.to_le_bytes());
+            toc[start + 4..start + 8].copy_from_slice(&length.to_le_bytes());
+        }
+        None => {
+            let mut filename = [0u8; FILENAME_SIZE];
+            filename[..name.len()].copy_from_slice(name.as_bytes());
+            let entry = AosV2Entry {
+                filename,
+                offset,
+                length,
+            };
+            let bytes = if is_extended {
+                extended::entry_to_bytes(&entry, 0)
+            } else {
+                layout.entry_to_bytes(&entry)
+            };
+            toc.extend_from_slice(&bytes);
+            header.toc_length = u32::try_from(toc.len()).context("目录表过大")?;
+            // 原文件填写了 data_offset 时随目录表一起后移
+            if header.data_offset != 0 {
+                header.data_offset = header
+                    .data_offset
+                    .checked_add(entry_size as u32)
+                    .context("data_offset 溢出")?;
+            }
+        }
+    }
+
+    if options.dry_run {
+        let footer_length = footer.map_or(0, |(_, length)| length);
+        let new_len =
+            (header_size + toc.len()) as u64 + padding + data_end + length as u64 + footer_length;
+        add_plan(
+            &name,
+            existing.map(|index| &archive.entries[index]),
+            offset,
+            length,
+            footer_length,
+        )
+        .print(archive_path, file_len, new_len);
+        return Ok(());
+    }
+
+    let temp_path = temp_path(archive_path, "add");
+    let result = (|| -> Result<()> {
+        let output = File::create(&temp_path)
+            .with_context(|| format!("无法创建临时文件: {}", temp_path.display()))?;
+        let mut output = BufWriter::new(output);
+        output.write_all(&header.to_bytes())?;
+        output.write_all(&toc)?;
+        copy_range(&mut file, toc_end, padding + data_end, &mut output)?;
+        output.write_all(&data)?;
+        if let Some((footer_offset, footer_length)) = footer {
+            copy_range(&mut file, footer_offset, footer_length, &mut output)?;
+        }
+        let output = output.into_inner().map_err(|err| err.into_error())?;
+        output.sync_all()?;
+        Ok(())
+    })();
+    if let Err(err) = result {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err.context(format!("无法写入 {}", archive_path.display())));
+    }
+    drop(file);
+    fs::rename(&temp_path, archive_path)
+        .with_context(|| format!("无法替换原文件: {}", archive_path.display()))?;
+
+    progress!("添加完成，偏移量 {offset}，{length} 字节。");
+    Event {
+        event: "done",
+        file: Some(&archive_path.to_string_lossy()),
+        ..Default::default()
+    }
+    .emit();
+    Ok(())
+}
+
+/// `dry_run` 时添加操作的规划
+fn add_plan(
+    name: &str,
+    replaced: Option<&AosV2Entry>,
+    offset: u32,
+    length: u32,
+    footer_length: u64,
+) -> DryRunPlan {
+    let mut plan = DryRunPlan::default();
+    match replaced {
+        Some(entry) => {
+            let (old_offset, old_length) = ({ entry.offset }, { entry.length });
+            plan.line(format!(
+                "替换条目 '{name}': 偏移量 {old_offset} -> {offset}，长度 {old_length} -> {length}，原数据不再被引用"
+            ));
+            plan.line("目录表条目数不变，其他条目不受影响");
+        }
+        None => {
+            plan.line(format!("新增条目 '{name}': 偏移量 {offset}，长度 {length}"));
+            plan.line("目录表增加一个条目，数据区在文件中整体后移，其他条目的偏移量不变");
+        }
+    }
+    if footer_length > 0 {
+        plan.line(format!(
+            "{footer_length} 字节尾部数据将移到新条目的数据之后"
+        ));
+    }
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testutil::{TempDir, make_padded_archive},
+        unpack::{UnpackOptions, unpack_archive},
+    };
+
+    #[test]
+    fn dry_run_leaves_the_archive_unchanged() {
+        let dir = TempDir::new("add-dry-run");
+        let bytes = make_padded_archive(&[("a", b"abc")], "t.aos", b"ZZZZ");
+        let archive = dir.write("test.aos", &bytes);
+        let file = dir.write("new", b"xyz");
+        let options = AddOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+        add_entry(&archive, &file, &options).unwrap();
+        assert_eq!(fs::read(&archive).unwrap(), bytes);
+        assert!(!temp_path(&archive, "add").exists());
+    }
+
+    #[test]
+    fn add_keeps_padding_before_the_data_region() {
+        let dir = TempDir::new("add-padded");
+        let bytes = make_padded_archive(&[("a", b"abc")], "t.aos", b"ZZZZ");
+        let archive = dir.write("test.aos", &bytes);
+        let file = dir.write("new", b"xyz");
+        add_entry(&archive, &file, &AddOptions::default()).unwrap();
+
+        let output = dir.path().join("out");
+        let options = UnpackOptions {
+            output_dir: Some(output.clone()),
+            ..Default::default()
+        };
+        unpack_archive(&archive, &options).unwrap();
+        assert_eq!(fs::read(output.join("a")).unwrap(), b"abc");
+        assert_eq!(fs::read(output.join("new")).unwrap(), b"xyz");
+        let entry_size = std::mem::size_of::<AosV2Entry>() as u64;
+        assert_eq!(
+            fs::metadata(&archive).unwrap().len(),
+            bytes.len() as u64 + entry_size + 3
+        );
+    }
+}
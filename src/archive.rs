@@ -0,0 +1,277 @@
+use std::{
+    cmp::Ordering,
+    io::{self, Read, Seek, SeekFrom},
+};
+
+use anyhow::Result;
+
+use crate::format::{AosV2Entry, AosV2Hdr, Layout, NameDecoder};
+
+/// 条目的排序依据
+#[derive(Debug, Clone, Copy, PartialEq, Eq, palc::ValueEnum)]
+pub enum SortKey {
+    /// 以所选编码与存储方式解码后的文件名，无效字符以替换字符代替
+    Name,
+    /// 数据区内的偏移量
+    Offset,
+    /// 数据长度
+    Size,
+}
+
+/// 已解析的文件头与目录表
+#[derive(Debug)]
+pub struct Archive {
+    pub header: AosV2Hdr,
+    pub entries: Vec<AosV2Entry>,
+}
+
+impl Archive {
+    /// 从文件开头读取文件头与目录表
+    pub fn from_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        Self::from_reader_with_layout(reader, Layout::default())
+    }
+
+    /// 按指定的目录表布局读取文件头与目录表
+    pub fn from_reader_with_layout<R: Read>(reader: &mut R, layout: Layout) -> io::Result<Self> {
+        let header = AosV2Hdr::from_reader(reader)?;
+        let entry_count = header.entry_count(layout);
+        let mut entries = Vec::with_capacity(entry_count);
+        for index in 0..entry_count {
+            entries.push(layout.read_entry_at_index(reader, index)?);
+        }
+        Ok(Self { header, entries })
+    }
+
+    /// 数据区在文件中的起始偏移量，条目的 `offset` 相对于此处
+    ///
+    /// `data_offset` 非零时采用其值，目录表与数据区之间可以有填充 (见 [`Self::data_padding`])；
+    /// 为 0 时等于文件头与目录表的总大小。`data_offset` 落在文件头或目录表内部时报错。
+    pub fn base_offset(&self) -> Result<u64> {
+        self.header.declared_base_offset()
+    }
+
+    /// 目录表与数据区之间填充的字节数，`data_offset` 为 0 或等于目录表结束位置时为 0
+    pub fn data_padding(&self) -> Result<u64> {
+        Ok(self.base_offset()? - self.header.toc_end())
+    }
+
+    /// 忽略文件头中的 `data_offset`，之后按文件头与目录表的总大小确定数据区起始，对应 `--compute-base`
+    pub fn ignore_data_offset(&mut self) {
+        self.header.data_offset = 0;
+    }
+
+    /// 条目数据在文件中的绝对偏移量
+    pub fn entry_file_offset(&self, entry: &AosV2Entry) -> Result<u64> {
+        Ok(self.base_offset()? + entry.offset as u64)
+    }
+
+    /// 最后一个条目数据的结束位置，相对于数据区起始
+    pub fn data_end(&self) -> u64 {
+        self.entries
+            .iter()
+            .map(|entry| entry.offset as u64 + entry.length as u64)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// 最后一个条目之后的尾部数据在文件中的绝对偏移量与长度，没有尾部数据时为 `None`
+    ///
+    /// 部分引擎会在此处存放签名等不属于正式结构的数据。
+    pub fn footer(&self, file_len: u64) -> Result<Option<(u64, u64)>> {
+        Ok(footer_range(
+            self.base_offset()? + self.data_end(),
+            file_len,
+        ))
+    }
+
+    /// 按指定依据排序的条目，`descending` 为 `true` 时降序，依据相同的条目保持目录表顺序
+    ///
+    /// 按名称排序时以 `names` 解码文件名，自动选择编码时应先调用 [`NameDecoder::resolve`]。
+    pub fn sorted_entries(
+        &self,
+        key: SortKey,
+        descending: bool,
+        names: &NameDecoder,
+    ) -> Vec<&AosV2Entry> {
+        self.sorted_indices(key, descending, names)
+            .into_iter()
+            .map(|index| &self.entries[index])
+            .collect()
+    }
+
+    /// 与 [`Self::sorted_entries`] 顺序相同的条目序号
+    pub(crate) fn sorted_indices(
+        &self,
+        key: SortKey,
+        descending: bool,
+        names: &NameDecoder,
+    ) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..self.entries.len()).collect();
+        match key {
+            SortKey::Name => {
+                let names: Vec<String> = self
+                    .entries
+                    .iter()
+                    .map(|entry| names.decode_quiet(entry))
+                    .collect();
+                indices.sort_by(|&a, &b| ordered(names[a].cmp(&names[b]), descending));
+            }
+            SortKey::Offset => indices.sort_by(|&a, &b| {
+                let (a, b) = (self.entries[a].offset, self.entries[b].offset);
+                ordered(a.cmp(&b), descending)
+            }),
+            SortKey::Size => indices.sort_by(|&a, &b| {
+                let (a, b) = (self.entries[a].length, self.entries[b].length);
+                ordered(a.cmp(&b), descending)
+            }),
+        }
+        indices
+    }
+}
+
+/// 数据在 `data_end` (绝对偏移量) 处结束时尾部数据的范围
+pub(crate) fn footer_range(data_end: u64, file_len: u64) -> Option<(u64, u64)> {
+    (file_len > data_end).then(|| (data_end, file_len - data_end))
+}
+
+fn ordered(ordering: Ordering, descending: bool) -> Ordering {
+    if descending {
+        ordering.reverse()
+    } else {
+        ordering
+    }
+}
+
+/// 逐个读取目录表中的条目
+///
+/// 迭代器首次使用时定位到目录表的起始位置，之后每次迭代只解析一个条目，
+/// 不会为整个目录表预先分配内存。读取出错后迭代结束。
+pub fn toc_entries<R: Read + Seek>(
+    reader: &mut R,
+    count: usize,
+    layout: Layout,
+) -> impl Iterator<Item = Result<AosV2Entry>> {
+    let mut positioned = false;
+    let mut remaining = count;
+    std::iter::from_fn(move || {
+        if remaining == 0 {
+            return None;
+        }
+        if !positioned {
+            positioned = true;
+            let toc_start = std::mem::size_of::<AosV2Hdr>() as u64;
+            if let Err(err) = reader.seek(SeekFrom::Start(toc_start)) {
+                remaining = 0;
+                return Some(Err(err.into()));
+            }
+        }
+        let entry = layout.read_entry_at_index(reader, count - remaining);
+        remaining -= 1;
+        if entry.is_err() {
+            remaining = 0;
+        }
+        Some(entry.map_err(Into::into))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        format::{NameEncoding, NameStyle},
+        testutil::{make_padded_archive, make_test_archive, set_data_offset},
+    };
+
+    #[test]
+    fn padded_data_offset_is_the_data_base() {
+        let bytes = make_padded_archive(&[("a", b"abc")], "t.aos", b"ZZZZ");
+        let archive = Archive::from_reader(&mut &bytes[..]).unwrap();
+        let toc_end = archive.header.toc_end();
+        assert_eq!(archive.base_offset().unwrap(), toc_end + 4);
+        assert_eq!(archive.data_padding().unwrap(), 4);
+        assert_eq!(
+            archive.entry_file_offset(&archive.entries[0]).unwrap(),
+            toc_end + 4
+        );
+        assert_eq!(&bytes[toc_end as usize + 4..], b"abc");
+        // 填充不是尾部数据
+        assert_eq!(archive.footer(bytes.len() as u64).unwrap(), None);
+    }
+
+    #[test]
+    fn trailing_bytes_are_the_footer() {
+        let mut bytes = make_padded_archive(&[("a", b"abc")], "t.aos", b"ZZZZ");
+        let data_end = bytes.len() as u64;
+        bytes.extend_from_slice(&[0xee; 16]);
+        let archive = Archive::from_reader(&mut &bytes[..]).unwrap();
+        assert_eq!(
+            archive.footer(bytes.len() as u64).unwrap(),
+            Some((data_end, 16))
+        );
+    }
+
+    /// 只含目录表的封包，条目按给定的顺序以 `style` 存储名称
+    fn archive_with_names(style: NameStyle, names: &[&[u8]]) -> Archive {
+        let entries = names
+            .iter()
+            .map(|name| AosV2Entry {
+                filename: style.encode(name).unwrap(),
+                offset: 0,
+                length: 0,
+            })
+            .collect();
+        let bytes = make_test_archive(&[("x", b"")], "t.aos");
+        let header = Archive::from_reader(&mut &bytes[..]).unwrap().header;
+        Archive { header, entries }
+    }
+
+    fn sorted_names(archive: &Archive, names: &NameDecoder) -> Vec<String> {
+        archive
+            .sorted_entries(SortKey::Name, false, names)
+            .into_iter()
+            .map(|entry| names.decode_quiet(entry))
+            .collect()
+    }
+
+    #[test]
+    fn name_sort_uses_the_chosen_encoding() {
+        // Shift-JIS 的 "い" 与 "あ"，按 UTF-8 解码时都是替换字符
+        let archive = archive_with_names(NameStyle::Nul, &[b"\x82\xa2", b"\x82\xa0"]);
+        let names = NameDecoder {
+            encoding: NameEncoding::ShiftJis,
+            ..Default::default()
+        };
+        assert_eq!(sorted_names(&archive, &names), ["あ", "い"]);
+    }
+
+    #[test]
+    fn name_sort_ignores_the_length_prefix() {
+        // 按长度字节排序时较短的 "b" 会排在前面
+        let archive = archive_with_names(NameStyle::LengthPrefixed, &[b"b", b"aa"]);
+        let names = NameDecoder {
+            style: NameStyle::LengthPrefixed,
+            ..Default::default()
+        };
+        assert_eq!(sorted_names(&archive, &names), ["aa", "b"]);
+    }
+
+    #[test]
+    fn zero_data_offset_uses_computed_base() {
+        let mut bytes = make_test_archive(&[("a", b"abc")], "t.aos");
+        set_data_offset(&mut bytes, 0);
+        let archive = Archive::from_reader(&mut &bytes[..]).unwrap();
+        assert_eq!(archive.base_offset().unwrap(), archive.header.toc_end());
+        assert_eq!(archive.data_padding().unwrap(), 0);
+        assert_eq!(archive.footer(bytes.len() as u64).unwrap(), None);
+    }
+
+    #[test]
+    fn data_offset_inside_toc_is_rejected() {
+        let mut bytes = make_test_archive(&[("a", b"abc")], "t.aos");
+        set_data_offset(&mut bytes, 100);
+        let mut archive = Archive::from_reader(&mut &bytes[..]).unwrap();
+        assert!(archive.base_offset().is_err());
+        archive.ignore_data_offset();
+        assert_eq!(archive.base_offset().unwrap(), archive.header.toc_end());
+    }
+}
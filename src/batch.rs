@@ -0,0 +1,50 @@
+//! 批量处理多个 .aos 文件时共用的汇总与退出码
+
+use std::path::PathBuf;
+
+/// 批量处理中未通过的文件及其原因
+#[derive(Debug)]
+pub struct BatchFailure {
+    pub path: PathBuf,
+    /// 第一个错误的描述
+    pub cause: String,
+}
+
+/// 打印通过与失败的文件数，以及失败的文件列表
+pub(crate) fn print_batch_summary(total: usize, failed: &[BatchFailure]) {
+    progress!(
+        "共 {} 个文件: {} 个通过，{} 个失败",
+        total,
+        total - failed.len(),
+        failed.len()
+    );
+    if !failed.is_empty() {
+        progress!("未通过的文件:");
+        for failure in failed {
+            progress!("  {}: {}", failure.path.display(), failure.cause);
+        }
+    }
+}
+
+/// 批量处理的退出码: 全部通过时为 0；`count_failures` 时为失败的文件数 (最大 255)，否则为 1
+pub fn batch_exit_status(failed: usize, count_failures: bool) -> u8 {
+    match failed {
+        0 => 0,
+        count if count_failures => count.min(255) as u8,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exit_status_counts_failures_only_when_asked() {
+        assert_eq!(batch_exit_status(0, true), 0);
+        assert_eq!(batch_exit_status(0, false), 0);
+        assert_eq!(batch_exit_status(3, false), 1);
+        assert_eq!(batch_exit_status(3, true), 3);
+        assert_eq!(batch_exit_status(300, true), 255);
+    }
+}
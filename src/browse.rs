@@ -0,0 +1,337 @@
+//! 交互式浏览封包内容，需要启用 `tui` 特性
+//!
+//! 以逐行输入命令的方式工作: 分页列出目录表、预览条目开头的数据、提取单个条目。
+//! 这不是全屏的终端界面，没有可滚动的选择列表与单键操作，`tui` 特性目前也不引入依赖。
+//! 启动时只读取文件头与目录表，条目数据在预览或提取时按需定位读取，大封包上也能立即响应。
+
+use std::{
+    fs::File,
+    io::{self, BufRead, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+use encoding_rs::SHIFT_JIS;
+
+use crate::{
+    archive::Archive,
+    format::{Layout, NameDecoder},
+    hexdump, magic,
+    repack::copy_range,
+    source::Source,
+    validate::check_entry_bounds,
+};
+
+/// 每页列出的条目数
+const PAGE_SIZE: usize = 20;
+/// 预览时读取的最大字节数
+const PREVIEW_LEN: usize = 512;
+
+const HELP: &str = "\
+命令:
+  l [页码]        列出一页条目，省略页码时为当前页
+  n / p           下一页 / 上一页
+  v <序号>        预览条目开头的数据，文本以文本显示，其余以十六进制显示
+  x <序号> [路径] 提取条目，默认写入输出目录中与条目同名的文件，不覆盖已有文件
+  / <文本>        列出名称包含该文本的条目
+  ? / h           显示本帮助
+  q               退出";
+
+/// 浏览选项
+#[derive(Debug, Default)]
+pub struct BrowseOptions {
+    /// 目录表布局
+    pub layout: Layout,
+    /// 文件名解码方式，无法解码的名称以替换字符显示
+    pub names: NameDecoder,
+    /// 提取条目时的输出目录，为 `None` 时为当前目录
+    pub output_dir: Option<PathBuf>,
+}
+
+/// 从标准输入读取命令，交互式浏览 .aos 文件，输入 `q` 或标准输入结束时返回
+pub fn browse_archive(archive_path: &Path, options: &BrowseOptions) -> Result<()> {
+    let stdin = io::stdin().lock();
+    let stdout = BufWriter::new(io::stdout().lock());
+    match Browser::open(archive_path, options)?.run(stdin, stdout) {
+        // 输出被管道另一端提前关闭时安静地结束
+        Err(err)
+            if err
+                .downcast_ref::<io::Error>()
+                .is_some_and(|err| err.kind() == io::ErrorKind::BrokenPipe) =>
+        {
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+struct Browser<'a> {
+    source: Source,
+    archive: Archive,
+    names: Vec<String>,
+    file_len: u64,
+    page: usize,
+    options: &'a BrowseOptions,
+}
+
+impl<'a> Browser<'a> {
+    fn open(archive_path: &Path, options: &'a BrowseOptions) -> Result<Self> {
+        let mut source = Source::open(archive_path)?;
+        let archive = Archive::from_reader_with_layout(&mut source, options.layout)?;
+        let decoder = NameDecoder {
+            lossy: true,
+            ..options.names.resolve(&archive.entries)?
+        };
+        let names = archive
+            .entries
+            .iter()
+            .map(|entry| decoder.decode(entry))
+            .collect::<Result<_>>()?;
+        let file_len = source.len()?;
+        Ok(Self {
+            source,
+            archive,
+            names,
+            file_len,
+            page: 0,
+            options,
+        })
+    }
+
+    fn run<R: BufRead, W: Write>(mut self, input: R, mut out: W) -> Result<()> {
+        writeln!(
+            out,
+            "共 {} 个条目，输入 ? 查看命令",
+            self.archive.entries.len()
+        )?;
+        self.list_page(&mut out)?;
+        let mut lines = input.lines();
+        loop {
+            write!(out, "> ")?;
+            out.flush()?;
+            let Some(line) = lines.next().transpose()? else {
+                writeln!(out)?;
+                break;
+            };
+            let line = line.trim();
+            let (command, argument) = line.split_once(' ').unwrap_or((line, ""));
+            let argument = argument.trim();
+            // 单个命令失败只打印错误，不退出
+            let result = match command {
+                "" => Ok(()),
+                "q" => break,
+                "?" | "h" => writeln!(out, "{HELP}").map_err(Into::into),
+                "l" => {
+                    if !argument.is_empty() {
+                        match argument.parse::<usize>() {
+                            Ok(page) if page > 0 => self.page = page - 1,
+                            _ => {
+                                writeln!(out, "无效的页码: '{argument}'")?;
+                                continue;
+                            }
+                        }
+                    }
+                    self.list_page(&mut out)
+                }
+                "n" => {
+                    if (self.page + 1) * PAGE_SIZE < self.archive.entries.len() {
+                        self.page += 1;
+                    }
+                    self.list_page(&mut out)
+                }
+                "p" => {
+                    self.page = self.page.saturating_sub(1);
+                    self.list_page(&mut out)
+                }
+                "v" => self
+                    .index(argument)
+                    .and_then(|index| self.preview(index, &mut out)),
+                "x" => {
+                    let (index, path) = argument.split_once(' ').unwrap_or((argument, ""));
+                    self.index(index)
+                        .and_then(|index| self.extract(index, path.trim(), &mut out))
+                }
+                "/" => self.search(argument, &mut out),
+                _ => writeln!(out, "未知的命令 '{command}'，输入 ? 查看命令").map_err(Into::into),
+            };
+            if let Err(err) = result {
+                writeln!(out, "错误: {err:#}")?;
+            }
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    fn index(&self, argument: &str) -> Result<usize> {
+        let count = self.archive.entries.len();
+        match argument.parse::<usize>() {
+            Ok(index) if index < count => Ok(index),
+            Ok(index) => bail!("序号 {index} 超出了条目数量 {count}"),
+            Err(_) => bail!("需要条目序号，例如 `v 0`"),
+        }
+    }
+
+    fn write_entry_line<W: Write>(&self, out: &mut W, index: usize) -> io::Result<()> {
+        let entry = &self.archive.entries[index];
+        writeln!(
+            out,
+            "{index:>6} {:>10} {:>10}  {}",
+            { entry.offset },
+            { entry.length },
+            self.names[index]
+        )
+    }
+
+    fn list_page<W: Write>(&mut self, out: &mut W) -> Result<()> {
+        let count = self.archive.entries.len();
+        let pages = count.div_ceil(PAGE_SIZE).max(1);
+        self.page = self.page.min(pages - 1);
+        writeln!(
+            out,
+            "{:>4} {:>7} {:>8}  名称 (第 {}/{pages} 页)",
+            "序号",
+            "偏移量",
+            "长度",
+            self.page + 1
+        )?;
+        let start = self.page * PAGE_SIZE;
+        for index in start..(start + PAGE_SIZE).min(count) {
+            self.write_entry_line(out, index)?;
+        }
+        Ok(())
+    }
+
+    fn search<W: Write>(&self, text: &str, out: &mut W) -> Result<()> {
+        if text.is_empty() {
+            bail!("需要要查找的文本，例如 `/ .png`");
+        }
+        let text = text.to_lowercase();
+        let mut found = 0usize;
+        for (index, name) in self.names.iter().enumerate() {
+            if name.to_lowercase().contains(&text) {
+                self.write_entry_line(out, index)?;
+                found += 1;
+            }
+        }
+        writeln!(out, "找到 {found} 个条目")?;
+        Ok(())
+    }
+
+    /// 检查条目的数据范围，返回其在文件中的绝对偏移量与长度
+    fn locate(&self, index: usize) -> Result<(u64, u64)> {
+        let entry = &self.archive.entries[index];
+        let base_offset = self.archive.base_offset()?;
+        if let Some(error) =
+            check_entry_bounds(&self.names[index], entry, base_offset, self.file_len)
+        {
+            bail!("{}: {error}", self.options.layout.entry_location(index));
+        }
+        Ok((base_offset + entry.offset as u64, entry.length as u64))
+    }
+
+    fn preview<W: Write>(&mut self, index: usize, out: &mut W) -> Result<()> {
+        let (start, length) = self.locate(index)?;
+        let shown = length.min(PREVIEW_LEN as u64);
+        let mut data = vec![0u8; shown as usize];
+        self.source.read_exact_at(&mut data, start)?;
+
+        let kind = magic::detect(&data);
+        writeln!(
+            out,
+            "{} ({} 字节{})",
+            self.names[index],
+            length,
+            kind.map(|kind| format!("，类型 {kind}"))
+                .unwrap_or_default()
+        )?;
+        match preview_text(&data, shown < length) {
+            Some(text) if kind.is_none() => writeln!(out, "{text}")?,
+            _ => hexdump::dump(&mut self.source, start, 0, shown, out)?,
+        }
+        if shown < length {
+            writeln!(out, "... 只显示了前 {shown} 字节")?;
+        }
+        Ok(())
+    }
+
+    fn extract<W: Write>(&mut self, index: usize, path: &str, out: &mut W) -> Result<()> {
+        let (start, length) = self.locate(index)?;
+        let path = if path.is_empty() {
+            // 只使用名称的最后一部分，名称中的目录不会在输出目录之外创建文件
+            let name = &self.names[index];
+            let file_name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+            if file_name.is_empty() || file_name == "." || file_name == ".." {
+                bail!("条目名称 '{name}' 不能用作文件名，请指定输出路径");
+            }
+            self.options
+                .output_dir
+                .as_deref()
+                .unwrap_or(Path::new("."))
+                .join(file_name)
+        } else {
+            PathBuf::from(path)
+        };
+        let mut file = File::create_new(&path).with_context(|| {
+            format!("无法创建文件 (已存在的文件不会被覆盖): {}", path.display())
+        })?;
+        copy_range(&mut self.source, start, length, &mut file)
+            .with_context(|| format!("无法写入文件: {}", path.display()))?;
+        writeln!(out, "已将 {length} 字节写入: {}", path.display())?;
+        Ok(())
+    }
+}
+
+/// 数据为 UTF-8 或 Shift-JIS 文本时返回解码结果，`truncated` 时允许末尾的字符不完整
+fn preview_text(data: &[u8], truncated: bool) -> Option<String> {
+    let is_text = |text: &str| {
+        !text
+            .chars()
+            .any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t'))
+    };
+    // 截断处最多切开一个多字节字符
+    let candidates: Vec<usize> = if truncated {
+        (data.len().saturating_sub(3)..=data.len()).rev().collect()
+    } else {
+        vec![data.len()]
+    };
+    if let Some(text) = candidates
+        .iter()
+        .find_map(|&end| std::str::from_utf8(&data[..end]).ok())
+    {
+        return is_text(text).then(|| text.to_owned());
+    }
+    let text = candidates.iter().find_map(|&end| {
+        SHIFT_JIS.decode_without_bom_handling_and_without_replacement(&data[..end])
+    })?;
+    is_text(&text).then(|| text.into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::testutil::{TempDir, make_padded_archive};
+
+    #[test]
+    fn preview_and_extract_from_padded_archive() {
+        let dir = TempDir::new("browse-padded");
+        let bytes = make_padded_archive(&[("a.txt", b"abc"), ("b", b"de")], "t.aos", b"ZZZZ");
+        let archive = dir.write("test.aos", &bytes);
+        let options = BrowseOptions {
+            output_dir: Some(dir.path().to_owned()),
+            ..Default::default()
+        };
+        let input = "v 0\nx 1\nx 1\nq\n";
+        let mut out = Vec::new();
+        Browser::open(&archive, &options)
+            .unwrap()
+            .run(input.as_bytes(), &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("a.txt (3 字节)\nabc\n"), "{out}");
+        assert_eq!(fs::read(dir.path().join("b")).unwrap(), b"de");
+        // 已存在的文件不会被覆盖
+        assert!(out.contains("错误: 无法创建文件"), "{out}");
+    }
+}
@@ -0,0 +1,26 @@
+//! 通过原子标记取消耗时的操作
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// 操作因取消标记被设置而中止时返回的错误，可通过 `anyhow::Error::is` 识别
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("操作已取消")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// 取消标记已被设置时返回 [`Cancelled`] 错误
+pub(crate) fn check(flag: Option<&AtomicBool>) -> anyhow::Result<()> {
+    if flag.is_some_and(|flag| flag.load(Ordering::Relaxed)) {
+        return Err(Cancelled.into());
+    }
+    Ok(())
+}
@@ -0,0 +1,108 @@
+//! `.aos_up.toml` 配置文件，为命令行参数提供默认值
+//!
+//! 优先级从高到低为: 命令行参数、配置文件、内置默认值。配置文件需要启用 `config` 特性。
+
+use std::{
+    env,
+    num::NonZeroU64,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+
+use crate::{format::NameEncoding, threads::Threads};
+
+/// 自动查找的配置文件名
+pub const CONFIG_FILE_NAME: &str = ".aos_up.toml";
+
+/// 配置文件中可以设置的默认值，未设置的项使用内置默认值
+#[derive(Debug, Default)]
+#[cfg_attr(
+    feature = "config",
+    derive(serde::Deserialize),
+    serde(default, deny_unknown_fields)
+)]
+pub struct Config {
+    /// 对应 `--entry-size`
+    pub entry_size: Option<usize>,
+    /// 对应 `--lossy-names`，可以在命令行中以 `--no-lossy-names` 关闭
+    pub lossy_names: Option<bool>,
+    /// 对应 `--name-encoding`
+    pub name_encoding: Option<NameEncoding>,
+    /// 对应 `--expect-data-align`
+    pub expect_data_align: Option<NonZeroU64>,
+    /// 对应 `--retry`
+    pub retry: Option<u32>,
+    /// 对应 `--op-timeout`，单位为秒
+    pub op_timeout: Option<NonZeroU64>,
+    /// 对应 `validate --threads`，旧名称 `parallel` 仍然可用
+    #[cfg_attr(feature = "config", serde(alias = "parallel"))]
+    pub threads: Option<Threads>,
+    /// 对应 `validate`、`pack` 与 `watch` 的 `--max-open-files`
+    pub max_open_files: Option<usize>,
+}
+
+impl Config {
+    /// 加载配置
+    ///
+    /// 指定 `path` 时只读取该文件；否则依次查找当前目录与主目录下的 [`CONFIG_FILE_NAME`]，
+    /// 使用找到的第一个，都不存在时返回空配置。
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        if let Some(path) = path {
+            return parse(path);
+        }
+        match find() {
+            Some(path) if cfg!(feature = "config") => parse(&path),
+            Some(path) => {
+                crate::events::warn(
+                    "config_ignored",
+                    None,
+                    &format!("此构建未启用 config 特性，忽略配置文件: {}", path.display()),
+                );
+                Ok(Self::default())
+            }
+            None => Ok(Self::default()),
+        }
+    }
+}
+
+fn find() -> Option<PathBuf> {
+    let home = env::var_os("HOME").or_else(|| env::var_os("USERPROFILE"));
+    [env::current_dir().ok(), home.map(PathBuf::from)]
+        .into_iter()
+        .flatten()
+        .map(|dir| dir.join(CONFIG_FILE_NAME))
+        .find(|path| path.is_file())
+}
+
+#[cfg(feature = "config")]
+fn parse(path: &Path) -> Result<Config> {
+    use anyhow::Context;
+
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("无法读取配置文件: {}", path.display()))?;
+    toml::from_str(&content).with_context(|| format!("配置文件格式错误: {}", path.display()))
+}
+
+#[cfg(not(feature = "config"))]
+fn parse(path: &Path) -> Result<Config> {
+    anyhow::bail!(
+        "此构建未启用 config 特性，无法读取配置文件: {}",
+        path.display()
+    )
+}
+
+#[cfg(all(test, feature = "config"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn name_options_are_read_from_toml() {
+        let config: Config =
+            toml::from_str("lossy_names = true\nname_encoding = \"shift-jis\"\nthreads = 4\n")
+                .unwrap();
+        assert_eq!(config.lossy_names, Some(true));
+        assert_eq!(config.name_encoding, Some(NameEncoding::ShiftJis));
+        assert!(toml::from_str::<Config>("name_encoding = \"latin1\"").is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+
+use crate::{
+    archive::Archive,
+    format::AosV2Entry,
+    hash::hash_range,
+    lock,
+    pack::{PackOptions, collect_files, file_sizes, stored_name},
+};
+
+fn open(path: &Path) -> Result<(File, Archive)> {
+    let mut file = lock::open_shared(path)?;
+    let archive = Archive::from_reader(&mut file)?;
+    Ok((file, archive))
+}
+
+fn by_name(archive: &Archive) -> Result<BTreeMap<String, &AosV2Entry>> {
+    archive
+        .entries
+        .iter()
+        .map(|entry| Ok((entry.get_filename_str()?, entry)))
+        .collect()
+}
+
+fn digest(file: &mut File, archive: &Archive, name: &str, entry: &AosV2Entry) -> Result<u64> {
+    let start = archive.entry_file_offset(entry)?;
+    hash_range(file, start, entry.length as u64).with_context(|| format!("无法读取条目 '{name}'"))
+}
+
+/// 比较两个 .aos 文件的条目，返回两者是否存在差异
+///
+/// 条目内容以固定大小的块流式计算 xxh3 摘要后比较，长度不同时直接判定为已修改，
+/// 因此内存占用与条目大小无关。
+pub fn diff_archives(old_path: &Path, new_path: &Path) -> Result<bool> {
+    let (mut old_file, old) = open(old_path)?;
+    let (mut new_file, new) = open(new_path)?;
+    let old_entries = by_name(&old)?;
+    let new_entries = by_name(&new)?;
+
+    let mut differs = false;
+    for (name, old_entry) in &old_entries {
+        let Some(new_entry) = new_entries.get(name) else {
+            println!("- {name} ({} 字节)", { old_entry.length });
+            differs = true;
+            continue;
+        };
+        let (old_len, new_len) = (old_entry.length, new_entry.length);
+        if old_len != new_len {
+            println!("M {name}: {old_len} -> {new_len} 字节");
+            differs = true;
+            continue;
+        }
+        let old_digest = digest(&mut old_file, &old, name, old_entry)?;
+        let new_digest = digest(&mut new_file, &new, name, new_entry)?;
+        if old_digest != new_digest {
+            println!("M {name}: {old_len} 字节, xxh3 {old_digest:016x} -> {new_digest:016x}");
+            differs = true;
+        }
+    }
+    for (name, new_entry) in &new_entries {
+        if !old_entries.contains_key(name) {
+            println!("+ {name} ({} 字节)", { new_entry.length });
+            differs = true;
+        }
+    }
+
+    if !differs {
+        println!("两个文件的条目内容一致。");
+    }
+    Ok(differs)
+}
+
+/// 将 .aos 文件与其源目录比较，返回两者是否存在差异
+///
+/// 条目与同名文件均流式计算 xxh3 摘要，不会将条目解包到磁盘。仅比较目录顶层的文件，
+/// 与 `pack` 的行为一致；目录中的 .aos 文件自身会被忽略。
+pub fn verify_against(archive_path: &Path, dir_path: &Path) -> Result<bool> {
+    let (mut archive_file, archive) = open(archive_path)?;
+    let entries = by_name(&archive)?;
+
+    let archive_canonical = fs::canonicalize(archive_path).ok();
+    let mut files = BTreeMap::new();
+    for dir_entry in
+        fs::read_dir(dir_path).with_context(|| format!("无法读取目录: {}", dir_path.display()))?
+    {
+        let path = dir_entry?.path();
+        if !path.is_file() || fs::canonicalize(&path).ok() == archive_canonical {
+            continue;
+        }
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        files.insert(name, path);
+    }
+
+    let mut differs = false;
+    for (name, entry) in &entries {
+        let Some(path) = files.get(name) else {
+            println!("- {name} ({} 字节，目录中不存在)", {
+                entry.length
+            });
+            differs = true;
+            continue;
+        };
+        let file_len = fs::metadata(path)
+            .with_context(|| format!("无法读取文件信息: {}", path.display()))?
+            .len();
+        let entry_len = entry.length as u64;
+        if file_len != entry_len {
+            println!("M {name}: 封包中 {entry_len} 字节，目录中 {file_len} 字节");
+            differs = true;
+            continue;
+        }
+        let entry_digest = digest(&mut archive_file, &archive, name, entry)?;
+        let file_digest = file_digest(path, file_len)?;
+        if entry_digest != file_digest {
+            println!("M {name}: {entry_len} 字节, xxh3 {entry_digest:016x} -> {file_digest:016x}");
+            differs = true;
+        }
+    }
+    for (name, path) in &files {
+        if !entries.contains_key(name) {
+            println!("+ {name} (仅存在于目录: {})", path.display());
+            differs = true;
+        }
+    }
+
+    if !differs {
+        println!("封包与目录内容一致。");
+    }
+    Ok(differs)
+}
+
+/// 按封包选项规划目录的目录表，与已有 .aos 文件的目录表比较，返回两者是否存在差异
+///
+/// 只读取文件名与大小，不读取文件内容也不写入输出，报告新增、删除、大小变化与顺序变化的条目。
+/// 顺序只比较两边都存在的条目的相对位置，用于排查目录遍历顺序等导致封包不可复现的原因。
+pub fn diff_pack_plan(dir_path: &Path, options: &PackOptions, archive_path: &Path) -> Result<bool> {
+    let mut file = lock::open_shared(archive_path)?;
+    let archive = Archive::from_reader_with_layout(&mut file, options.entry_layout())?;
+    let old: Vec<(String, u64)> = archive
+        .entries
+        .iter()
+        .map(|entry| Ok((entry.get_filename_str()?, entry.length as u64)))
+        .collect::<Result<_>>()?;
+
+    let files = collect_files(dir_path, options)?;
+    let renames = options.renames()?;
+    let sizes = file_sizes(options.name_root(dir_path), &files, &renames)?;
+    let new: Vec<(String, u64)> = files
+        .iter()
+        .zip(sizes)
+        .map(|(path, size)| {
+            Ok((
+                stored_name(options.name_root(dir_path), path, &renames)?,
+                size,
+            ))
+        })
+        .collect::<Result<_>>()?;
+
+    let old_sizes: BTreeMap<&str, u64> = old
+        .iter()
+        .map(|(name, size)| (name.as_str(), *size))
+        .collect();
+    let new_sizes: BTreeMap<&str, u64> = new
+        .iter()
+        .map(|(name, size)| (name.as_str(), *size))
+        .collect();
+
+    let mut differs = false;
+    for (name, size) in &old {
+        if !new_sizes.contains_key(name.as_str()) {
+            println!("- {name} ({size} 字节)");
+            differs = true;
+        }
+    }
+    for (name, size) in &new {
+        match old_sizes.get(name.as_str()) {
+            None => {
+                println!("+ {name} ({size} 字节)");
+                differs = true;
+            }
+            Some(old_size) if old_size != size => {
+                println!("M {name}: {old_size} -> {size} 字节");
+                differs = true;
+            }
+            Some(_) => {}
+        }
+    }
+
+    let old_order: Vec<&str> = old
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .filter(|name| new_sizes.contains_key(name))
+        .collect();
+    let new_order: Vec<&str> = new
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .filter(|name| old_sizes.contains_key(name))
+        .collect();
+    let old_positions: BTreeMap<&str, usize> = old_order
+        .iter()
+        .enumerate()
+        .map(|(position, name)| (*name, position))
+        .collect();
+    for (position, name) in new_order.iter().enumerate() {
+        let old_position = old_positions[name];
+        if old_position != position {
+            println!("R {name}: 第 {old_position} 个 -> 第 {position} 个");
+            differs = true;
+        }
+    }
+
+    if !differs {
+        println!("封包计划与现有封包的目录表一致。");
+    }
+    Ok(differs)
+}
+
+fn file_digest(path: &Path, len: u64) -> Result<u64> {
+    let mut file = File::open(path).with_context(|| format!("无法打开文件: {}", path.display()))?;
+    hash_range(&mut file, 0, len).with_context(|| format!("无法读取文件: {}", path.display()))
+}
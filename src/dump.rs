@@ -0,0 +1,119 @@
+use std::{
+    fmt::Write as _,
+    fs::{self, File},
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    archive::Archive,
+    extended,
+    format::{AosV2Hdr, Layout},
+    repack::copy_range,
+    source::Source,
+    validate::check_entry_bounds,
+};
+
+/// 将数据区 (数据区起始偏移量之后的全部字节，见 [`Archive::base_offset`]) 原样流式复制到
+/// `output_path`，返回复制的字节数
+///
+/// 输出文件中的偏移量与条目的 `offset` 一一对应。
+pub fn dump_data(archive_path: &Path, output_path: &Path) -> Result<u64> {
+    let mut source = Source::open(archive_path)?;
+    let header = AosV2Hdr::from_reader(&mut source)?;
+    source.seek(SeekFrom::Start(header.declared_base_offset()?))?;
+
+    let output = File::create(output_path)
+        .with_context(|| format!("无法创建输出文件: {}", output_path.display()))?;
+    let mut output = BufWriter::new(output);
+    let copied = io::copy(&mut source, &mut output)
+        .with_context(|| format!("无法写入输出文件: {}", output_path.display()))?;
+    output.flush()?;
+    Ok(copied)
+}
+
+/// 将每个条目的数据写入输出文件中与其 `offset` 相同的位置，返回写入的条目数
+///
+/// 与 [`dump_data`] 不同，只有被条目引用的字节会写入，空隙与文件末尾之后的数据不复制。
+/// 输出文件先扩展到数据区末尾，空隙在支持稀疏文件的文件系统 (例如 Linux 的 ext4、
+/// macOS 的 APFS) 上成为不占用磁盘空间的空洞；Windows 上 NTFS 默认不创建稀疏文件，
+/// 空隙以 0 填充并占用空间。同时在 `<输出文件>.map` 中按偏移量顺序写入每个条目的
+/// 偏移量、长度与名称，以制表符分隔。
+pub fn extract_sparse(archive_path: &Path, output_path: &Path, layout: Layout) -> Result<usize> {
+    let mut source = Source::open(archive_path)?;
+    let header = AosV2Hdr::from_reader(&mut source)?;
+    let layout = if extended::is_extended(&header) {
+        extended::extended_layout()
+    } else {
+        layout
+    };
+    source.seek(SeekFrom::Start(0))?;
+    let archive = Archive::from_reader_with_layout(&mut source, layout)?;
+    let base_offset = archive.base_offset()?;
+    let file_len = source.len()?;
+    let mut entries: Vec<_> = archive.entries.iter().enumerate().collect();
+    entries.sort_by_key(|(_, entry)| entry.offset);
+
+    let mut map = String::new();
+    for &(index, entry) in &entries {
+        let name = String::from_utf8_lossy(entry.raw_filename());
+        if let Some(error) = check_entry_bounds(&name, entry, base_offset, file_len) {
+            bail!("{}: {error}", layout.entry_location(index));
+        }
+        let _ = writeln!(map, "{}\t{}\t{name}", { entry.offset }, { entry.length });
+    }
+
+    let mut output = File::create(output_path)
+        .with_context(|| format!("无法创建输出文件: {}", output_path.display()))?;
+    // 先设置文件长度，未写入的部分 (包括末尾的空隙) 不分配磁盘空间
+    output.set_len(archive.data_end())?;
+    for &(_, entry) in &entries {
+        output.seek(SeekFrom::Start(entry.offset as u64))?;
+        copy_range(
+            &mut source,
+            base_offset + entry.offset as u64,
+            entry.length as u64,
+            &mut output,
+        )
+        .with_context(|| format!("无法写入输出文件: {}", output_path.display()))?;
+    }
+    output.flush()?;
+
+    let map_path = PathBuf::from(format!("{}.map", output_path.display()));
+    fs::write(&map_path, map)
+        .with_context(|| format!("无法写入条目映射: {}", map_path.display()))?;
+    Ok(entries.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{TempDir, make_padded_archive};
+
+    const ENTRIES: &[(&str, &[u8])] = &[("a", b"abc"), ("b", b"de")];
+
+    #[test]
+    fn dump_data_starts_at_padded_data_offset() {
+        let dir = TempDir::new("dump-padded");
+        let archive = dir.write("test.aos", &make_padded_archive(ENTRIES, "t.aos", b"ZZZZ"));
+        let output = dir.path().join("data.bin");
+        assert_eq!(dump_data(&archive, &output).unwrap(), 5);
+        assert_eq!(fs::read(output).unwrap(), b"abcde");
+    }
+
+    #[test]
+    fn extract_sparse_reads_from_padded_data_offset() {
+        let dir = TempDir::new("sparse-padded");
+        let archive = dir.write("test.aos", &make_padded_archive(ENTRIES, "t.aos", b"ZZZZ"));
+        let output = dir.path().join("data.bin");
+        assert_eq!(
+            extract_sparse(&archive, &output, Layout::default()).unwrap(),
+            2
+        );
+        assert_eq!(fs::read(&output).unwrap(), b"abcde");
+        let map = fs::read_to_string(dir.path().join("data.bin.map")).unwrap();
+        assert_eq!(map, "0\t3\ta\n3\t2\tb\n");
+    }
+}
@@ -0,0 +1,140 @@
+//! 供程序读取的 JSON Lines 事件流
+//!
+//! 启用后，进度、警告与错误事件以每行一个 JSON 对象的形式写入标准错误，
+//! 标准输出中供人阅读的文本保持不变。
+
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static SILENT: AtomicBool = AtomicBool::new(false);
+
+/// 启用 JSON Lines 事件流
+pub fn enable_json_events() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// 不再输出进度、汇总与警告，只通过错误与退出状态报告结果
+pub fn set_silent() {
+    SILENT.store(true, Ordering::Relaxed);
+}
+
+/// 是否处于静默模式
+pub fn is_silent() -> bool {
+    SILENT.load(Ordering::Relaxed)
+}
+
+/// 库函数每处理完一个条目后报告的进度，供图形界面等嵌入方使用
+#[derive(Debug, Clone, Copy)]
+pub struct Progress<'a> {
+    /// 刚处理完的条目名称
+    pub file: &'a str,
+    /// 已处理的字节数
+    pub bytes_done: u64,
+    /// 需要处理的总字节数
+    pub bytes_total: u64,
+}
+
+/// 事件的级别
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) enum Level {
+    /// 只写入事件流，不打印
+    Debug,
+    #[default]
+    Info,
+    Warning,
+    Error,
+}
+
+/// 单个事件，未设置的字段不会写出
+#[derive(Debug, Default)]
+pub(crate) struct Event<'a> {
+    pub level: Level,
+    /// 事件类型，例如 `extract`、`add`
+    pub event: &'a str,
+    /// 相关的文件或条目名称
+    pub file: Option<&'a str>,
+    /// 条目在数据区内的偏移量，`footer` 事件中为文件内的绝对偏移量
+    pub offset: Option<u64>,
+    pub length: Option<u64>,
+    pub message: Option<&'a str>,
+}
+
+impl Event<'_> {
+    /// 启用事件流时将事件写入标准错误
+    pub(crate) fn emit(&self) {
+        if !ENABLED.load(Ordering::Relaxed) {
+            return;
+        }
+        let level = match self.level {
+            Level::Debug => "debug",
+            Level::Info => "info",
+            Level::Warning => "warning",
+            Level::Error => "error",
+        };
+        let mut line = format!("{{\"level\":\"{level}\",\"event\":{}", quote(self.event));
+        if let Some(file) = self.file {
+            let _ = write!(line, ",\"file\":{}", quote(file));
+        }
+        if let Some(offset) = self.offset {
+            let _ = write!(line, ",\"offset\":{offset}");
+        }
+        if let Some(length) = self.length {
+            let _ = write!(line, ",\"length\":{length}");
+        }
+        if let Some(message) = self.message {
+            let _ = write!(line, ",\"message\":{}", quote(message));
+        }
+        line.push_str("}\n");
+        // 事件流只是附加信息，写入失败不影响命令本身
+        let _ = io::stderr().lock().write_all(line.as_bytes());
+    }
+}
+
+/// 打印警告 (静默模式下不打印)，并在启用事件流时写出对应的事件
+pub(crate) fn warn(event: &str, file: Option<&str>, message: &str) {
+    progress!("  警告: {message}");
+    Event {
+        level: Level::Warning,
+        event,
+        file,
+        message: Some(message),
+        ..Default::default()
+    }
+    .emit();
+}
+
+/// 在启用事件流时写出命令失败的事件
+pub fn emit_error(err: &anyhow::Error) {
+    Event {
+        level: Level::Error,
+        event: "error",
+        message: Some(&format!("{err:#}")),
+        ..Default::default()
+    }
+    .emit();
+}
+
+/// 将字符串转义为 JSON 字符串字面量
+pub(crate) fn quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
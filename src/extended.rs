@@ -0,0 +1,130 @@
+//! 本工具自定义的扩展格式
+//!
+//! 文件头的 `unknown1` 为 [`EXTENDED_MAGIC`]，每个条目在标准的 40 字节之后带有一个
+//! 小端序的 `flags: u32`，用于记录压缩、加密等自定义标记。游戏引擎无法读取扩展格式的封包。
+//! 可选的注释保存在 `archive_name` 字段中封包名称结尾的空字符之后，同样以空字符结尾。
+
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::format::{ARCHIVE_NAME_SIZE, AosV2Entry, AosV2Hdr, Layout};
+
+/// 扩展格式写在 `unknown1` 中的标识，即小端序的 `AOSX`
+pub const EXTENDED_MAGIC: u32 = u32::from_le_bytes(*b"AOSX");
+/// 扩展格式中每个条目的字节数
+pub const EXTENDED_ENTRY_SIZE: usize = std::mem::size_of::<AosV2Entry>() + 4;
+
+/// 扩展格式的目录表布局
+pub fn extended_layout() -> Layout {
+    Layout::with_entry_size(EXTENDED_ENTRY_SIZE).expect("扩展条目大于标准条目")
+}
+
+/// 文件头是否标记为扩展格式
+pub fn is_extended(header: &AosV2Hdr) -> bool {
+    header.unknown1 == EXTENDED_MAGIC
+}
+
+/// 检查文件头与是否按扩展格式读取的选择一致
+pub(crate) fn check_variant(header: &AosV2Hdr, extended: bool) -> Result<()> {
+    match (is_extended(header), extended) {
+        (true, false) => bail!("这是扩展格式的封包，请使用 --extended 读取"),
+        (false, true) => bail!("这不是扩展格式的封包，请去掉 --extended"),
+        _ => Ok(()),
+    }
+}
+
+/// 扩展格式文件头中的注释，没有注释或不是扩展格式时为 `None`
+pub fn read_comment(header: &AosV2Hdr) -> Option<String> {
+    if !is_extended(header) {
+        return None;
+    }
+    let name = &header.archive_name;
+    let start = name.iter().position(|&c| c == 0)? + 1;
+    let rest = &name[start..];
+    let len = rest.iter().position(|&c| c == 0).unwrap_or(rest.len());
+    (len > 0).then(|| String::from_utf8_lossy(&rest[..len]).into_owned())
+}
+
+/// 将注释写在 `archive_name` 中封包名称结尾的空字符之后
+pub(crate) fn write_comment(
+    archive_name: &mut [u8; ARCHIVE_NAME_SIZE],
+    comment: &str,
+) -> Result<()> {
+    let start = archive_name
+        .iter()
+        .position(|&c| c == 0)
+        .unwrap_or(ARCHIVE_NAME_SIZE)
+        + 1;
+    // 注释之后还需要一个结尾的空字符
+    let available = ARCHIVE_NAME_SIZE.saturating_sub(start + 1);
+    if comment.contains('\0') || comment.len() > available {
+        bail!("注释过长或包含空字符，当前封包名称下最多可以写入 {available} 字节",);
+    }
+    archive_name[start..start + comment.len()].copy_from_slice(comment.as_bytes());
+    Ok(())
+}
+
+/// 按目录表顺序读取扩展格式中每个条目的标记
+pub(crate) fn read_flags<R: Read + Seek>(reader: &mut R, count: usize) -> Result<Vec<u32>> {
+    let toc_start = std::mem::size_of::<AosV2Hdr>() as u64;
+    reader.seek(SeekFrom::Start(toc_start))?;
+    let mut buffer = [0u8; EXTENDED_ENTRY_SIZE];
+    let mut flags = Vec::with_capacity(count);
+    for _ in 0..count {
+        reader.read_exact(&mut buffer)?;
+        let field = &buffer[std::mem::size_of::<AosV2Entry>()..];
+        flags.push(u32::from_le_bytes(
+            field.try_into().expect("标记字段为 4 字节"),
+        ));
+    }
+    Ok(flags)
+}
+
+/// 将条目与其标记转换为扩展格式的字节
+pub(crate) fn entry_to_bytes(entry: &AosV2Entry, flags: u32) -> Vec<u8> {
+    let mut bytes = entry.to_bytes();
+    bytes.extend_from_slice(&flags.to_le_bytes());
+    bytes
+}
+
+/// 读取标记文件，每行为以制表符分隔的文件名与标记，标记可以是十进制或 `0x` 开头的十六进制
+pub(crate) fn read_flags_file(path: &Path) -> Result<BTreeMap<String, u32>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("无法读取标记文件: {}", path.display()))?;
+    let mut flags = BTreeMap::new();
+    for (number, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some((name, value)) = line.rsplit_once('\t') else {
+            bail!("标记文件第 {} 行缺少制表符分隔的标记", number + 1);
+        };
+        let value = value.trim();
+        let parsed = match value
+            .strip_prefix("0x")
+            .or_else(|| value.strip_prefix("0X"))
+        {
+            Some(hex) => u32::from_str_radix(hex, 16),
+            None => value.parse(),
+        };
+        let value =
+            parsed.with_context(|| format!("标记文件第 {} 行的标记无效: '{value}'", number + 1))?;
+        flags.insert(name.to_owned(), value);
+    }
+    Ok(flags)
+}
+
+/// 按给定顺序写入标记文件，格式与 [`read_flags_file`] 相同
+pub(crate) fn write_flags_file(path: &Path, flags: &[(String, u32)]) -> Result<()> {
+    let content: String = flags
+        .iter()
+        .map(|(name, value)| format!("{name}\t{value:#010x}\n"))
+        .collect();
+    fs::write(path, content).with_context(|| format!("无法写入标记文件: {}", path.display()))
+}
@@ -0,0 +1,106 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+
+use crate::format::AosV2Entry;
+
+/// 选择要处理的条目
+#[derive(Debug, Default, Clone)]
+pub struct EntryFilter {
+    /// 匹配完整的解码后文件名
+    pub regex: Option<Regex>,
+    /// 条目在目录表中的序号范围
+    pub index: Option<IndexRange>,
+    /// 保留全为 0 的填充条目，默认跳过，见 [`AosV2Entry::is_padding`]
+    pub keep_empty: bool,
+    /// 只选择长度不小于该字节数的条目
+    pub min_size: Option<u64>,
+    /// 只选择长度不大于该字节数的条目
+    pub max_size: Option<u64>,
+}
+
+impl EntryFilter {
+    /// 判断目录表中第 `index` 个条目是否被选中
+    pub fn matches(&self, index: usize, name: &str, entry: &AosV2Entry) -> bool {
+        !self.skips_padding(entry)
+            && self.index.is_none_or(|range| range.contains(index))
+            && !self.excludes_size(entry)
+            && self.regex.as_ref().is_none_or(|regex| regex.is_match(name))
+    }
+
+    /// 条目的长度是否在 `min_size` 与 `max_size` 的范围之外
+    pub fn excludes_size(&self, entry: &AosV2Entry) -> bool {
+        let length = entry.length as u64;
+        self.min_size.is_some_and(|min| length < min)
+            || self.max_size.is_some_and(|max| length > max)
+    }
+
+    /// 条目是否为被跳过的填充条目
+    pub fn skips_padding(&self, entry: &AosV2Entry) -> bool {
+        !self.keep_empty && entry.is_padding()
+    }
+
+    /// 检查序号范围没有超出封包的条目数量
+    pub fn check_count(&self, count: usize) -> Result<()> {
+        match self.index {
+            Some(range) if range.start >= count || range.end.is_some_and(|end| end > count) => {
+                bail!("条目序号范围 {range} 超出了条目数量 {count}")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// 从 0 开始的条目序号范围，不包含 `end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexRange {
+    pub start: usize,
+    /// 为 `None` 时一直到最后一个条目
+    pub end: Option<usize>,
+}
+
+impl IndexRange {
+    pub fn contains(&self, index: usize) -> bool {
+        index >= self.start && self.end.is_none_or(|end| index < end)
+    }
+}
+
+impl FromStr for IndexRange {
+    type Err = anyhow::Error;
+
+    /// 支持 `5`、`0..10`、`0..=9`、`5..` 与 `..10`
+    fn from_str(s: &str) -> Result<Self> {
+        let parse = |part: &str| -> Result<usize> {
+            part.trim()
+                .parse()
+                .with_context(|| format!("无效的条目序号: '{part}'"))
+        };
+        let Some((start, end)) = s.split_once("..") else {
+            let index = parse(s)?;
+            return Ok(Self {
+                start: index,
+                end: Some(index + 1),
+            });
+        };
+        let start = if start.is_empty() { 0 } else { parse(start)? };
+        let end = match end.strip_prefix('=') {
+            Some(last) => Some(parse(last)? + 1),
+            None if end.is_empty() => None,
+            None => Some(parse(end)?),
+        };
+        if end.is_some_and(|end| end <= start) {
+            bail!("条目序号范围 '{s}' 为空");
+        }
+        Ok(Self { start, end })
+    }
+}
+
+impl fmt::Display for IndexRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.end {
+            Some(end) => write!(f, "{}..{end}", self.start),
+            None => write!(f, "{}..", self.start),
+        }
+    }
+}
@@ -0,0 +1,571 @@
+//! aos V2 的二进制结构定义
+
+use std::io::{self, Read};
+
+use anyhow::{Context, Result, bail};
+
+// C++ 代码中的 #pragma pack(1) 在 Rust 中用 #[repr(C, packed)] 实现
+// 我们需要确保内存布局与 C++ 版本完全一致
+
+pub const ARCHIVE_NAME_SIZE: usize = 261;
+pub const FILENAME_SIZE: usize = 32;
+
+#[repr(C, packed)]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AosV2Hdr {
+    pub unknown1: u32,
+    pub data_offset: u32, // 在原始代码中未使用，但封包时可以填充为0
+    pub toc_length: u32,
+    #[cfg_attr(feature = "serde", serde(with = "name_str"))]
+    pub archive_name: [u8; ARCHIVE_NAME_SIZE],
+}
+
+impl AosV2Hdr {
+    pub fn from_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut buffer = [0u8; std::mem::size_of::<Self>()];
+        reader.read_exact(&mut buffer).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!(
+                    "无法读取文件偏移量 0x0 处 {} 字节的文件头: {err}",
+                    buffer.len()
+                ),
+            )
+        })?;
+        // 使用 unsafe 是因为我们正在从原始字节转换，必须确保类型布局正确
+        Ok(unsafe { std::ptr::read(buffer.as_ptr() as *const _) })
+    }
+
+    /// 以替换字符代替无效字节的内嵌封包名称
+    pub fn archive_name_lossy(&self) -> String {
+        let len = self
+            .archive_name
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(ARCHIVE_NAME_SIZE);
+        String::from_utf8_lossy(&self.archive_name[..len]).into_owned()
+    }
+
+    /// 按目录表布局计算的条目数量
+    pub fn entry_count(&self, layout: Layout) -> usize {
+        self.toc_length as usize / layout.entry_size()
+    }
+
+    /// 目录表的结束位置，即文件头与目录表的总大小；`data_offset` 为 0 时数据区从此处开始
+    pub fn toc_end(&self) -> u64 {
+        (std::mem::size_of::<Self>() + self.toc_length as usize) as u64
+    }
+
+    /// 文件头声明的数据区起始偏移量：`data_offset` 非零时采用其值，为 0 时按文件头与目录表大小计算
+    ///
+    /// 声明的偏移量落在文件头或目录表内部时报错。
+    pub fn declared_base_offset(&self) -> Result<u64> {
+        let computed = self.toc_end();
+        match self.data_offset as u64 {
+            0 => Ok(computed),
+            declared if declared < computed => bail!(
+                "data_offset ({declared}) 小于文件头与目录表的总大小 ({computed})，\
+                 可使用 --compute-base 忽略该字段"
+            ),
+            declared => Ok(declared),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let size = std::mem::size_of::<Self>();
+        let mut bytes = Vec::with_capacity(size);
+        // 使用 unsafe 将结构体转换为字节切片
+        unsafe {
+            let ptr = self as *const Self as *const u8;
+            bytes.extend_from_slice(std::slice::from_raw_parts(ptr, size));
+        }
+        bytes
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AosV2Entry {
+    #[cfg_attr(feature = "serde", serde(with = "name_str"))]
+    pub filename: [u8; FILENAME_SIZE],
+    pub offset: u32,
+    pub length: u32,
+}
+
+impl AosV2Entry {
+    pub fn from_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let mut buffer = [0u8; std::mem::size_of::<Self>()];
+        reader.read_exact(&mut buffer)?;
+        Ok(unsafe { std::ptr::read(buffer.as_ptr() as *const _) })
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let size = std::mem::size_of::<Self>();
+        let mut bytes = Vec::with_capacity(size);
+        unsafe {
+            let ptr = self as *const Self as *const u8;
+            bytes.extend_from_slice(std::slice::from_raw_parts(ptr, size));
+        }
+        bytes
+    }
+
+    /// 是否为全为 0 的条目 (空文件名、偏移量与长度均为 0)，部分封包以此将目录表填充到对齐边界
+    pub fn is_padding(&self) -> bool {
+        self.filename.iter().all(|&b| b == 0) && self.offset == 0 && self.length == 0
+    }
+
+    /// 文件名在第一个空字符之前的原始字节
+    pub fn raw_filename(&self) -> &[u8] {
+        let null_pos = self
+            .filename
+            .iter()
+            .position(|&c| c == 0)
+            .unwrap_or(FILENAME_SIZE);
+        &self.filename[..null_pos]
+    }
+
+    /// 按指定的存储方式取出文件名的原始字节
+    pub fn name_bytes(&self, style: NameStyle) -> Result<&[u8]> {
+        match style {
+            NameStyle::Nul => Ok(self.raw_filename()),
+            NameStyle::LengthPrefixed => {
+                let len = self.filename[0] as usize;
+                if len >= FILENAME_SIZE {
+                    bail!(
+                        "长度前缀 {len} 超出文件名字段 (最大 {} 字节)",
+                        FILENAME_SIZE - 1
+                    );
+                }
+                Ok(&self.filename[1..1 + len])
+            }
+        }
+    }
+
+    // 辅助函数，用于从字节数组中获取文件名字符串
+    pub fn get_filename_str(&self) -> Result<String> {
+        String::from_utf8(self.raw_filename().to_vec()).context("文件名包含无效的 UTF-8 字符")
+    }
+}
+
+/// 条目文件名字段的存储方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, palc::ValueEnum)]
+pub enum NameStyle {
+    /// 以空字符结尾
+    #[default]
+    Nul,
+    /// 第一个字节为名称的长度，其后为名称本身
+    LengthPrefixed,
+}
+
+impl NameStyle {
+    /// 将名称写入文件名字段，名称最长 `FILENAME_SIZE - 1` 字节
+    pub fn encode(self, name: &[u8]) -> Result<[u8; FILENAME_SIZE]> {
+        if name.len() >= FILENAME_SIZE {
+            bail!("文件名过长 (最大 {} 字节)", FILENAME_SIZE - 1);
+        }
+        let mut field = [0u8; FILENAME_SIZE];
+        match self {
+            Self::Nul => field[..name.len()].copy_from_slice(name),
+            Self::LengthPrefixed => {
+                field[0] = name.len() as u8;
+                field[1..1 + name.len()].copy_from_slice(name);
+            }
+        }
+        Ok(field)
+    }
+}
+
+/// 文件名的编码
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, palc::ValueEnum)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum NameEncoding {
+    #[default]
+    Utf8,
+    ShiftJis,
+    /// 根据封包中的文件名自动选择，见 [`NameDecoder::resolve`]
+    Auto,
+}
+
+impl NameEncoding {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Utf8 => "UTF-8",
+            Self::ShiftJis => "Shift-JIS",
+            Self::Auto => "auto",
+        }
+    }
+
+    /// 严格解码，遇到无效字符时返回 `None`
+    fn decode_strict(self, raw: &[u8]) -> Option<String> {
+        match self {
+            Self::Utf8 => std::str::from_utf8(raw).ok().map(str::to_owned),
+            Self::ShiftJis => encoding_rs::SHIFT_JIS
+                .decode_without_bom_handling_and_without_replacement(raw)
+                .map(|name| name.into_owned()),
+            Self::Auto => Self::Utf8
+                .decode_strict(raw)
+                .or_else(|| Self::ShiftJis.decode_strict(raw)),
+        }
+    }
+
+    /// 解码，无效字符以替换字符代替
+    fn decode_lossy(self, raw: &[u8]) -> String {
+        match self {
+            Self::ShiftJis => encoding_rs::SHIFT_JIS
+                .decode_without_bom_handling(raw)
+                .0
+                .into_owned(),
+            Self::Utf8 | Self::Auto => String::from_utf8_lossy(raw).into_owned(),
+        }
+    }
+}
+
+/// 自动选择编码时检查的条目数
+pub(crate) const AUTO_SAMPLE_SIZE: usize = 64;
+
+/// UTF-8 的字节顺序标记
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// 文件名的解码方式
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NameDecoder {
+    /// 遇到无效字符时以替换字符代替并给出警告，而不是报错
+    pub lossy: bool,
+    /// 文件名字段的存储方式
+    pub style: NameStyle,
+    /// 文件名的编码，为 [`NameEncoding::Auto`] 时应先调用 [`NameDecoder::resolve`]
+    pub encoding: NameEncoding,
+}
+
+impl NameDecoder {
+    /// 为 [`NameEncoding::Auto`] 选定整个封包使用的编码，其他编码原样返回
+    ///
+    /// 只检查前 [`AUTO_SAMPLE_SIZE`] 个条目，以免每个条目各自选择编码导致结果不一致:
+    /// 任一文件名以 UTF-8 的字节顺序标记开头，或全部是有效的 UTF-8 (包括纯 ASCII) 时
+    /// 选择 UTF-8；否则全部是有效的 Shift-JIS 时选择 Shift-JIS；都不是时报错，
+    /// 指定 `lossy` 时则以 UTF-8 解码并替换无效字符。选择的编码会打印出来。
+    pub fn resolve<'a>(&self, entries: impl IntoIterator<Item = &'a AosV2Entry>) -> Result<Self> {
+        if self.encoding != NameEncoding::Auto {
+            return Ok(*self);
+        }
+        let sample: Vec<&[u8]> = entries
+            .into_iter()
+            .take(AUTO_SAMPLE_SIZE)
+            .filter_map(|entry| entry.name_bytes(self.style).ok())
+            .collect();
+        let all_valid = |encoding: NameEncoding| {
+            sample
+                .iter()
+                .all(|raw| encoding.decode_strict(raw).is_some())
+        };
+        let encoding = if sample.iter().any(|raw| raw.starts_with(UTF8_BOM))
+            || all_valid(NameEncoding::Utf8)
+        {
+            NameEncoding::Utf8
+        } else if all_valid(NameEncoding::ShiftJis) {
+            NameEncoding::ShiftJis
+        } else if self.lossy {
+            NameEncoding::Utf8
+        } else {
+            bail!(
+                "文件名既不是有效的 UTF-8 也不是有效的 Shift-JIS，无法自动选择编码，可使用 --lossy-names 以替换字符继续"
+            );
+        };
+        progress!("文件名编码: {}", encoding.label());
+        crate::events::Event {
+            event: "name_encoding",
+            message: Some(encoding.label()),
+            ..Default::default()
+        }
+        .emit();
+        Ok(Self { encoding, ..*self })
+    }
+
+    /// 解码条目的文件名
+    pub fn decode(&self, entry: &AosV2Entry) -> Result<String> {
+        let raw = entry.name_bytes(self.style)?;
+        match self.decode_raw(raw) {
+            Err(name) if self.lossy => {
+                crate::events::warn(
+                    "lossy_name",
+                    Some(&name),
+                    &format!(
+                        "文件名包含无效字符，已替换为 '{name}' (原始字节: {})",
+                        hex(raw)
+                    ),
+                );
+                Ok(name)
+            }
+            Err(_) => bail!(
+                "文件名包含无效的 {} 字符 (原始字节: {})，可使用 --lossy-names 以替换字符继续",
+                self.encoding.label(),
+                hex(raw)
+            ),
+            Ok(name) => Ok(name),
+        }
+    }
+
+    /// 不打印警告地解码条目的文件名，无效字符以替换字符代替，用于排序等不需要报错的场合；
+    /// 长度前缀无效时退回以空字符结尾的原始字节
+    pub(crate) fn decode_quiet(&self, entry: &AosV2Entry) -> String {
+        match entry.name_bytes(self.style) {
+            Ok(raw) => self.decode_raw(raw).unwrap_or_else(|name| name),
+            Err(_) => String::from_utf8_lossy(entry.raw_filename()).into_owned(),
+        }
+    }
+
+    /// 严格解码原始文件名，不打印警告；失败时返回以替换字符代替无效字符的结果
+    pub(crate) fn decode_raw(&self, raw: &[u8]) -> std::result::Result<String, String> {
+        self.encoding
+            .decode_strict(raw)
+            .ok_or_else(|| self.encoding.decode_lossy(raw))
+    }
+
+    /// 所用编码的名称
+    pub(crate) fn encoding_label(&self) -> &'static str {
+        self.encoding.label()
+    }
+}
+
+/// 将字节格式化为以空格分隔的十六进制
+fn hex(bytes: &[u8]) -> String {
+    let parts: Vec<String> = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    parts.join(" ")
+}
+
+/// 目录表的布局
+///
+/// 部分 .aos 文件的条目在 `length` 之后还带有额外的保留字段，导致条目大小大于
+/// `AosV2Entry` 本身。读取时跳过多出的字节，写入时以 0 填充。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Layout {
+    entry_size: usize,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self {
+            entry_size: std::mem::size_of::<AosV2Entry>(),
+        }
+    }
+}
+
+impl Layout {
+    /// 使用指定的条目大小，不能小于标准条目的大小
+    pub fn with_entry_size(entry_size: usize) -> Result<Self> {
+        let min = std::mem::size_of::<AosV2Entry>();
+        if entry_size < min {
+            bail!("条目大小 {entry_size} 小于标准条目大小 {min} 字节");
+        }
+        Ok(Self { entry_size })
+    }
+
+    /// 目录表中每个条目占用的字节数
+    pub fn entry_size(&self) -> usize {
+        self.entry_size
+    }
+
+    /// 第 `index` 个目录表项在文件中的绝对偏移量
+    pub fn toc_entry_offset(&self, index: usize) -> u64 {
+        (std::mem::size_of::<AosV2Hdr>() + index * self.entry_size) as u64
+    }
+
+    /// 用于错误信息的条目位置描述，例如 `条目 #42 (目录表项位于 0x6b8)`
+    pub(crate) fn entry_location(&self, index: usize) -> String {
+        format!(
+            "条目 #{index} (目录表项位于 {:#x})",
+            self.toc_entry_offset(index)
+        )
+    }
+
+    /// 读取第 `index` 个目录表项，出错时在错误信息中注明条目序号与文件偏移量
+    pub(crate) fn read_entry_at_index<R: Read>(
+        &self,
+        reader: &mut R,
+        index: usize,
+    ) -> io::Result<AosV2Entry> {
+        self.read_entry(reader).map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("无法读取目录表中的{}: {err}", self.entry_location(index)),
+            )
+        })
+    }
+
+    /// 读取一个条目，并跳过其后的保留字段
+    pub fn read_entry<R: Read>(&self, reader: &mut R) -> io::Result<AosV2Entry> {
+        let entry = AosV2Entry::from_reader(reader)?;
+        let extra = self.entry_size - std::mem::size_of::<AosV2Entry>();
+        io::copy(&mut reader.take(extra as u64), &mut io::sink())?;
+        Ok(entry)
+    }
+
+    /// 将条目转换为字节，保留字段以 0 填充
+    pub fn entry_to_bytes(&self, entry: &AosV2Entry) -> Vec<u8> {
+        let mut bytes = entry.to_bytes();
+        bytes.resize(self.entry_size, 0);
+        bytes
+    }
+}
+
+/// 将以空字符结尾的定长字节数组按字符串序列化
+///
+/// 序列化时按 [`NameEncoding::Auto`] 逐个名称解码: 有效的 UTF-8 按 UTF-8，否则按 Shift-JIS，
+/// 都无效时以替换字符代替，因此不会因名称的编码而失败。需要整个封包统一编码时应使用
+/// [`NameDecoder::resolve`] 与 [`NameDecoder::decode`] 得到的名称。反序列化时以 UTF-8 存储。
+#[cfg(feature = "serde")]
+mod name_str {
+    use serde::{Deserialize, Deserializer, Serializer, de::Error as _};
+
+    use super::NameEncoding;
+
+    pub fn serialize<S: Serializer, const N: usize>(
+        bytes: &[u8; N],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let len = bytes.iter().position(|&c| c == 0).unwrap_or(N);
+        let raw = &bytes[..len];
+        let name = NameEncoding::Auto
+            .decode_strict(raw)
+            .unwrap_or_else(|| NameEncoding::Auto.decode_lossy(raw));
+        serializer.serialize_str(&name)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(
+        deserializer: D,
+    ) -> Result<[u8; N], D::Error> {
+        let s = String::deserialize(deserializer)?;
+        // 需要为结尾的空字符留出一个字节
+        if s.len() >= N {
+            return Err(D::Error::custom(format!(
+                "名称 '{s}' 过长 (最大 {} 字节)",
+                N - 1
+            )));
+        }
+        let mut bytes = [0u8; N];
+        bytes[..s.len()].copy_from_slice(s.as_bytes());
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(style: NameStyle, name: &[u8]) -> AosV2Entry {
+        AosV2Entry {
+            filename: style.encode(name).unwrap(),
+            offset: 0,
+            length: 0,
+        }
+    }
+
+    #[test]
+    fn name_styles_round_trip() {
+        let longest = [b'x'; FILENAME_SIZE - 1];
+        for style in [NameStyle::Nul, NameStyle::LengthPrefixed] {
+            for name in [&b"a"[..], b"dir/script.txt", &longest] {
+                let entry = entry(style, name);
+                assert_eq!(entry.name_bytes(style).unwrap(), name, "{style:?}");
+            }
+            assert!(style.encode(&[b'x'; FILENAME_SIZE]).is_err());
+        }
+    }
+
+    #[test]
+    fn length_prefix_is_the_first_byte() {
+        let entry = entry(NameStyle::LengthPrefixed, b"abc");
+        assert_eq!(entry.filename[..5], *b"\x03abc\0");
+        // 以空字符结尾的方式读取时长度字节会成为名称的一部分
+        assert_eq!(entry.name_bytes(NameStyle::Nul).unwrap(), b"\x03abc");
+
+        let mut entry = entry;
+        entry.filename[0] = FILENAME_SIZE as u8;
+        assert!(entry.name_bytes(NameStyle::LengthPrefixed).is_err());
+    }
+
+    fn resolve(names: &[&[u8]], lossy: bool) -> Result<NameEncoding> {
+        let entries: Vec<_> = names
+            .iter()
+            .map(|name| entry(NameStyle::Nul, name))
+            .collect();
+        let decoder = NameDecoder {
+            lossy,
+            encoding: NameEncoding::Auto,
+            ..Default::default()
+        };
+        Ok(decoder.resolve(&entries)?.encoding)
+    }
+
+    #[test]
+    fn auto_encoding_picks_one_encoding_per_archive() {
+        assert_eq!(
+            resolve(&[b"a.png", b"script.txt"], false).unwrap(),
+            NameEncoding::Utf8
+        );
+        assert_eq!(
+            resolve(&["立ち絵.png".as_bytes(), b"a.txt"], false).unwrap(),
+            NameEncoding::Utf8
+        );
+        // "立ち絵.png" 与 "背景.png" 的 Shift-JIS 编码
+        let sjis: &[&[u8]] = &[b"\x97\xa7\x82\xbf\x8aG.png", b"\x94w\x8ci.png", b"a.txt"];
+        assert_eq!(resolve(sjis, false).unwrap(), NameEncoding::ShiftJis);
+        // 字节顺序标记优先
+        assert_eq!(
+            resolve(&[b"\xef\xbb\xbfa", sjis[0]], true).unwrap(),
+            NameEncoding::Utf8
+        );
+        let decoder = NameDecoder {
+            encoding: NameEncoding::ShiftJis,
+            ..Default::default()
+        };
+        assert_eq!(
+            decoder.decode(&entry(NameStyle::Nul, sjis[0])).unwrap(),
+            "立ち絵.png"
+        );
+    }
+
+    #[test]
+    fn auto_encoding_fails_without_lossy_when_neither_decodes() {
+        // 0xff 既不是 UTF-8 也不是 Shift-JIS 的有效字节
+        let names: &[&[u8]] = &[b"\xffa", b"b"];
+        let err = resolve(names, false).unwrap_err();
+        assert!(err.to_string().contains("--lossy-names"), "{err}");
+        assert_eq!(resolve(names, true).unwrap(), NameEncoding::Utf8);
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod serde_tests {
+    use super::*;
+
+    fn entry_json(name: &[u8]) -> serde_json::Value {
+        let entry = AosV2Entry {
+            filename: NameStyle::Nul.encode(name).unwrap(),
+            offset: 4,
+            length: 8,
+        };
+        serde_json::to_value(&entry).unwrap()
+    }
+
+    #[test]
+    fn shift_jis_names_serialize_as_decoded_text() {
+        let (name, _, _) = encoding_rs::SHIFT_JIS.encode("テスト.txt");
+        assert_eq!(entry_json(&name)["filename"], "テスト.txt");
+        assert_eq!(
+            entry_json("ui/логотип.png".as_bytes())["filename"],
+            "ui/логотип.png"
+        );
+    }
+
+    #[test]
+    fn undecodable_names_serialize_with_replacement_characters() {
+        assert_eq!(entry_json(b"a\x80\xff")["filename"], "a\u{fffd}\u{fffd}");
+    }
+}
@@ -0,0 +1,79 @@
+use std::io::{self, Read, Seek, SeekFrom};
+
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::sha256::Sha256;
+
+/// 流式计算哈希时每次读取的块大小
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// `list --checksum` 与 `hash-manifest` 支持的校验算法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, palc::ValueEnum)]
+pub enum ChecksumAlgo {
+    Crc32,
+    Xxh3,
+    Sha256,
+}
+
+impl ChecksumAlgo {
+    /// 十六进制校验值的长度
+    pub fn hex_len(self) -> usize {
+        match self {
+            Self::Crc32 => 8,
+            Self::Xxh3 => 16,
+            Self::Sha256 => 64,
+        }
+    }
+}
+
+/// 以固定大小的块流式读取 `[start, start + len)` 范围内的数据，依次交给 `update`
+fn for_each_chunk<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    len: u64,
+    mut update: impl FnMut(&[u8]),
+) -> io::Result<()> {
+    reader.seek(SeekFrom::Start(start))?;
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut remaining = len;
+    while remaining > 0 {
+        let chunk = remaining.min(CHUNK_SIZE as u64) as usize;
+        reader.read_exact(&mut buffer[..chunk])?;
+        update(&buffer[..chunk]);
+        remaining -= chunk as u64;
+    }
+    Ok(())
+}
+
+/// 以固定大小的块流式读取 `[start, start + len)` 范围内的数据并计算 xxh3 摘要
+pub fn hash_range<R: Read + Seek>(reader: &mut R, start: u64, len: u64) -> io::Result<u64> {
+    let mut hasher = Xxh3::new();
+    for_each_chunk(reader, start, len, |chunk| hasher.update(chunk))?;
+    Ok(hasher.digest())
+}
+
+/// 流式计算 `[start, start + len)` 范围内数据的校验值，以十六进制字符串返回
+pub fn checksum_range<R: Read + Seek>(
+    reader: &mut R,
+    start: u64,
+    len: u64,
+    algo: ChecksumAlgo,
+) -> io::Result<String> {
+    match algo {
+        ChecksumAlgo::Crc32 => {
+            let mut hasher = crc32fast::Hasher::new();
+            for_each_chunk(reader, start, len, |chunk| hasher.update(chunk))?;
+            Ok(format!("{:08x}", hasher.finalize()))
+        }
+        ChecksumAlgo::Xxh3 => Ok(format!("{:016x}", hash_range(reader, start, len)?)),
+        ChecksumAlgo::Sha256 => {
+            let mut hasher = Sha256::new();
+            for_each_chunk(reader, start, len, |chunk| hasher.update(chunk))?;
+            Ok(hasher
+                .finalize()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect())
+        }
+    }
+}
@@ -0,0 +1,159 @@
+use std::{
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    archive::Archive,
+    format::{Layout, NameDecoder},
+    source::Source,
+    validate::check_entry_bounds,
+};
+
+/// 每次从封包中读取的块大小，必须是每行字节数的整数倍
+const CHUNK_SIZE: usize = 64 * 1024;
+/// 每行显示的字节数
+const BYTES_PER_LINE: usize = 16;
+
+/// 十六进制转储选项
+#[derive(Debug, Default)]
+pub struct HexdumpOptions {
+    /// 跳过条目开头的字节数
+    pub skip: u64,
+    /// 最多显示的字节数，为 `None` 时显示到条目末尾
+    pub length: Option<u64>,
+    /// 目录表布局
+    pub layout: Layout,
+    /// 文件名解码方式
+    pub names: NameDecoder,
+}
+
+/// 以 `hexdump -C` 的格式将条目数据打印到标准输出
+///
+/// 数据按块读取，不会将整个条目载入内存。偏移量相对于条目开头。
+pub fn hexdump_entry(archive_path: &Path, name: &str, options: &HexdumpOptions) -> Result<()> {
+    let mut stdout = BufWriter::new(io::stdout().lock());
+    let result =
+        write_hexdump(archive_path, name, options, &mut stdout).and_then(|()| Ok(stdout.flush()?));
+    match result {
+        // 输出被管道另一端提前关闭 (例如 `| head`) 时安静地结束
+        Err(err)
+            if err
+                .downcast_ref::<io::Error>()
+                .is_some_and(|err| err.kind() == io::ErrorKind::BrokenPipe) =>
+        {
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+/// 将条目数据的十六进制转储写入 `out`
+fn write_hexdump<W: Write>(
+    archive_path: &Path,
+    name: &str,
+    options: &HexdumpOptions,
+    out: &mut W,
+) -> Result<()> {
+    let mut source = Source::open(archive_path)?;
+    let archive = Archive::from_reader_with_layout(&mut source, options.layout)?;
+    let base_offset = archive.base_offset()?;
+    let file_len = source.len()?;
+
+    let names = options.names.resolve(&archive.entries)?;
+    let mut found = None;
+    for (index, entry) in archive.entries.iter().enumerate() {
+        let decoded = names
+            .decode(entry)
+            .with_context(|| options.layout.entry_location(index))?;
+        if decoded == name {
+            found = Some((index, entry));
+            break;
+        }
+    }
+    let Some((index, entry)) = found else {
+        bail!("封包中不存在条目 '{name}'");
+    };
+    if let Some(error) = check_entry_bounds(name, entry, base_offset, file_len) {
+        bail!("{}: {error}", options.layout.entry_location(index));
+    }
+
+    let entry_len = entry.length as u64;
+    let start = options.skip.min(entry_len);
+    let end = options.length.map_or(entry_len, |length| {
+        start.saturating_add(length).min(entry_len)
+    });
+
+    dump(
+        &mut source,
+        base_offset + entry.offset as u64,
+        start,
+        end,
+        out,
+    )?;
+    Ok(())
+}
+
+/// 转储条目中 `[start, end)` 范围内的数据，`entry_start` 为条目在文件中的绝对偏移量
+pub(crate) fn dump<W: Write>(
+    source: &mut Source,
+    entry_start: u64,
+    start: u64,
+    end: u64,
+    out: &mut W,
+) -> io::Result<()> {
+    let mut buffer = vec![0u8; CHUNK_SIZE];
+    let mut position = start;
+    while position < end {
+        let chunk = ((end - position) as usize).min(CHUNK_SIZE);
+        source.read_exact_at(&mut buffer[..chunk], entry_start + position)?;
+        for (index, line) in buffer[..chunk].chunks(BYTES_PER_LINE).enumerate() {
+            write_line(out, position + (index * BYTES_PER_LINE) as u64, line)?;
+        }
+        position += chunk as u64;
+    }
+    writeln!(out, "{end:08x}")
+}
+
+fn write_line<W: Write>(out: &mut W, offset: u64, bytes: &[u8]) -> io::Result<()> {
+    write!(out, "{offset:08x} ")?;
+    for index in 0..BYTES_PER_LINE {
+        if index % 8 == 0 {
+            write!(out, " ")?;
+        }
+        match bytes.get(index) {
+            Some(byte) => write!(out, "{byte:02x} ")?,
+            None => write!(out, "   ")?,
+        }
+    }
+    let ascii: String = bytes
+        .iter()
+        .map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' {
+                b as char
+            } else {
+                '.'
+            }
+        })
+        .collect();
+    writeln!(out, " |{ascii}|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{TempDir, make_padded_archive};
+
+    #[test]
+    fn dumps_entry_data_in_padded_archive() {
+        let dir = TempDir::new("hexdump-padded");
+        let bytes = make_padded_archive(&[("a", b"abc"), ("b", b"de")], "t.aos", b"ZZZZ");
+        let path = dir.write("test.aos", &bytes);
+        let mut out = Vec::new();
+        write_hexdump(&path, "b", &HexdumpOptions::default(), &mut out).unwrap();
+        let expected = format!("00000000  64 65 {:44}|de|\n00000002\n", "");
+        assert_eq!(String::from_utf8(out).unwrap(), expected);
+    }
+}
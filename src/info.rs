@@ -0,0 +1,89 @@
+use std::{
+    io::{Seek, SeekFrom},
+    path::Path,
+};
+
+use anyhow::Result;
+
+use crate::{
+    archive::{Archive, footer_range},
+    extended,
+    format::Layout,
+    output::{InfoDocument, OutputFormat, render},
+    source::Source,
+    validate::check_file_size,
+};
+
+/// 查看文件头信息的选项
+#[derive(Debug, Default)]
+pub struct InfoOptions {
+    /// 输出格式
+    pub format: OutputFormat,
+    /// 目录表布局
+    pub layout: Layout,
+}
+
+/// 打印 .aos 文件的文件头信息
+pub fn info_archive(archive_path: &Path, options: &InfoOptions) -> Result<()> {
+    let mut file = Source::open(archive_path)?;
+    let file_size = file.len()?;
+    let mut archive = Archive::from_reader_with_layout(&mut file, options.layout)?;
+    // 扩展格式的条目大小固定，按其布局重新读取目录表
+    if extended::is_extended(&archive.header) && options.layout != extended::extended_layout() {
+        file.seek(SeekFrom::Start(0))?;
+        archive = Archive::from_reader_with_layout(&mut file, extended::extended_layout())?;
+    }
+    let header = &archive.header;
+    // data_offset 无效时按文件头与目录表大小计算尾部数据，并在下方给出警告
+    let base_offset = archive.base_offset();
+    let footer = footer_range(
+        *base_offset.as_ref().unwrap_or(&header.toc_end()) + archive.data_end(),
+        file_size,
+    );
+    let document = InfoDocument {
+        archive_name: header.archive_name_lossy(),
+        unknown1: header.unknown1,
+        data_offset: header.data_offset,
+        toc_length: header.toc_length,
+        entry_count: archive.entries.len(),
+        file_size,
+        footer_length: footer.map_or(0, |(_, length)| length),
+        comment: extended::read_comment(header),
+    };
+
+    if options.format != OutputFormat::Text {
+        println!("{}", render(&document, options.format)?);
+        return Ok(());
+    }
+
+    println!("封包名称: {}", document.archive_name);
+    if extended::is_extended(header) {
+        println!("unknown1: {:#010x} (扩展格式)", document.unknown1);
+    } else {
+        println!("unknown1: {:#010x}", document.unknown1);
+    }
+    if let Some(comment) = &document.comment {
+        println!("注释: {comment}");
+    }
+    match &base_offset {
+        Ok(base_offset) if *base_offset > header.toc_end() => println!(
+            "data_offset: {} (目录表之后有 {} 字节填充)",
+            document.data_offset,
+            base_offset - header.toc_end()
+        ),
+        _ => println!("data_offset: {}", document.data_offset),
+    }
+    println!("toc_length: {}", document.toc_length);
+    println!("条目数量: {}", document.entry_count);
+    println!("文件大小: {} 字节", document.file_size);
+    if let Some((offset, length)) = footer {
+        println!("尾部数据: {length} 字节 (偏移量 {offset})");
+    }
+    if let Err(err) = &base_offset {
+        println!("警告: {err}");
+    }
+    if let Some(warning) = check_file_size(file_size) {
+        println!("警告: {warning}");
+    }
+    Ok(())
+}
@@ -0,0 +1,99 @@
+//! .aos 文件的解包 / 封包
+
+/// 打印进度与汇总信息，静默模式 (见 [`set_silent`]) 下不输出
+#[macro_export]
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        if !$crate::is_silent() {
+            println!($($arg)*);
+        }
+    };
+}
+
+mod add;
+mod archive;
+mod batch;
+#[cfg(feature = "tui")]
+mod browse;
+mod cancel;
+pub mod config;
+mod diff;
+mod dump;
+mod events;
+pub mod extended;
+mod filter;
+pub mod format;
+mod hash;
+mod hexdump;
+mod info;
+mod limit;
+mod list;
+mod lock;
+mod magic;
+mod manifest;
+mod meta;
+mod normalize;
+pub mod output;
+mod pack;
+mod pipeline;
+mod plan;
+mod probe;
+mod read_at;
+mod rebuild;
+mod rename;
+mod repack;
+mod retry;
+mod sha256;
+mod size;
+mod source;
+mod split;
+mod stream;
+#[cfg(any(test, feature = "testutil"))]
+pub mod testutil;
+mod text;
+mod threads;
+mod timeout;
+mod translit;
+mod unpack;
+mod validate;
+#[cfg(feature = "watch")]
+mod watch;
+mod zip;
+
+pub use add::{AddOptions, add_entry};
+pub use archive::{Archive, SortKey, toc_entries};
+pub use batch::{BatchFailure, batch_exit_status};
+#[cfg(feature = "tui")]
+pub use browse::{BrowseOptions, browse_archive};
+pub use cancel::Cancelled;
+pub use diff::{diff_archives, diff_pack_plan, verify_against};
+pub use dump::{dump_data, extract_sparse};
+pub use events::{Progress, emit_error, enable_json_events, is_silent, set_silent};
+pub use filter::{EntryFilter, IndexRange};
+pub use hash::ChecksumAlgo;
+pub use hexdump::{HexdumpOptions, hexdump_entry};
+pub use info::{InfoOptions, info_archive};
+pub use limit::default_max_open_files;
+pub use list::{ListOptions, list_archive};
+pub use lock::disable_locking;
+pub use manifest::{HashManifestOptions, hash_manifest};
+pub use normalize::{NormalizeOptions, NormalizeOrder, normalize_archive};
+pub use pack::{PackOptions, estimate_pack, pack_directory, pack_directory_with_progress};
+pub use probe::{ProbeClass, ProbeReport, probe_file};
+pub use rebuild::rebuild_toc;
+pub use repack::{RepackOptions, repack_archive};
+pub use size::ByteSize;
+pub use source::is_url;
+pub use split::{SplitOptions, split_archive};
+pub use stream::{PackStream, pack_stream};
+pub use threads::{MAX_THREADS, Threads};
+pub use unpack::{
+    UnpackOptions, compare_with_dir, report_duplicate_names, unpack_archive,
+    unpack_archive_with_progress,
+};
+pub use validate::{
+    GapReport, ValidateOptions, ValidationReport, quick_validate, quick_validate_with,
+    validate_archive, validate_batch,
+};
+#[cfg(feature = "watch")]
+pub use watch::watch_directory;
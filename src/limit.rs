@@ -0,0 +1,94 @@
+//! 限制同时打开的文件句柄数量
+
+use std::sync::{Condvar, Mutex};
+
+/// 无法检测系统限制时使用的默认值
+const FALLBACK_LIMIT: usize = 256;
+
+/// 计数信号量，每个许可对应一个可以同时打开的文件句柄
+pub(crate) struct OpenFileLimit {
+    available: Mutex<usize>,
+    released: Condvar,
+}
+
+/// 持有期间占用一个许可，离开作用域时归还
+pub(crate) struct Permit<'a>(&'a OpenFileLimit);
+
+impl OpenFileLimit {
+    pub(crate) fn new(limit: usize) -> Self {
+        Self {
+            available: Mutex::new(limit.max(1)),
+            released: Condvar::new(),
+        }
+    }
+
+    /// 等待直到有可用的许可
+    pub(crate) fn acquire(&self) -> Permit<'_> {
+        let mut available = self.available.lock().unwrap_or_else(|e| e.into_inner());
+        while *available == 0 {
+            available = self
+                .released
+                .wait(available)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        *available -= 1;
+        Permit(self)
+    }
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        *self.0.available.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+        self.0.released.notify_one();
+    }
+}
+
+/// 默认的同时打开文件数上限，为系统软限制的一半，为标准输入输出与其他句柄留出余量
+///
+/// 目前只在 Linux 上通过 `/proc/self/limits` 检测，其他平台使用固定值。
+pub fn default_max_open_files() -> usize {
+    soft_limit().map_or(FALLBACK_LIMIT, |limit| (limit / 2).max(1))
+}
+
+fn soft_limit() -> Option<usize> {
+    let limits = std::fs::read_to_string("/proc/self/limits").ok()?;
+    let line = limits
+        .lines()
+        .find(|line| line.starts_with("Max open files"))?;
+    line["Max open files".len()..]
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        thread,
+        time::Duration,
+    };
+
+    use super::*;
+
+    #[test]
+    fn permits_bound_concurrent_holders() {
+        let limit = OpenFileLimit::new(2);
+        let (open, most) = (AtomicUsize::new(0), AtomicUsize::new(0));
+        thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..4 {
+                        let _permit = limit.acquire();
+                        let now = open.fetch_add(1, Ordering::SeqCst) + 1;
+                        most.fetch_max(now, Ordering::SeqCst);
+                        thread::sleep(Duration::from_millis(1));
+                        open.fetch_sub(1, Ordering::SeqCst);
+                    }
+                });
+            }
+        });
+        assert_eq!(most.into_inner(), 2);
+    }
+}
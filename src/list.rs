@@ -0,0 +1,294 @@
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    archive::{Archive, SortKey},
+    events::is_silent,
+    filter::EntryFilter,
+    format::{Layout, NameDecoder},
+    hash::{ChecksumAlgo, checksum_range},
+    output::{EntryInfo, ListDocument, OutputFormat, render},
+    source::Source,
+};
+
+/// 列出条目的选项
+#[derive(Debug, Default)]
+pub struct ListOptions {
+    /// 每行只输出一个文件名，不输出表头、大小与汇总信息，仅对文本格式有效
+    pub only_names: bool,
+    /// 与 `only_names` 同时使用时以 NUL 字节而非换行分隔文件名
+    pub print0: bool,
+    /// 输出格式
+    pub format: OutputFormat,
+    /// 只列出被选中的条目
+    pub filter: EntryFilter,
+    /// 额外将目录表写入该 CSV 文件
+    pub csv: Option<PathBuf>,
+    /// 目录表布局
+    pub layout: Layout,
+    /// 文件名解码方式
+    pub names: NameDecoder,
+    /// 按该依据排序输出，为 `None` 时按目录表顺序
+    pub sort: Option<SortKey>,
+    /// 与 `sort` 同时使用时降序排列
+    pub descending: bool,
+    /// 读取每个条目的数据并计算校验值，为 `None` 时只读取目录表
+    pub checksum: Option<ChecksumAlgo>,
+    /// 将不超过该字节数的条目数据以 Base64 编码写入 JSON 输出，更大的条目只保留偏移量与长度。
+    /// 只能与 [`OutputFormat::Json`] 同时使用，需要启用 `json` 特性
+    pub inline_data: Option<u64>,
+}
+
+/// 列出 .aos 文件中的条目
+pub fn list_archive(archive_path: &Path, options: &ListOptions) -> Result<()> {
+    let mut file = Source::open(archive_path)?;
+    let archive = Archive::from_reader_with_layout(&mut file, options.layout)?;
+    options.filter.check_count(archive.entries.len())?;
+    if options.inline_data.is_some() && options.format != OutputFormat::Json {
+        bail!("内嵌条目数据只能与 JSON 输出格式同时使用");
+    }
+    let Listing {
+        entries,
+        padding,
+        oversized,
+        size_filtered,
+    } = collect_entries(&mut file, &archive, options)?;
+
+    if let Some(csv_path) = &options.csv {
+        write_csv(csv_path, &entries)
+            .with_context(|| format!("无法写入 CSV 文件: {}", csv_path.display()))?;
+    }
+
+    // 标准输出只包含文档本身，注意写入标准错误
+    if !is_silent() {
+        if oversized > 0 {
+            eprintln!("  注意: {oversized} 个条目超过内嵌大小上限，只输出了偏移量与长度");
+        }
+        if size_filtered > 0 && options.format != OutputFormat::Text {
+            eprintln!("  注意: 按大小筛选排除了 {size_filtered} 个条目");
+        }
+    }
+    if options.format != OutputFormat::Text {
+        println!("{}", render(&ListDocument { entries }, options.format)?);
+        return Ok(());
+    }
+
+    if options.only_names {
+        let separator = if options.print0 { b'\0' } else { b'\n' };
+        let mut stdout = io::stdout().lock();
+        for entry in &entries {
+            stdout.write_all(entry.name.as_bytes())?;
+            stdout.write_all(&[separator])?;
+        }
+        stdout.flush()?;
+        return Ok(());
+    }
+
+    // 中文表头每个字符占两列宽度
+    let mut total = 0u64;
+    if let Some(algo) = options.checksum {
+        let width = algo.hex_len();
+        println!(
+            "{:>7} {:>8}  {:<header_width$}  名称",
+            "偏移量",
+            "长度",
+            "校验值",
+            header_width = width - 3
+        );
+        for entry in &entries {
+            let checksum = entry.checksum.as_deref().unwrap_or_default();
+            println!(
+                "{:>10} {:>10}  {checksum:<width$}  {}",
+                entry.offset, entry.length, entry.name
+            );
+            total += entry.length as u64;
+        }
+    } else {
+        println!("{:>7} {:>8}  名称", "偏移量", "长度");
+        for entry in &entries {
+            println!("{:>10} {:>10}  {}", entry.offset, entry.length, entry.name);
+            total += entry.length as u64;
+        }
+    }
+    println!("共 {} 个条目，{total} 字节", entries.len());
+    if padding > 0 {
+        progress!("  注意: 跳过了 {padding} 个全为 0 的填充条目，可使用 --keep-empty-entries 列出");
+    }
+    if size_filtered > 0 {
+        progress!("  注意: 按大小筛选排除了 {size_filtered} 个条目");
+    }
+    Ok(())
+}
+
+/// 按 `options` 选出并排序的条目
+struct Listing {
+    entries: Vec<EntryInfo>,
+    /// 跳过的填充条目数
+    padding: usize,
+    /// 超过内嵌大小上限、未内嵌数据的条目数
+    oversized: usize,
+    /// 按大小筛选排除的条目数
+    size_filtered: usize,
+}
+
+fn collect_entries(file: &mut Source, archive: &Archive, options: &ListOptions) -> Result<Listing> {
+    let names = options.names.resolve(&archive.entries)?;
+    let order = match options.sort {
+        Some(key) => archive.sorted_indices(key, options.descending, &names),
+        None => (0..archive.entries.len()).collect(),
+    };
+    let mut entries = Vec::new();
+    let mut padding = 0usize;
+    let mut oversized = 0usize;
+    let mut size_filtered = 0usize;
+    for index in order {
+        let entry = &archive.entries[index];
+        if options.filter.skips_padding(entry) {
+            padding += 1;
+            continue;
+        }
+        if options.filter.excludes_size(entry) {
+            size_filtered += 1;
+        }
+        let name = names
+            .decode(entry)
+            .with_context(|| options.layout.entry_location(index))?;
+        if options.filter.matches(index, &name, entry) {
+            let checksum = options
+                .checksum
+                .map(|algo| {
+                    let start = archive.entry_file_offset(entry)?;
+                    checksum_range(file, start, entry.length as u64, algo)
+                        .with_context(|| format!("无法读取条目 '{name}'"))
+                })
+                .transpose()?;
+            let data = match options.inline_data {
+                Some(max) if entry.length as u64 <= max => {
+                    let mut buffer = vec![0u8; entry.length as usize];
+                    file.read_exact_at(&mut buffer, archive.entry_file_offset(entry)?)
+                        .with_context(|| format!("无法读取条目 '{name}'"))?;
+                    Some(encode_base64(&buffer)?)
+                }
+                Some(_) => {
+                    oversized += 1;
+                    None
+                }
+                None => None,
+            };
+            entries.push(EntryInfo {
+                name,
+                offset: entry.offset,
+                length: entry.length,
+                checksum,
+                data,
+            });
+        }
+    }
+
+    Ok(Listing {
+        entries,
+        padding,
+        oversized,
+        size_filtered,
+    })
+}
+
+/// 将目录表写为带表头的 CSV，文件名以 UTF-8 写入
+#[cfg(feature = "csv")]
+fn write_csv(path: &Path, entries: &[EntryInfo]) -> Result<()> {
+    let mut writer = csv::Writer::from_path(path)?;
+    writer.write_record(["name", "offset", "length"])?;
+    for entry in entries {
+        writer.write_record([
+            entry.name.as_str(),
+            &entry.offset.to_string(),
+            &entry.length.to_string(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(not(feature = "csv"))]
+fn write_csv(_path: &Path, _entries: &[EntryInfo]) -> Result<()> {
+    anyhow::bail!("此构建未启用 csv 特性")
+}
+
+#[cfg(feature = "json")]
+fn encode_base64(data: &[u8]) -> Result<String> {
+    use base64::Engine;
+
+    Ok(base64::engine::general_purpose::STANDARD.encode(data))
+}
+
+#[cfg(not(feature = "json"))]
+fn encode_base64(_data: &[u8]) -> Result<String> {
+    bail!("此构建未启用 json 特性")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{Defect, TempDir, make_corrupt_archive, make_padded_archive};
+
+    #[test]
+    fn checksums_cover_entry_data_in_padded_archive() {
+        let dir = TempDir::new("list-padded");
+        let bytes = make_padded_archive(&[("a", b"abc"), ("b", b"de")], "t.aos", b"ZZZZ");
+        let path = dir.write("test.aos", &bytes);
+        let mut file = Source::open(&path).unwrap();
+        let archive = Archive::from_reader(&mut file).unwrap();
+        let options = ListOptions {
+            checksum: Some(ChecksumAlgo::Crc32),
+            ..Default::default()
+        };
+        let listing = collect_entries(&mut file, &archive, &options).unwrap();
+        let checksums: Vec<_> = listing
+            .entries
+            .iter()
+            .map(|entry| (entry.offset, entry.checksum.as_deref().unwrap()))
+            .collect();
+        let crc32 = |data: &[u8]| format!("{:08x}", crc32fast::hash(data));
+        assert_eq!(
+            checksums,
+            [(0, crc32(b"abc").as_str()), (3, crc32(b"de").as_str())]
+        );
+    }
+
+    #[test]
+    fn trailing_zero_entries_are_skipped_unless_kept() {
+        let dir = TempDir::new("list-zero-entries");
+        let bytes = make_corrupt_archive(
+            &[("a", b"abc"), ("b", b"de")],
+            "t.aos",
+            Defect::TrailingZeroEntries,
+        );
+        let path = dir.write("test.aos", &bytes);
+        let mut file = Source::open(&path).unwrap();
+        let archive = Archive::from_reader(&mut file).unwrap();
+        assert_eq!(archive.entries.len(), 4);
+
+        let names = |listing: &Listing| -> Vec<String> {
+            listing
+                .entries
+                .iter()
+                .map(|entry| entry.name.clone())
+                .collect()
+        };
+        let listing = collect_entries(&mut file, &archive, &ListOptions::default()).unwrap();
+        assert_eq!(
+            (names(&listing), listing.padding),
+            (vec!["a".into(), "b".into()], 2)
+        );
+
+        let mut options = ListOptions::default();
+        options.filter.keep_empty = true;
+        let listing = collect_entries(&mut file, &archive, &options).unwrap();
+        assert_eq!(listing.padding, 0);
+        assert_eq!(names(&listing), ["a", "b", "", ""]);
+    }
+}
@@ -0,0 +1,94 @@
+//! 打开 .aos 文件时的建议性文件锁
+//!
+//! 读取封包的命令持有共享锁，修改封包的命令持有排他锁，锁在文件关闭时释放。
+//! 建议锁只约束同样加锁的进程，不支持加锁的文件系统 (例如部分网络文件系统) 上可以关闭。
+
+use std::{
+    fs::File,
+    io,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use anyhow::{Context, Result};
+
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// 不再为打开的 .aos 文件加锁
+pub fn disable_locking() {
+    DISABLED.store(true, Ordering::Relaxed);
+}
+
+/// 以只读方式打开文件并加共享锁，其他进程持有排他锁时等待其释放
+pub(crate) fn open_shared(path: &Path) -> Result<File> {
+    let file = File::open(path).with_context(|| format!("无法打开文件: {}", path.display()))?;
+    lock_shared(&file, path)?;
+    Ok(file)
+}
+
+/// 为已打开的文件加共享锁
+pub(crate) fn lock_shared(file: &File, path: &Path) -> Result<()> {
+    if DISABLED.load(Ordering::Relaxed) {
+        return Ok(());
+    }
+    match file.try_lock_shared() {
+        Ok(()) => Ok(()),
+        Err(std::fs::TryLockError::WouldBlock) => {
+            progress!("等待其他进程释放文件锁: {}", path.display());
+            file.lock_shared().map_err(|err| lock_error(err, path))
+        }
+        Err(std::fs::TryLockError::Error(err)) => Err(lock_error(err, path)),
+    }
+}
+
+/// 以只读方式打开文件并加排他锁，用于之后会替换该文件的命令
+///
+/// 等待期间文件可能已被持有锁的进程替换为新文件，此时重新打开，保证读取到的是最新内容。
+pub(crate) fn open_exclusive(path: &Path) -> Result<File> {
+    loop {
+        let file = File::open(path).with_context(|| format!("无法打开文件: {}", path.display()))?;
+        if DISABLED.load(Ordering::Relaxed) {
+            return Ok(file);
+        }
+        match file.try_lock() {
+            Ok(()) => return Ok(file),
+            Err(std::fs::TryLockError::WouldBlock) => {
+                progress!("等待其他进程释放文件锁: {}", path.display());
+                file.lock().map_err(|err| lock_error(err, path))?;
+            }
+            Err(std::fs::TryLockError::Error(err)) => return Err(lock_error(err, path)),
+        }
+        if !replaced(&file, path)? {
+            return Ok(file);
+        }
+    }
+}
+
+/// 路径是否已指向另一个文件
+#[cfg(unix)]
+fn replaced(file: &File, path: &Path) -> Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let opened = file.metadata()?;
+    let current = std::fs::metadata(path)?;
+    Ok(opened.dev() != current.dev() || opened.ino() != current.ino())
+}
+
+/// 其他平台上没有稳定的方式比较两个文件是否相同，视为未被替换
+#[cfg(not(unix))]
+fn replaced(_file: &File, _path: &Path) -> Result<bool> {
+    Ok(false)
+}
+
+fn lock_error(err: io::Error, path: &Path) -> anyhow::Error {
+    if err.kind() == io::ErrorKind::Unsupported {
+        return anyhow::anyhow!(
+            "文件系统不支持文件锁: {}，可使用 --no-lock 关闭加锁",
+            path.display()
+        );
+    }
+    anyhow::Error::new(err).context(format!(
+        "无法为文件加锁: {}，可使用 --no-lock 关闭加锁",
+        path.display()
+    ))
+}
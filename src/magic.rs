@@ -0,0 +1,46 @@
+//! 根据数据开头的魔数识别常见的文件类型
+
+/// 识别类型时最多读取的字节数
+pub(crate) const MAGIC_LEN: usize = 16;
+
+/// 扩展名及其在数据开头 `offset` 处的魔数
+const SIGNATURES: &[(&str, usize, &[u8])] = &[
+    ("png", 0, b"\x89PNG\r\n\x1a\n"),
+    ("jpg", 0, b"\xff\xd8\xff"),
+    ("gif", 0, b"GIF8"),
+    ("bmp", 0, b"BM"),
+    ("tiff", 0, b"II*\x00"),
+    ("tiff", 0, b"MM\x00*"),
+    ("webp", 8, b"WEBP"),
+    ("wav", 8, b"WAVE"),
+    ("avi", 8, b"AVI "),
+    ("ogg", 0, b"OggS"),
+    ("mp3", 0, b"ID3"),
+    ("flac", 0, b"fLaC"),
+    ("dds", 0, b"DDS "),
+    ("zip", 0, b"PK\x03\x04"),
+    ("gz", 0, b"\x1f\x8b"),
+    ("pdf", 0, b"%PDF"),
+    ("ttf", 0, b"\x00\x01\x00\x00\x00"),
+    ("otf", 0, b"OTTO"),
+];
+
+/// 同一类型的其他常用扩展名
+const ALIASES: &[(&str, &str)] = &[("jpeg", "jpg"), ("tif", "tiff")];
+
+/// 识别数据的类型，返回对应的扩展名
+pub(crate) fn detect(bytes: &[u8]) -> Option<&'static str> {
+    SIGNATURES
+        .iter()
+        .find(|(_, offset, magic)| bytes.get(*offset..*offset + magic.len()) == Some(magic))
+        .map(|&(ext, _, _)| ext)
+}
+
+/// 将用户给出的类型名统一为 [`detect`] 返回的扩展名，不区分大小写，可以带有开头的 `.`
+pub(crate) fn normalize_type(name: &str) -> String {
+    let name = name.trim_start_matches('.').to_ascii_lowercase();
+    ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == name)
+        .map_or(name, |(_, ext)| (*ext).to_owned())
+}
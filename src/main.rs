@@ -1,267 +1,1389 @@
 use std::{
-    fs::{self, File},
-    io::{self, Read, Seek, SeekFrom, Write},
+    fs,
+    num::{NonZeroU64, NonZeroUsize},
     path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    time::Duration,
 };
 
-use anyhow::{Context, Result, bail};
-use palc::{Parser, Subcommand};
+use anyhow::{Result, bail};
+use aos_up::{
+    AddOptions, ByteSize, Cancelled, ChecksumAlgo, EntryFilter, HashManifestOptions,
+    HexdumpOptions, IndexRange, InfoOptions, ListOptions, NormalizeOptions, NormalizeOrder,
+    PackOptions, ProbeClass, RepackOptions, SortKey, SplitOptions, Threads, UnpackOptions,
+    ValidateOptions, add_entry, batch_exit_status, compare_with_dir,
+    config::Config,
+    default_max_open_files, diff_archives, diff_pack_plan, disable_locking, dump_data, emit_error,
+    enable_json_events, estimate_pack, extract_sparse,
+    format::{Layout, NameDecoder, NameEncoding, NameStyle},
+    hash_manifest, hexdump_entry, info_archive, is_silent, is_url, list_archive, normalize_archive,
+    output::{OutputFormat, SchemaDocument, json_schema},
+    pack_directory, probe_file, progress, rebuild_toc, repack_archive, report_duplicate_names,
+    set_silent, split_archive, unpack_archive, validate_archive, validate_batch, verify_against,
+};
+use palc::{Args, Parser, Subcommand};
+use regex::Regex;
 
-// C++ 代码中的 #pragma pack(1) 在 Rust 中用 #[repr(C, packed)] 实现
-// 我们需要确保内存布局与 C++ 版本完全一致
+/// 因取消而退出时的状态码，与被 SIGINT 终止时的惯例一致
+const EXIT_CANCELLED: u8 = 130;
+/// `probe` 判定为非标准的封包时的状态码，1 保留给运行出错
+const EXIT_PROBE_NONSTANDARD: u8 = 2;
+/// `probe` 判定为不是封包时的状态码
+const EXIT_PROBE_NOT_AOS: u8 = 3;
 
-const ARCHIVE_NAME_SIZE: usize = 261;
-const FILENAME_SIZE: usize = 32;
+/// `unpack --flatten-duplicates-report` 发现重名条目时的状态码
+const EXIT_DUPLICATE_NAMES: u8 = 2;
+/// `unpack --recursive-unpack` 默认最多解包的嵌套层数
+const DEFAULT_MAX_DEPTH: NonZeroUsize = NonZeroUsize::new(8).unwrap();
+/// `list --inline-data` 默认内嵌的单个条目大小上限
+const DEFAULT_MAX_INLINE_SIZE: u64 = 1 << 20;
 
-#[repr(C, packed)]
-#[derive(Debug)]
-struct AosV2Hdr {
-    unknown1: u32,
-    data_offset: u32, // 在原始代码中未使用，但封包时可以填充为0
-    toc_length: u32,
-    archive_name: [u8; ARCHIVE_NAME_SIZE],
-}
+/// 支持的格式版本
+const SUPPORTED_FORMATS: &[&str] = &["AOS V2"];
+/// 支持的文件名编码
+const SUPPORTED_ENCODINGS: &[&str] = &["UTF-8", "Shift-JIS"];
+/// 可选特性及其是否在本次构建中启用
+const OPTIONAL_FEATURES: &[(&str, bool)] = &[
+    ("config", cfg!(feature = "config")),
+    ("csv", cfg!(feature = "csv")),
+    ("http", cfg!(feature = "http")),
+    ("json", cfg!(feature = "json")),
+    ("schema", cfg!(feature = "schema")),
+    ("serde", cfg!(feature = "serde")),
+    ("testutil", cfg!(feature = "testutil")),
+    ("toml", cfg!(feature = "toml")),
+    ("tui", cfg!(feature = "tui")),
+    ("watch", cfg!(feature = "watch")),
+    ("yaml", cfg!(feature = "yaml")),
+];
 
-impl AosV2Hdr {
-    fn from_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
-        let mut buffer = [0u8; std::mem::size_of::<Self>()];
-        reader.read_exact(&mut buffer)?;
-        // 使用 unsafe 是因为我们正在从原始字节转换，必须确保类型布局正确
-        Ok(unsafe { std::ptr::read(buffer.as_ptr() as *const _) })
+/// 打印版本及能力信息
+fn print_version_info() {
+    println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"));
+    println!("支持的格式版本: {}", SUPPORTED_FORMATS.join(", "));
+    println!("支持的文件名编码: {}", SUPPORTED_ENCODINGS.join(", "));
+    if OPTIONAL_FEATURES.is_empty() {
+        println!("可选特性: 无");
+    } else {
+        let features: Vec<String> = OPTIONAL_FEATURES
+            .iter()
+            .map(|(name, enabled)| format!("{}{name}", if *enabled { '+' } else { '-' }))
+            .collect();
+        println!("可选特性: {}", features.join(" "));
     }
+}
 
-    fn to_bytes(&self) -> Vec<u8> {
-        let size = std::mem::size_of::<Self>();
-        let mut bytes = Vec::with_capacity(size);
-        // 使用 unsafe 将结构体转换为字节切片
-        unsafe {
-            let ptr = self as *const Self as *const u8;
-            bytes.extend_from_slice(std::slice::from_raw_parts(ptr, size));
+/// 安装 Ctrl-C 处理函数，返回收到信号时被设置的取消标记
+///
+/// 只在会检查取消标记的命令中安装，以免其他命令无法被 Ctrl-C 中断。
+/// 再次按下 Ctrl-C 时立即退出。
+fn install_cancel_handler() -> Arc<AtomicBool> {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&cancel);
+    let installed = ctrlc::set_handler(move || {
+        if flag.swap(true, Ordering::Relaxed) {
+            std::process::exit(EXIT_CANCELLED.into());
         }
-        bytes
+        eprintln!("正在取消，再次按下 Ctrl-C 立即退出...");
+    });
+    if let Err(err) = installed {
+        eprintln!("  警告: 无法安装 Ctrl-C 处理函数: {err}");
     }
+    cancel
 }
 
-#[repr(C, packed)]
-#[derive(Debug)]
-struct AosV2Entry {
-    filename: [u8; FILENAME_SIZE],
-    offset: u32,
-    length: u32,
+/// 确保路径指向一个已存在的文件
+fn ensure_file(path: &Path) -> Result<()> {
+    if !path.is_file() {
+        bail!(
+            "错误: 文件 '{}' 不存在或不是一个有效的文件。",
+            path.display()
+        );
+    }
+    Ok(())
 }
 
-impl AosV2Entry {
-    fn from_reader<R: Read>(reader: &mut R) -> io::Result<Self> {
-        let mut buffer = [0u8; std::mem::size_of::<Self>()];
-        reader.read_exact(&mut buffer)?;
-        Ok(unsafe { std::ptr::read(buffer.as_ptr() as *const _) })
+/// 确保路径是一个 URL 或指向一个已存在的文件
+fn ensure_archive(path: &Path) -> Result<()> {
+    if is_url(path) {
+        return Ok(());
     }
+    ensure_file(path)
+}
 
-    fn to_bytes(&self) -> Vec<u8> {
-        let size = std::mem::size_of::<Self>();
-        let mut bytes = Vec::with_capacity(size);
-        unsafe {
-            let ptr = self as *const Self as *const u8;
-            bytes.extend_from_slice(std::slice::from_raw_parts(ptr, size));
-        }
-        bytes
+/// 确保路径指向一个已存在的目录
+fn ensure_dir(path: &Path) -> Result<()> {
+    if !path.is_dir() {
+        bail!(
+            "错误: 目录 '{}' 不存在或不是一个有效的目录。",
+            path.display()
+        );
     }
+    Ok(())
+}
 
-    // 辅助函数，用于从字节数组中获取文件名字符串
-    fn get_filename_str(&self) -> Result<String> {
-        let null_pos = self
-            .filename
-            .iter()
-            .position(|&c| c == 0)
-            .unwrap_or(FILENAME_SIZE);
-        String::from_utf8(self.filename[..null_pos].to_vec()).context("文件名包含无效的 UTF-8 字符")
+/// 将命令行中的路径展开为 .aos 文件列表，目录展开为其中按名称排序的 .aos 文件
+fn expand_archive_paths(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut archives = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            ensure_file(path)?;
+            archives.push(path.clone());
+            continue;
+        }
+        let mut found: Vec<PathBuf> = fs::read_dir(path)?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .is_some_and(|ext| ext.eq_ignore_ascii_case("aos"))
+            })
+            .collect();
+        found.sort();
+        archives.extend(found);
     }
+    Ok(archives)
 }
 
-/// 解包 .aos 文件
-fn unpack_archive(archive_path: &Path) -> Result<()> {
-    println!("正在解包: {}", archive_path.display());
-
-    let mut file = File::open(archive_path)
-        .with_context(|| format!("无法打开文件: {}", archive_path.display()))?;
-
-    // 1. 读取文件头
-    let header = AosV2Hdr::from_reader(&mut file)?;
-
-    // 2. 读取目录表 (TOC)
-    let entry_count = header.toc_length as usize / std::mem::size_of::<AosV2Entry>();
-    let mut entries = Vec::with_capacity(entry_count);
-    for _ in 0..entry_count {
-        entries.push(AosV2Entry::from_reader(&mut file)?);
+/// 解析输出路径：相对路径基于 `relative_to` 解析，并确保其父目录存在
+fn resolve_output_path(path: &Path, relative_to: Option<&Path>) -> Result<PathBuf> {
+    let resolved = match relative_to {
+        Some(base) if path.is_relative() => base.join(path),
+        _ => path.to_path_buf(),
+    };
+    if let Some(parent) = resolved.parent()
+        && !parent.as_os_str().is_empty()
+        && !parent.is_dir()
+    {
+        bail!("错误: 输出目录 '{}' 不存在。", parent.display());
     }
+    Ok(resolved)
+}
 
-    // 3. 创建输出目录
-    let output_dir_name = archive_path.file_stem().unwrap_or_default();
-    let output_dir = archive_path.with_file_name(output_dir_name);
-    fs::create_dir_all(&output_dir)
-        .with_context(|| format!("无法创建目录: {}", output_dir.display()))?;
-
-    println!("解包到目录: {}", output_dir.display());
-
-    // 4. 计算数据区基地址并提取文件
-    let base_offset = (std::mem::size_of::<AosV2Hdr>() + header.toc_length as usize) as u64;
-
-    for entry in &entries {
-        let filename_str = entry.get_filename_str()?;
-        let output_path = output_dir.join(&filename_str);
-
-        println!("  -> 提取: {filename_str}");
-
-        let mut buffer = vec![0u8; entry.length as usize];
-        file.seek(SeekFrom::Start(base_offset + entry.offset as u64))?;
-        file.read_exact(&mut buffer)?;
+/// 目录表布局相关的参数
+#[derive(Args, Debug)]
+struct LayoutArgs {
+    /// 目录表中每个条目的字节数，用于条目带有额外保留字段的变体，默认为 40
+    #[arg(long, value_name = "N")]
+    entry_size: Option<usize>,
+}
 
-        fs::write(&output_path, &buffer)
-            .with_context(|| format!("无法写入文件: {}", output_path.display()))?;
+impl LayoutArgs {
+    fn layout(&self, config: &Config) -> Result<Layout> {
+        self.entry_size
+            .or(config.entry_size)
+            .map_or_else(|| Ok(Layout::default()), Layout::with_entry_size)
     }
-
-    println!("解包完成。");
-    Ok(())
 }
 
-/// 封包一个目录
-fn pack_directory(dir_path: &Path) -> Result<()> {
-    println!("正在封包目录: {}", dir_path.display());
-
-    let files_to_pack: Vec<PathBuf> = fs::read_dir(dir_path)
-        .with_context(|| format!("无法读取目录: {}", dir_path.display()))?
-        .filter_map(Result::ok)
-        .map(|entry| entry.path())
-        .filter(|path| path.is_file())
-        .collect();
-
-    if files_to_pack.is_empty() {
-        bail!("目录为空，没有可封包的文件。");
-    }
+/// 文件名解码相关的参数
+#[derive(Args, Debug)]
+struct NameArgs {
+    /// 文件名包含无效字符时以替换字符代替并给出警告，而不是中止
+    #[arg(long)]
+    lossy_names: bool,
+    /// 文件名包含无效字符时中止，覆盖配置文件中的 `lossy_names = true`
+    #[arg(long, conflicts_with = "lossy_names")]
+    no_lossy_names: bool,
+    /// 文件名字段的存储方式: nul 以空字符结尾，length-prefixed 以第一个字节为长度
+    #[arg(long, value_name = "STYLE", default_value_t = NameStyle::Nul)]
+    name_style: NameStyle,
+    /// 文件名的编码: utf8、shift-jis，或 auto 根据封包开头的若干文件名选择，
+    /// 均为有效的 UTF-8 时使用 UTF-8，否则尝试 Shift-JIS。默认为 utf8
+    #[arg(long, value_name = "ENCODING")]
+    name_encoding: Option<NameEncoding>,
+}
 
-    // 1. 构建目录表 (TOC) 和计算数据区
-    let mut entries = Vec::new();
-    let mut data_blob = Vec::new();
-    let mut current_offset = 0u32;
-
-    for file_path in &files_to_pack {
-        let filename = file_path
-            .file_name()
-            .and_then(|s| s.to_str())
-            .context("文件名无效")?;
-
-        if filename.len() >= FILENAME_SIZE {
-            bail!(
-                "文件名 '{}' 过长 (最大 {} 字节)",
-                filename,
-                FILENAME_SIZE - 1
-            );
+impl NameArgs {
+    fn decoder(&self, config: &Config) -> NameDecoder {
+        NameDecoder {
+            lossy: !self.no_lossy_names && (self.lossy_names || config.lossy_names == Some(true)),
+            style: self.name_style,
+            encoding: self
+                .name_encoding
+                .or(config.name_encoding)
+                .unwrap_or_default(),
         }
+    }
+}
 
-        let file_data = fs::read(file_path)?;
-        let file_length = file_data.len() as u32;
-
-        let mut filename_bytes = [0u8; FILENAME_SIZE];
-        filename_bytes[..filename.len()].copy_from_slice(filename.as_bytes());
-
-        let entry = AosV2Entry {
-            filename: filename_bytes,
-            offset: current_offset,
-            length: file_length,
-        };
-        entries.push(entry);
+/// `pack` 与 `watch` 共用的封包参数
+#[derive(Args, Debug)]
+struct PackArgs {
+    /// 输出的 .aos 文件路径，默认为与目录同名的 .aos 文件
+    #[arg(short, long, value_name = "FILE")]
+    output_archive: Option<PathBuf>,
+    /// 解析相对的输出路径时使用的基准目录，默认为当前工作目录
+    #[arg(long, value_name = "DIR", requires = "output_archive")]
+    relative_to: Option<PathBuf>,
+    #[command(flatten)]
+    layout: LayoutArgs,
+    /// 读写单个文件遇到暂时性 IO 错误时重试，总共最多尝试 N 次 (包括第一次)，
+    /// 适用于不稳定的网络文件系统；默认为 0，与 1 相同，即不重试
+    #[arg(long, value_name = "N")]
+    retry: Option<u32>,
+    /// 读取单个文件 (包括重试) 超过该秒数仍未完成时中止。尽力而为: 挂起的系统调用
+    /// 无法被取消，只是不再等待它
+    #[arg(long, value_name = "SECONDS")]
+    op_timeout: Option<NonZeroU64>,
+    /// 封包后的总大小超过该值时在写入前中止，可使用 K、M、G 等单位后缀，例如 `2G`
+    #[arg(long, value_name = "SIZE")]
+    max_archive_size: Option<ByteSize>,
+    /// 内容完全相同的文件只存储一份数据，其条目指向同一偏移量
+    #[arg(long)]
+    dedup: bool,
+    #[command(flatten)]
+    extended: ExtendedArgs,
+    /// 重命名表，每行为以制表符分隔的原文件名与存储在封包中的新名称
+    #[arg(long, value_name = "FILE")]
+    rename_map: Option<PathBuf>,
+    /// 报告完成前将输出文件同步写入磁盘，确保退出后立即断电也不会留下不完整的文件。
+    /// 会等待磁盘写入完成，大文件或慢速磁盘上明显更慢
+    #[arg(long)]
+    sync: bool,
+    /// 将该文件的内容作为尾部数据追加到数据区之后，通常由 `unpack --preserve-footer` 保存
+    #[arg(long, value_name = "FILE")]
+    footer: Option<PathBuf>,
+    /// 在末尾 (包括尾部数据之后) 追加空字节，使文件大小为 N 的整数倍，
+    /// 用于要求按扇区对齐的引擎。填充位于所有条目的数据之后，不影响解包；
+    /// 未指定时使用目录旁 .aosmeta 中由 `unpack --preserve-footer` 记录的值
+    #[arg(long, alias = "null-pad-data", value_name = "N")]
+    pad_to: Option<NonZeroU64>,
+    /// 在扩展格式的文件头中写入注释，例如构建标识或时间，可由 info 查看，需要 `--extended`
+    #[arg(long, value_name = "TEXT")]
+    comment: Option<String>,
+    /// 将该值写入标准格式文件头中未使用的 unknown1 字段作为标识。此为非标准用法，
+    /// 不能与 `--extended` 同时使用
+    #[arg(long, value_name = "U32")]
+    tag: Option<u32>,
+    /// 文件名字段的存储方式: nul 以空字符结尾，length-prefixed 以第一个字节为长度
+    #[arg(long, value_name = "STYLE", default_value_t = NameStyle::Nul)]
+    name_style: NameStyle,
+    /// 内嵌的封包名称使用实际输出的文件名，而不是默认的 `<目录名>.aos`
+    #[arg(long)]
+    sync_archive_name: bool,
+    /// 递归封包子目录中的文件，条目名称为以 `/` 分隔的相对路径
+    #[arg(short, long)]
+    recursive: bool,
+    /// 条目名称为文件相对于该目录的路径 (以 `/` 分隔)，而不是相对于要封包的目录，
+    /// 例如 `pack assets/ui -r --name-root assets` 存储为 `ui/...`
+    #[arg(long, value_name = "DIR")]
+    name_root: Option<PathBuf>,
+    /// 递归时只包含根目录以下至多 N 层的文件，更深的文件被跳过并报告数量；
+    /// 1 表示只包含根目录中的文件
+    #[arg(long, value_name = "N", requires = "recursive")]
+    max_depth: Option<usize>,
+    /// 目录表按该文件中逐行列出的文件名排序，名称为重命名之前的相对路径；
+    /// 未列出的文件按名称排序后追加，列出但不存在的文件给出警告
+    #[arg(long, value_name = "FILE")]
+    order_file: Option<PathBuf>,
+    /// 目录中存在未在 `--order-file` 中列出的文件时报错，而不是追加在最后
+    #[arg(long, requires = "order_file")]
+    strict_order: bool,
+    /// 条目名称匹配该正则表达式的文件按 UTF-8 文本 (可带 BOM) 转换为 Shift-JIS 后存储，
+    /// 无法以 Shift-JIS 表示的字符替换为 `?` 并给出警告
+    #[arg(long, value_name = "PATTERN")]
+    encode_text: Option<Regex>,
+    /// 同时打开的源文件数上限，默认为系统软限制的一半；`--op-timeout` 超时后仍在读取的文件也计入
+    #[arg(long, value_name = "N")]
+    max_open_files: Option<usize>,
+}
 
-        data_blob.extend_from_slice(&file_data);
-        current_offset += file_length;
+impl PackArgs {
+    /// 转换为封包选项，不设置取消标记
+    fn options(&self, config: &Config) -> Result<PackOptions> {
+        let output_archive = self
+            .output_archive
+            .as_deref()
+            .map(|path| resolve_output_path(path, self.relative_to.as_deref()))
+            .transpose()?;
+        Ok(PackOptions {
+            output_archive,
+            layout: self.layout.layout(config)?,
+            retry: self.retry.or(config.retry).unwrap_or(0),
+            op_timeout: self
+                .op_timeout
+                .or(config.op_timeout)
+                .map(|secs| Duration::from_secs(secs.get())),
+            max_archive_size: self.max_archive_size,
+            dedup: self.dedup,
+            extended: self.extended.extended,
+            flags_file: self.extended.flags_file.clone(),
+            rename_map: self.rename_map.clone(),
+            sync: self.sync,
+            footer: self.footer.clone(),
+            pad_to: self.pad_to,
+            comment: self.comment.clone(),
+            tag: self.tag,
+            name_style: self.name_style,
+            sync_archive_name: self.sync_archive_name,
+            recursive: self.recursive,
+            name_root: self.name_root.clone(),
+            max_depth: self.max_depth,
+            order_file: self.order_file.clone(),
+            strict_order: self.strict_order,
+            encode_text: self.encode_text.clone(),
+            max_open_files: self.max_open_files.or(config.max_open_files),
+            cancel: None,
+        })
     }
+}
 
-    // 2. 构建文件头
-    let dir_name = dir_path
-        .file_name()
-        .and_then(|s| s.to_str())
-        .unwrap_or("archive");
-    let archive_name_str = format!("{dir_name}.aos");
-    let mut archive_name_bytes = [0u8; ARCHIVE_NAME_SIZE];
-    // 确保不会因为文件名过长而 panic
-    let name_len = std::cmp::min(archive_name_str.len(), ARCHIVE_NAME_SIZE - 1);
-    archive_name_bytes[..name_len].copy_from_slice(&archive_name_str.as_bytes()[..name_len]);
-
-    let toc_length = (entries.len() * std::mem::size_of::<AosV2Entry>()) as u32;
-    let header_size = std::mem::size_of::<AosV2Hdr>() as u32;
-
-    let header = AosV2Hdr {
-        unknown1: 0,
-        data_offset: header_size + toc_length,
-        toc_length,
-        archive_name: archive_name_bytes,
-    };
-
-    // 3. 写入到 .aos 文件
-    let output_filename = dir_path.with_extension("aos");
-    let mut output_file = File::create(&output_filename)
-        .with_context(|| format!("无法创建输出文件: {}", output_filename.display()))?;
+/// 本工具扩展格式相关的参数
+#[derive(Args, Debug)]
+struct ExtendedArgs {
+    /// 使用本工具的扩展格式，每个条目带有一个 32 位标记，游戏引擎无法读取该格式，
+    /// 指定后忽略 `--entry-size`
+    #[arg(long)]
+    extended: bool,
+    /// 扩展格式的标记文件，每行为以制表符分隔的文件名与标记；
+    /// 封包时从中读取标记，解包时将标记写入其中
+    #[arg(long, value_name = "PATH", requires = "extended")]
+    flags_file: Option<PathBuf>,
+}
 
-    // 写入文件头
-    output_file.write_all(&header.to_bytes())?;
+/// 选择条目的参数
+#[derive(Args, Debug)]
+struct FilterArgs {
+    /// 只处理文件名匹配该正则表达式的条目，匹配的对象是完整的解码后文件名，
+    /// 需要整体匹配时请使用 `^` 与 `$`
+    #[arg(long, value_name = "PATTERN")]
+    regex: Option<Regex>,
+    /// 只处理目录表中该序号范围内的条目，序号从 0 开始，例如 `5`、`0..10` 或 `10..`
+    #[arg(long, value_name = "RANGE")]
+    index: Option<IndexRange>,
+    /// 保留全为 0 的目录表条目 (空文件名、偏移量与长度均为 0)。默认将其视为
+    /// 把目录表填充到对齐边界的填充条目而跳过
+    #[arg(long)]
+    keep_empty_entries: bool,
+    /// 只处理长度不小于该大小的条目，可使用 K、M、G 等单位后缀，例如 `1K`
+    #[arg(long, value_name = "SIZE")]
+    min_size: Option<ByteSize>,
+    /// 只处理长度不大于该大小的条目，可使用 K、M、G 等单位后缀，例如 `100K`
+    #[arg(long, value_name = "SIZE")]
+    max_size: Option<ByteSize>,
+}
 
-    // 写入目录表
-    for entry in &entries {
-        output_file.write_all(&entry.to_bytes())?;
+impl FilterArgs {
+    fn filter(&self) -> Result<EntryFilter> {
+        if let (Some(min), Some(max)) = (self.min_size, self.max_size)
+            && min > max
+        {
+            bail!("--min-size ({min}) 大于 --max-size ({max})");
+        }
+        Ok(EntryFilter {
+            regex: self.regex.clone(),
+            index: self.index,
+            keep_empty: self.keep_empty_entries,
+            min_size: self.min_size.map(|size| size.0),
+            max_size: self.max_size.map(|size| size.0),
+        })
     }
-
-    // 写入文件数据
-    output_file.write_all(&data_blob)?;
-
-    println!("封包完成，输出文件: {}", output_filename.display());
-    Ok(())
 }
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Cli {
+    /// 事件日志格式: text 不输出事件，json 将进度、警告与错误事件以 JSON Lines 写入标准错误
+    #[arg(long, value_name = "FORMAT", default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+    /// 配置文件路径，默认依次查找当前目录与主目录下的 `.aos_up.toml`；
+    /// 命令行参数优先于配置文件
+    #[arg(long, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// 不输出任何进度、汇总与警告，只在标准错误中报告错误并以退出状态表示成败，
+    /// list、info 等命令本身要查看的内容仍会输出
+    #[arg(long)]
+    silent: bool,
+    /// 不为打开的 .aos 文件加建议性文件锁，用于不支持文件锁的文件系统
+    #[arg(long)]
+    no_lock: bool,
     #[command(subcommand)]
     command: Commands,
 }
 
+/// 事件日志的格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, palc::ValueEnum)]
+enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
 #[derive(Subcommand, Debug)]
 enum Commands {
     /// 解包 .aos 文件
     Unpack {
-        /// 要解包的 .aos 文件路径
+        /// 要解包的 .aos 文件路径，启用 http 特性时也可以是 http(s) URL
         #[arg(value_name = "FILE")]
         archive_path: PathBuf,
+        /// 输出目录，默认为与 .aos 文件同名的目录
+        #[arg(short, long, value_name = "DIR")]
+        output: Option<PathBuf>,
+        /// 在输出目录下再嵌套一层子目录，不能是绝对路径或包含 `..`
+        #[arg(long, value_name = "SUBDIR")]
+        prefix: Option<PathBuf>,
+        #[command(flatten)]
+        layout: LayoutArgs,
+        #[command(flatten)]
+        names: NameArgs,
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// 读写单个条目遇到暂时性 IO 错误时重试，总共最多尝试 N 次 (包括第一次)，
+        /// 适用于不稳定的网络文件系统；默认为 0，与 1 相同，即不重试
+        #[arg(long, value_name = "N")]
+        retry: Option<u32>,
+        /// 读取或写入单个条目 (包括重试) 超过该秒数仍未完成时中止，
+        /// 与 `--skip-toc-validation` 同时使用时跳过该条目。尽力而为: 挂起的系统调用
+        /// 无法被取消，只是不再等待它
+        #[arg(long, value_name = "SECONDS")]
+        op_timeout: Option<NonZeroU64>,
+        /// 数据区起始偏移量不是 N 的整数倍时给出警告，用于检查对齐敏感的引擎所需的封包
+        #[arg(long, value_name = "N")]
+        expect_data_align: Option<NonZeroU64>,
+        /// 解包完成后按目录表顺序写入已提取条目的偏移量与大小，
+        /// 扩展名为 .json、.yaml 或 .toml 时以对应格式写入，否则写入文本
+        #[arg(long, value_name = "PATH")]
+        write_index: Option<PathBuf>,
+        /// 按偏移量升序提取条目，以顺序读取代替来回寻址，适用于机械硬盘与网络文件
+        #[arg(long)]
+        sort_by_offset: bool,
+        /// 用于抢救损坏的封包: 跳过结构检查失败或无法读取的条目并给出警告，
+        /// 而不是中止。输出可能不完整或部分损坏
+        #[arg(long, alias = "best-effort")]
+        skip_toc_validation: bool,
+        /// 将文件名中在 Windows 上非法的字符 (`<>:"|?*` 与控制字符) 替换后再写入
+        #[arg(long)]
+        sanitize: bool,
+        /// `--sanitize` 使用的替换字符
+        #[arg(
+            long,
+            value_name = "CHAR",
+            default_value_t = '_',
+            requires = "sanitize"
+        )]
+        sanitize_char: char,
+        #[command(flatten)]
+        extended: ExtendedArgs,
+        /// 文件名匹配该正则表达式的条目在写入前去掉数据末尾的空字节，适用于以空字节填充的文本
+        #[arg(long, value_name = "PATTERN")]
+        trim_trailing_nulls: Option<Regex>,
+        /// 只提取该文件中逐行列出的条目，为 `-` 时从标准输入读取
+        #[arg(long, value_name = "FILE")]
+        names_from: Option<PathBuf>,
+        /// 列表中的名称在封包中不存在时报错退出，而不是给出警告
+        #[arg(long, requires = "names_from")]
+        strict: bool,
+        /// 将最后一个条目之后的尾部数据 (例如引擎特有的签名) 保存到该文件，
+        /// 之后可通过 `pack --footer` 追加回去
+        #[arg(long, value_name = "FILE")]
+        preserve_footer: Option<PathBuf>,
+        /// 只提取数据开头的魔数识别为该类型的条目，例如 png、jpg、wav、ogg、dds，
+        /// 没有扩展名的条目写出时补上扩展名
+        #[arg(long = "type", value_name = "EXT")]
+        content_type: Option<String>,
+        /// 忽略文件头中的 data_offset，按文件头与目录表大小计算数据区起始位置，
+        /// 用于 data_offset 被填写为错误值的封包
+        #[arg(long)]
+        compute_base: bool,
+        /// 每写入一个条目后向标准输出写入一行 JSON (index、total、name、offset、length、
+        /// destination) 并立即刷新。启用后标准输出中不再有供人阅读的文本，
+        /// 警告可通过 `--log-format json` 在标准错误中获得
+        #[arg(long, alias = "output-stdout-json-stream")]
+        json_stream: bool,
+        /// 将条目名称统一为小写后写出，只有大小写不同的条目加上 `~N` 后缀，
+        /// 并将磁盘上的名称与原名称写入该文件，之后可用 `pack --rename-map` 恢复原名称
+        #[arg(long, value_name = "FILE")]
+        preserve_case_db: Option<PathBuf>,
+        /// 提取每个条目时打印其文件内绝对偏移量 (数据区起始 + 条目偏移量)、相对偏移量与长度，
+        /// 用于排查提取出的数据错乱等偏移量计算问题
+        #[arg(long)]
+        debug_offsets: bool,
+        /// 将条目名称转写为 ASCII 后写出 (假名转为罗马字，其他非 ASCII 字符转为 `u` 加码位)，
+        /// 用于不支持非 ASCII 文件名的环境。转写是有损的，原名称记录在 `--preserve-case-db` 中
+        #[arg(long, requires = "preserve_case_db")]
+        transliterate: bool,
+        /// 在后台线程中写出文件，读取与写入同时进行，适用于 .aos 文件与输出目录位于不同磁盘的情况；
+        /// 最多缓存 8 个条目的数据。固定使用一个写出线程，按目录表顺序写入
+        #[arg(long)]
+        pipeline: bool,
+        /// 跳过名称为空 (偏移量或长度不为 0) 的条目并给出警告，默认报错
+        #[arg(long)]
+        skip_unnamed: bool,
+        /// 解包前扫描目录表，列出解码后名称相同的条目的序号、偏移量与长度；
+        /// 存在重名条目时解包后以状态码 2 退出
+        #[arg(long)]
+        flatten_duplicates_report: bool,
+        /// 检查提取出的每个条目是否本身也是 .aos 封包 (判断方式与 probe 相同)，
+        /// 是则解包到条目旁以其名称 (去掉扩展名) 命名的目录中
+        #[arg(long)]
+        recursive_unpack: bool,
+        /// 与 `--recursive-unpack` 同时使用时最多解包的嵌套层数，默认为 8
+        #[arg(long, value_name = "N", requires = "recursive_unpack")]
+        max_depth: Option<NonZeroUsize>,
+        /// 不解包，按大小与 xxh3 摘要将条目与输出目录中已有的文件比较，报告不同与缺失的文件；
+        /// 目录已是最新时以状态码 0 退出，否则为 1
+        #[arg(long)]
+        compare_with_dir: bool,
+        /// 将条目写入该 .zip 文件而不是输出目录。名称以 UTF-8 存储并保留子目录，
+        /// 不压缩且时间戳固定为 1980-01-01，相同的封包总是得到相同的 zip 文件
+        #[arg(long, value_name = "PATH")]
+        as_zip: Option<PathBuf>,
+        /// 文件名匹配该正则表达式的条目按 Shift-JIS 文本转换为 UTF-8 后写出，其余条目原样写出；
+        /// 无法转换或转换后无法逐字节还原的条目给出警告并原样写出。可用 `pack --encode-text` 转换回去
+        #[arg(long, value_name = "PATTERN")]
+        decode_text: Option<Regex>,
+        /// 在 `--decode-text` 转换后的文本开头写入 UTF-8 BOM
+        #[arg(long, requires = "decode_text")]
+        text_bom: bool,
     },
     /// 封包一个目录
     Pack {
         /// 要封包的目录路径
         #[arg(value_name = "DIRECTORY")]
         dir_path: PathBuf,
+        #[command(flatten)]
+        pack: PackArgs,
+        /// 只根据文件大小计算封包后的大小，不读取文件内容也不写入输出
+        #[arg(long)]
+        estimate: bool,
+        /// 不写入输出，只将规划的目录表与该 .aos 文件比较，报告新增、删除、顺序或大小变化的条目，
+        /// 存在差异时返回非零状态码
+        #[arg(long, value_name = "ARCHIVE")]
+        diff_against: Option<PathBuf>,
+    },
+    /// 监视目录，文件变化时自动重新封包，接受与 pack 相同的封包参数，需要启用 watch 特性
+    Watch {
+        /// 要监视并封包的目录路径
+        #[arg(value_name = "DIRECTORY")]
+        dir_path: PathBuf,
+        #[command(flatten)]
+        pack: PackArgs,
+        /// 在该时间 (毫秒) 内连续发生的变化合并为一次封包
+        #[arg(long, value_name = "MS", default_value_t = 500)]
+        debounce: u64,
+    },
+    /// 列出 .aos 文件中的条目
+    List {
+        /// 要查看的 .aos 文件路径，启用 http 特性时也可以是 http(s) URL
+        #[arg(value_name = "FILE")]
+        archive_path: PathBuf,
+        /// 每行只输出一个文件名，便于通过管道交给其他工具
+        #[arg(short = 'q', long)]
+        only_names: bool,
+        /// 以 NUL 字节分隔文件名，可配合 `xargs -0` 安全处理任意文件名
+        #[arg(long, requires = "only_names")]
+        print0: bool,
+        /// 输出格式: text、json、yaml 或 toml，非文本格式需要启用同名特性
+        #[arg(long, value_name = "FORMAT", default_value_t = OutputFormat::Text)]
+        output_format: OutputFormat,
+        /// 额外将目录表 (名称、偏移量、长度) 写入 CSV 文件，需要启用 csv 特性
+        #[arg(long, value_name = "PATH")]
+        csv: Option<PathBuf>,
+        #[command(flatten)]
+        layout: LayoutArgs,
+        #[command(flatten)]
+        names: NameArgs,
+        #[command(flatten)]
+        filter: FilterArgs,
+        /// 按 name、offset 或 size 排序输出，默认按目录表顺序
+        #[arg(long, value_name = "KEY")]
+        sort: Option<SortKey>,
+        /// 与 `--sort` 同时使用时降序排列
+        #[arg(long, requires = "sort")]
+        reverse: bool,
+        /// 读取每个条目的数据并计算 crc32、xxh3 或 sha256 校验值，作为额外的一列输出。
+        /// 需要读取全部数据，比只读取目录表慢得多
+        #[arg(long, value_name = "ALGO")]
+        checksum: Option<ChecksumAlgo>,
+        /// 在 `--output-format json` 的输出中以 Base64 内嵌每个条目的数据，用于导出小型封包
+        #[arg(long)]
+        inline_data: bool,
+        /// 与 `--inline-data` 同时使用时，超过该大小的条目不内嵌数据，只保留偏移量与长度，默认为 1M
+        #[arg(long, value_name = "SIZE", requires = "inline_data")]
+        max_inline_size: Option<ByteSize>,
+    },
+    /// 查看 .aos 文件的文件头信息
+    Info {
+        /// 要查看的 .aos 文件路径，启用 http 特性时也可以是 http(s) URL
+        #[arg(value_name = "FILE")]
+        archive_path: PathBuf,
+        /// 输出格式: text、json、yaml 或 toml，非文本格式需要启用同名特性
+        #[arg(long, value_name = "FORMAT", default_value_t = OutputFormat::Text)]
+        output_format: OutputFormat,
+        #[command(flatten)]
+        layout: LayoutArgs,
+    },
+    /// 只读取文件头与第一个条目判断文件是否为 .aos 封包，输出 AOS V2、
+    /// possibly AOS (nonstandard) 或 not AOS，退出码分别为 0、2、3
+    Probe {
+        /// 要检查的文件路径，启用 http 特性时也可以是 http(s) URL
+        #[arg(value_name = "FILE")]
+        path: PathBuf,
+    },
+    /// 以十六进制与 ASCII 对照的格式打印条目的原始数据
+    Hexdump {
+        /// .aos 文件路径，启用 http 特性时也可以是 http(s) URL
+        #[arg(value_name = "FILE")]
+        archive_path: PathBuf,
+        /// 要查看的条目名称
+        #[arg(value_name = "NAME")]
+        name: String,
+        /// 跳过条目开头的 N 个字节
+        #[arg(long, value_name = "N", default_value_t = 0)]
+        skip: u64,
+        /// 最多显示 N 个字节，默认显示到条目末尾
+        #[arg(long, value_name = "N")]
+        length: Option<u64>,
+        #[command(flatten)]
+        layout: LayoutArgs,
+        #[command(flatten)]
+        names: NameArgs,
+    },
+    /// 以逐行输入命令的方式交互式浏览封包: 分页列出条目、预览条目开头的数据 (文本或十六进制)、
+    /// 提取单个条目，条目数据按需读取。不是全屏界面。需要启用 tui 特性
+    Browse {
+        /// .aos 文件路径，启用 http 特性时也可以是 http(s) URL
+        #[arg(value_name = "FILE")]
+        archive_path: PathBuf,
+        /// 提取条目时的输出目录，默认为当前目录
+        #[arg(short, long, value_name = "DIR")]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        layout: LayoutArgs,
+        #[command(flatten)]
+        names: NameArgs,
+    },
+    /// 将数据区 (目录表之后的全部字节) 原样导出为单个文件
+    DumpData {
+        /// .aos 文件路径，启用 http 特性时也可以是 http(s) URL
+        #[arg(value_name = "FILE")]
+        archive_path: PathBuf,
+        /// 输出文件路径，其中的偏移量与条目的偏移量一致
+        #[arg(value_name = "OUTPUT")]
+        output: PathBuf,
+    },
+    /// 将每个条目的数据写入单个输出文件中与其偏移量相同的位置，空隙在支持的文件系统上成为稀疏空洞，
+    /// 并在 `<OUTPUT>.map` 中记录每个条目的偏移量、长度与名称
+    ExtractSparse {
+        /// .aos 文件路径，启用 http 特性时也可以是 http(s) URL
+        #[arg(value_name = "FILE")]
+        archive_path: PathBuf,
+        /// 输出文件路径
+        #[arg(value_name = "OUTPUT")]
+        output: PathBuf,
+        #[command(flatten)]
+        layout: LayoutArgs,
+    },
+    /// 计算整个文件与每个条目数据的校验值并以 JSON 写入清单，用于比较不同构建或部署之间的封包。
+    /// 需要启用 json 特性
+    HashManifest {
+        /// .aos 文件路径，启用 http 特性时也可以是 http(s) URL
+        #[arg(value_name = "FILE")]
+        archive_path: PathBuf,
+        /// 输出的清单文件路径
+        #[arg(value_name = "OUTPUT")]
+        output: PathBuf,
+        /// 校验算法
+        #[arg(long, value_name = "ALGO", default_value_t = ChecksumAlgo::Sha256)]
+        algorithm: ChecksumAlgo,
+        #[command(flatten)]
+        layout: LayoutArgs,
+        #[command(flatten)]
+        names: NameArgs,
+    },
+    /// 由原始数据区与记录条目边界的布局文件重建 .aos 文件，用于目录表损坏而数据区完好的封包
+    RebuildToc {
+        /// 数据区文件，例如 `dump-data` 的输出
+        #[arg(value_name = "DATA")]
+        data_path: PathBuf,
+        /// 布局文件，每行为以制表符分隔的条目名称、偏移量与长度，偏移量相对数据区起始
+        #[arg(long = "layout", value_name = "FILE")]
+        layout_file: PathBuf,
+        /// 输出的 .aos 文件路径
+        #[arg(value_name = "OUTPUT")]
+        output: PathBuf,
+        #[command(flatten)]
+        layout: LayoutArgs,
+    },
+    /// 以规范形式重写 .aos 文件: 数据紧密排列、不留空隙，data_offset 取标准值，名称以 0 填充
+    Normalize {
+        /// 要规范化的 .aos 文件路径
+        #[arg(value_name = "FILE")]
+        archive_path: PathBuf,
+        /// 输出的 .aos 文件路径，默认为原文件旁的 `<名称>_normalized.aos`
+        #[arg(short, long, value_name = "FILE")]
+        output_archive: Option<PathBuf>,
+        /// 条目顺序: original 保持原目录表顺序，name 按文件名排序
+        #[arg(long, value_name = "ORDER", default_value_t = NormalizeOrder::Original)]
+        order: NormalizeOrder,
+        /// 只打印偏移量与文件大小的变化，不写入输出文件
+        #[arg(long)]
+        dry_run: bool,
+        #[command(flatten)]
+        layout: LayoutArgs,
+        #[command(flatten)]
+        names: NameArgs,
+    },
+    /// 以原 .aos 文件为模板，保持原始字节布局重新封包
+    Repack {
+        /// 作为模板的原 .aos 文件路径
+        #[arg(value_name = "FILE")]
+        archive_path: PathBuf,
+        /// 包含 (可能已修改的) 文件的目录路径
+        #[arg(value_name = "DIRECTORY")]
+        dir_path: PathBuf,
+        /// 输出的 .aos 文件路径，默认为原文件旁的 `<名称>_repack.aos`
+        #[arg(short, long, value_name = "FILE")]
+        output_archive: Option<PathBuf>,
+        #[command(flatten)]
+        layout: LayoutArgs,
+        /// 重命名表，每行为以制表符分隔的原条目名称与新名称
+        #[arg(long, value_name = "FILE")]
+        rename_map: Option<PathBuf>,
+        /// 将原文件最后一个条目之后的尾部数据追加到输出末尾
+        #[arg(long)]
+        preserve_footer: bool,
+        /// 将内嵌的封包名称改为输出文件名，而不是保留原文件中的名称
+        #[arg(long)]
+        sync_archive_name: bool,
+        /// 只打印被替换、顺延与重命名的条目以及输出文件大小，不写入输出文件
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// 向 .aos 文件追加一个条目，原有条目保持不变
+    Add {
+        /// 要修改的 .aos 文件路径
+        #[arg(value_name = "ARCHIVE")]
+        archive_path: PathBuf,
+        /// 要添加的文件路径
+        #[arg(value_name = "FILE")]
+        file_path: PathBuf,
+        /// 存储在封包中的名称，默认为文件名
+        #[arg(long, value_name = "NAME")]
+        name: Option<String>,
+        /// 已存在同名条目时让其指向新数据，而不是报错
+        #[arg(long)]
+        force: bool,
+        /// 只打印受影响的条目、偏移量与文件大小的变化，不修改 .aos 文件
+        #[arg(long)]
+        dry_run: bool,
+        #[command(flatten)]
+        layout: LayoutArgs,
+    },
+    /// 将 .aos 文件的条目按顺序分配到多个不超过大小上限的封包中
+    Split {
+        /// 要拆分的 .aos 文件路径
+        #[arg(value_name = "FILE")]
+        archive_path: PathBuf,
+        /// 每个输出文件的大小上限，可使用 K、M、G 等单位后缀，例如 `700M`
+        #[arg(long, value_name = "SIZE")]
+        max_size: ByteSize,
+        /// 输出文件名前缀，输出为 `<NAME>_001.aos` 等，并写入索引 `<NAME>.index`
+        #[arg(long, value_name = "NAME")]
+        prefix: PathBuf,
+        #[command(flatten)]
+        layout: LayoutArgs,
+    },
+    /// 比较两个 .aos 文件的条目，存在差异时以非零状态码退出
+    Diff {
+        /// 旧的 .aos 文件路径
+        #[arg(value_name = "OLD")]
+        old_path: PathBuf,
+        /// 新的 .aos 文件路径
+        #[arg(value_name = "NEW")]
+        new_path: PathBuf,
+    },
+    /// 将 .aos 文件与其源目录比较，存在差异时以非零状态码退出
+    VerifyAgainst {
+        /// 要检查的 .aos 文件路径
+        #[arg(value_name = "FILE")]
+        archive_path: PathBuf,
+        /// 源目录路径
+        #[arg(value_name = "DIRECTORY")]
+        dir_path: PathBuf,
+    },
+    /// 检查 .aos 文件的结构，发现错误时以非零状态码退出
+    Validate {
+        /// 要检查的 .aos 文件路径，可以指定多个；指定目录时检查其中所有的 .aos 文件
+        #[arg(value_name = "FILE", required = true)]
+        archive_paths: Vec<PathBuf>,
+        /// 同时检查多个文件时使用的线程数: auto (默认) 使用可用的 CPU 核数，
+        /// 正整数固定线程数，1 在当前线程中依次检查；`--parallel` 为旧名称
+        #[arg(long, alias = "parallel", value_name = "auto|N")]
+        threads: Option<Threads>,
+        /// 同时打开的 .aos 文件数上限，与线程数无关，默认为系统软限制的一半
+        #[arg(long, value_name = "N")]
+        max_open_files: Option<usize>,
+        #[command(flatten)]
+        layout: LayoutArgs,
+        #[command(flatten)]
+        names: NameArgs,
+        /// 数据区起始偏移量不是 N 的整数倍时给出警告，用于检查对齐敏感的引擎所需的封包
+        #[arg(long, value_name = "N")]
+        expect_data_align: Option<NonZeroU64>,
+        /// 统计数据区中未被条目覆盖的空隙，区分对齐填充与浪费的空间
+        #[arg(long)]
+        check_gaps: bool,
+        /// 要求目录表中条目的偏移量单调不减，报告第一个违反该约定的位置
+        #[arg(long)]
+        require_sorted_offsets: bool,
+        /// 名称为空的条目只记为警告而不是错误，与 `unpack --skip-unnamed` 对应
+        #[arg(long)]
+        skip_unnamed: bool,
+        /// 检查多个文件时以未通过的文件数 (最大 255) 作为退出码，而不是统一为 1
+        #[arg(long)]
+        exit_code_count: bool,
+    },
+    /// 打印版本、支持的格式及已启用的可选特性
+    VersionInfo,
+    /// 供开发者使用: 打印 info 或 list 的 JSON 输出文档的 JSON Schema，需要启用 schema 特性
+    JsonSchema {
+        /// 文档类型: info 或 list
+        #[arg(value_name = "DOCUMENT")]
+        document: SchemaDocument,
     },
 }
 
-fn main() -> Result<()> {
+fn main() -> Result<ExitCode> {
     let cli = Cli::parse();
+    if cli.log_format == LogFormat::Json {
+        enable_json_events();
+    }
+    if cli.silent {
+        set_silent();
+    }
+    if cli.no_lock {
+        disable_locking();
+    }
+    let config = Config::load(cli.config.as_deref())?;
+    match run(&cli.command, &config).inspect_err(emit_error) {
+        Err(err) if err.is::<Cancelled>() => {
+            eprintln!("{err}");
+            Ok(ExitCode::from(EXIT_CANCELLED))
+        }
+        result => result,
+    }
+}
 
-    match &cli.command {
-        Commands::Unpack { archive_path } => {
-            if !archive_path.exists() || !archive_path.is_file() {
-                bail!(
-                    "错误: 文件 '{}' 不存在或不是一个有效的文件。",
-                    archive_path.display()
-                );
+fn run(command: &Commands, config: &Config) -> Result<ExitCode> {
+    match command {
+        Commands::Unpack {
+            archive_path,
+            output,
+            prefix,
+            layout,
+            names,
+            filter,
+            retry,
+            op_timeout,
+            expect_data_align,
+            write_index,
+            sort_by_offset,
+            skip_toc_validation,
+            sanitize,
+            sanitize_char,
+            extended,
+            trim_trailing_nulls,
+            names_from,
+            strict,
+            preserve_footer,
+            content_type,
+            compute_base,
+            json_stream,
+            preserve_case_db,
+            debug_offsets,
+            transliterate,
+            pipeline,
+            skip_unnamed,
+            flatten_duplicates_report,
+            recursive_unpack,
+            max_depth,
+            compare_with_dir: compare,
+            as_zip,
+            decode_text,
+            text_bom,
+        } => {
+            ensure_archive(archive_path)?;
+            if "\\/<>:\"|?*".contains(*sanitize_char) || sanitize_char.is_control() {
+                bail!("替换字符 {sanitize_char:?} 本身不能是非法字符或路径分隔符");
             }
-            unpack_archive(archive_path)?;
+            let options = UnpackOptions {
+                output_dir: output.clone(),
+                prefix: prefix.clone(),
+                layout: layout.layout(config)?,
+                names: names.decoder(config),
+                filter: filter.filter()?,
+                retry: retry.or(config.retry).unwrap_or(0),
+                op_timeout: op_timeout
+                    .or(config.op_timeout)
+                    .map(|secs| Duration::from_secs(secs.get())),
+                expect_data_align: expect_data_align.or(config.expect_data_align),
+                index: write_index.clone(),
+                sort_by_offset: *sort_by_offset,
+                best_effort: *skip_toc_validation,
+                sanitize: sanitize.then_some(*sanitize_char),
+                extended: extended.extended,
+                flags_file: extended.flags_file.clone(),
+                trim_trailing_nulls: trim_trailing_nulls.clone(),
+                names_from: names_from.clone(),
+                strict_names: *strict,
+                footer: preserve_footer.clone(),
+                content_type: content_type.clone(),
+                compute_base: *compute_base,
+                cancel: Some(install_cancel_handler()),
+                json_stream: *json_stream,
+                case_db: preserve_case_db.clone(),
+                debug_offsets: *debug_offsets,
+                transliterate: *transliterate,
+                pipeline: *pipeline,
+                skip_unnamed: *skip_unnamed,
+                recursive: recursive_unpack.then(|| max_depth.unwrap_or(DEFAULT_MAX_DEPTH)),
+                zip: as_zip
+                    .as_deref()
+                    .map(|path| resolve_output_path(path, None))
+                    .transpose()?,
+                decode_text: decode_text.clone(),
+                text_bom: *text_bom,
+            };
+            // 标准输出只保留 JSON 行
+            if *json_stream {
+                set_silent();
+            }
+            if *compare {
+                let differs = compare_with_dir(archive_path, &options)?;
+                return Ok(if differs {
+                    ExitCode::FAILURE
+                } else {
+                    ExitCode::SUCCESS
+                });
+            }
+            let duplicates = if *flatten_duplicates_report {
+                report_duplicate_names(archive_path, &options)?
+            } else {
+                0
+            };
+            unpack_archive(archive_path, &options)?;
+            if duplicates > 0 {
+                return Ok(ExitCode::from(EXIT_DUPLICATE_NAMES));
+            }
+        }
+        Commands::Pack {
+            dir_path,
+            pack,
+            estimate,
+            diff_against,
+        } => {
+            ensure_dir(dir_path)?;
+            let options = PackOptions {
+                // 只有实际封包时才检查取消标记
+                cancel: (!*estimate && diff_against.is_none()).then(install_cancel_handler),
+                ..pack.options(config)?
+            };
+            if let Some(archive_path) = diff_against {
+                ensure_file(archive_path)?;
+                if diff_pack_plan(dir_path, &options, archive_path)? {
+                    return Ok(ExitCode::FAILURE);
+                }
+            } else if *estimate {
+                estimate_pack(dir_path, &options)?;
+            } else {
+                pack_directory(dir_path, &options)?;
+            }
+        }
+        Commands::Watch {
+            dir_path,
+            pack,
+            debounce,
+        } => {
+            ensure_dir(dir_path)?;
+            let options = pack.options(config)?;
+            #[cfg(feature = "watch")]
+            aos_up::watch_directory(dir_path, &options, Duration::from_millis(*debounce))?;
+            #[cfg(not(feature = "watch"))]
+            {
+                let _ = (options, debounce);
+                bail!("此构建未启用 watch 特性");
+            }
+        }
+        Commands::List {
+            archive_path,
+            only_names,
+            print0,
+            output_format,
+            csv,
+            layout,
+            names,
+            filter,
+            sort,
+            reverse,
+            checksum,
+            inline_data,
+            max_inline_size,
+        } => {
+            ensure_archive(archive_path)?;
+            let options = ListOptions {
+                only_names: *only_names,
+                print0: *print0,
+                format: *output_format,
+                filter: filter.filter()?,
+                csv: csv.clone(),
+                layout: layout.layout(config)?,
+                names: names.decoder(config),
+                sort: *sort,
+                descending: *reverse,
+                checksum: *checksum,
+                inline_data: inline_data
+                    .then(|| max_inline_size.map_or(DEFAULT_MAX_INLINE_SIZE, |size| size.0)),
+            };
+            list_archive(archive_path, &options)?;
+        }
+        Commands::Info {
+            archive_path,
+            output_format,
+            layout,
+        } => {
+            ensure_archive(archive_path)?;
+            let options = InfoOptions {
+                format: *output_format,
+                layout: layout.layout(config)?,
+            };
+            info_archive(archive_path, &options)?;
         }
-        Commands::Pack { dir_path } => {
-            if !dir_path.exists() || !dir_path.is_dir() {
-                bail!(
-                    "错误: 目录 '{}' 不存在或不是一个有效的目录。",
-                    dir_path.display()
+        Commands::Probe { path } => {
+            ensure_archive(path)?;
+            let report = probe_file(path)?;
+            println!("{}", report.class);
+            for finding in &report.findings {
+                println!("  - {finding}");
+            }
+            return Ok(ExitCode::from(match report.class {
+                ProbeClass::V2 => 0,
+                ProbeClass::Nonstandard => EXIT_PROBE_NONSTANDARD,
+                ProbeClass::NotAos => EXIT_PROBE_NOT_AOS,
+            }));
+        }
+        Commands::Hexdump {
+            archive_path,
+            name,
+            skip,
+            length,
+            layout,
+            names,
+        } => {
+            ensure_archive(archive_path)?;
+            let options = HexdumpOptions {
+                skip: *skip,
+                length: *length,
+                layout: layout.layout(config)?,
+                names: names.decoder(config),
+            };
+            hexdump_entry(archive_path, name, &options)?;
+        }
+        Commands::Browse {
+            archive_path,
+            output,
+            layout,
+            names,
+        } => {
+            ensure_archive(archive_path)?;
+            #[cfg(feature = "tui")]
+            aos_up::browse_archive(
+                archive_path,
+                &aos_up::BrowseOptions {
+                    layout: layout.layout(config)?,
+                    names: names.decoder(config),
+                    output_dir: output.clone(),
+                },
+            )?;
+            #[cfg(not(feature = "tui"))]
+            {
+                let _ = (output, layout, names);
+                bail!("此构建未启用 tui 特性");
+            }
+        }
+        Commands::DumpData {
+            archive_path,
+            output,
+        } => {
+            ensure_archive(archive_path)?;
+            let output = resolve_output_path(output, None)?;
+            let copied = dump_data(archive_path, &output)?;
+            progress!("已导出 {copied} 字节的数据区到: {}", output.display());
+        }
+        Commands::ExtractSparse {
+            archive_path,
+            output,
+            layout,
+        } => {
+            ensure_archive(archive_path)?;
+            let output = resolve_output_path(output, None)?;
+            let count = extract_sparse(archive_path, &output, layout.layout(config)?)?;
+            progress!(
+                "已将 {count} 个条目按原偏移量写入: {}，条目映射: {}.map",
+                output.display(),
+                output.display()
+            );
+        }
+        Commands::HashManifest {
+            archive_path,
+            output,
+            algorithm,
+            layout,
+            names,
+        } => {
+            ensure_archive(archive_path)?;
+            let output = resolve_output_path(output, None)?;
+            let options = HashManifestOptions {
+                layout: layout.layout(config)?,
+                names: names.decoder(config),
+                algorithm: *algorithm,
+            };
+            let count = hash_manifest(archive_path, &output, &options)?;
+            progress!(
+                "已记录整个文件与 {count} 个条目的校验值: {}",
+                output.display()
+            );
+        }
+        Commands::RebuildToc {
+            data_path,
+            layout_file,
+            output,
+            layout,
+        } => {
+            let output = resolve_output_path(output, None)?;
+            let count = rebuild_toc(data_path, layout_file, &output, layout.layout(config)?)?;
+            progress!("已重建包含 {count} 个条目的封包: {}", output.display());
+        }
+        Commands::Normalize {
+            archive_path,
+            output_archive,
+            order,
+            dry_run,
+            layout,
+            names,
+        } => {
+            ensure_file(archive_path)?;
+            let options = NormalizeOptions {
+                output_archive: output_archive
+                    .as_deref()
+                    .map(|path| resolve_output_path(path, None))
+                    .transpose()?,
+                layout: layout.layout(config)?,
+                order: *order,
+                names: names.decoder(config),
+                dry_run: *dry_run,
+            };
+            let output = normalize_archive(archive_path, &options)?;
+            if !dry_run {
+                progress!("规范化完成，输出文件: {}", output.display());
+            }
+        }
+        Commands::Repack {
+            archive_path,
+            dir_path,
+            output_archive,
+            layout,
+            rename_map,
+            preserve_footer,
+            sync_archive_name,
+            dry_run,
+        } => {
+            ensure_file(archive_path)?;
+            ensure_dir(dir_path)?;
+            let output_archive = output_archive
+                .as_deref()
+                .map(|path| resolve_output_path(path, None))
+                .transpose()?;
+            let options = RepackOptions {
+                output_archive,
+                layout: layout.layout(config)?,
+                rename_map: rename_map.clone(),
+                preserve_footer: *preserve_footer,
+                sync_archive_name: *sync_archive_name,
+                dry_run: *dry_run,
+            };
+            repack_archive(archive_path, dir_path, &options)?;
+        }
+        Commands::Diff { old_path, new_path } => {
+            for path in [old_path, new_path] {
+                ensure_file(path)?;
+            }
+            if diff_archives(old_path, new_path)? {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Commands::Add {
+            archive_path,
+            file_path,
+            name,
+            force,
+            dry_run,
+            layout,
+        } => {
+            ensure_file(archive_path)?;
+            ensure_file(file_path)?;
+            let options = AddOptions {
+                name: name.clone(),
+                layout: layout.layout(config)?,
+                force: *force,
+                dry_run: *dry_run,
+            };
+            add_entry(archive_path, file_path, &options)?;
+        }
+        Commands::Split {
+            archive_path,
+            max_size,
+            prefix,
+            layout,
+        } => {
+            ensure_file(archive_path)?;
+            let options = SplitOptions {
+                max_size: *max_size,
+                prefix: prefix.clone(),
+                layout: layout.layout(config)?,
+            };
+            split_archive(archive_path, &options)?;
+        }
+        Commands::VerifyAgainst {
+            archive_path,
+            dir_path,
+        } => {
+            ensure_file(archive_path)?;
+            ensure_dir(dir_path)?;
+            if verify_against(archive_path, dir_path)? {
+                return Ok(ExitCode::FAILURE);
+            }
+        }
+        Commands::Validate {
+            archive_paths,
+            threads,
+            max_open_files,
+            layout,
+            names,
+            expect_data_align,
+            check_gaps,
+            require_sorted_offsets,
+            skip_unnamed,
+            exit_code_count,
+        } => {
+            let options = ValidateOptions {
+                layout: layout.layout(config)?,
+                names: names.decoder(config),
+                expect_data_align: expect_data_align.or(config.expect_data_align),
+                check_gaps: *check_gaps,
+                require_sorted_offsets: *require_sorted_offsets,
+                skip_unnamed: *skip_unnamed,
+            };
+            // 单个文件时直接列出问题，多个文件或目录时按文件分段输出并汇总
+            if let [archive_path] = archive_paths.as_slice()
+                && !archive_path.is_dir()
+            {
+                ensure_file(archive_path)?;
+            } else {
+                let paths = expand_archive_paths(archive_paths)?;
+                let failed = validate_batch(
+                    &paths,
+                    &options,
+                    threads.or(config.threads).unwrap_or_default(),
+                    max_open_files
+                        .or(config.max_open_files)
+                        .unwrap_or_else(default_max_open_files),
                 );
+                return Ok(ExitCode::from(batch_exit_status(
+                    failed.len(),
+                    *exit_code_count,
+                )));
+            }
+            let archive_path = &archive_paths[0];
+            let report = validate_archive(archive_path, &options)?;
+            // 静默模式下只在失败时将问题输出到标准错误
+            if is_silent() {
+                if !report.is_ok() {
+                    eprint!("{}", report.to_text(""));
+                    return Ok(ExitCode::FAILURE);
+                }
+                return Ok(ExitCode::SUCCESS);
             }
-            pack_directory(dir_path)?;
+            print!("{}", report.to_text(""));
+            if !report.is_ok() {
+                println!("校验失败，共 {} 个错误。", report.errors.len());
+                return Ok(ExitCode::FAILURE);
+            }
+            println!("校验通过。");
         }
+        Commands::VersionInfo => print_version_info(),
+        Commands::JsonSchema { document } => println!("{}", json_schema(*document)?),
     }
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(
+        lossy_names: bool,
+        no_lossy_names: bool,
+        name_encoding: Option<NameEncoding>,
+    ) -> NameArgs {
+        NameArgs {
+            lossy_names,
+            no_lossy_names,
+            name_style: NameStyle::Nul,
+            name_encoding,
+        }
+    }
+
+    #[test]
+    fn watch_accepts_the_pack_options() {
+        let args = [
+            "aos",
+            "watch",
+            "assets",
+            "-r",
+            "--max-depth",
+            "2",
+            "--dedup",
+            "--pad-to",
+            "2048",
+            "--rename-map",
+            "names.tsv",
+        ];
+        let Commands::Watch { pack, .. } = Cli::try_parse_from(args).unwrap().command else {
+            panic!("应解析为 watch 命令");
+        };
+        let options = pack.options(&Config::default()).unwrap();
+        assert!(options.recursive && options.dedup);
+        assert_eq!(options.max_depth, Some(2));
+        assert_eq!(options.pad_to.map(NonZeroU64::get), Some(2048));
+        assert_eq!(options.rename_map.as_deref(), Some(Path::new("names.tsv")));
+    }
+
+    #[test]
+    fn command_line_overrides_config_name_options() {
+        let config = Config {
+            lossy_names: Some(true),
+            name_encoding: Some(NameEncoding::ShiftJis),
+            ..Default::default()
+        };
+        let decoder = names(false, false, None).decoder(&config);
+        assert!(decoder.lossy);
+        assert_eq!(decoder.encoding, NameEncoding::ShiftJis);
+
+        let decoder = names(false, true, Some(NameEncoding::Utf8)).decoder(&config);
+        assert!(!decoder.lossy);
+        assert_eq!(decoder.encoding, NameEncoding::Utf8);
+
+        let decoder = names(true, false, None).decoder(&Config::default());
+        assert!(decoder.lossy);
+        assert_eq!(decoder.encoding, NameEncoding::Utf8);
+    }
 }
@@ -0,0 +1,133 @@
+//! `hash-manifest`: 记录整个 .aos 文件与每个条目的校验值，用于追踪构建产物的来源
+
+use std::{fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    archive::Archive,
+    format::{Layout, NameDecoder},
+    hash::{ChecksumAlgo, checksum_range},
+    output::{EntryInfo, HashManifest, OutputFormat, render},
+    pack::temp_path,
+    source::Source,
+    validate::check_entry_bounds,
+};
+
+/// 校验值清单选项
+#[derive(Debug)]
+pub struct HashManifestOptions {
+    /// 目录表布局
+    pub layout: Layout,
+    /// 文件名解码方式
+    pub names: NameDecoder,
+    /// 校验算法
+    pub algorithm: ChecksumAlgo,
+}
+
+impl Default for HashManifestOptions {
+    fn default() -> Self {
+        Self {
+            layout: Layout::default(),
+            names: NameDecoder::default(),
+            algorithm: ChecksumAlgo::Sha256,
+        }
+    }
+}
+
+/// 计算整个文件与每个条目数据的校验值，以 JSON 写入 `output_path`，返回记录的条目数
+///
+/// 整个文件的校验值可以发现对封包的任何修改，包括目录表与尾部数据；条目的校验值用于定位
+/// 内容发生变化的条目。需要启用 `json` 特性。
+pub fn hash_manifest(
+    archive_path: &Path,
+    output_path: &Path,
+    options: &HashManifestOptions,
+) -> Result<usize> {
+    // 在读取整个文件之前报错
+    if !cfg!(feature = "json") {
+        bail!("此构建未启用 json 特性，无法写入清单");
+    }
+    let mut source = Source::open(archive_path)?;
+    let archive = Archive::from_reader_with_layout(&mut source, options.layout)?;
+    let names = options.names.resolve(&archive.entries)?;
+    let base_offset = archive.base_offset()?;
+    let file_len = source.len()?;
+    let algorithm = options.algorithm;
+
+    let mut entries = Vec::with_capacity(archive.entries.len());
+    for (index, entry) in archive.entries.iter().enumerate() {
+        if entry.is_padding() {
+            continue;
+        }
+        let name = names
+            .decode(entry)
+            .with_context(|| options.layout.entry_location(index))?;
+        if let Some(error) = check_entry_bounds(&name, entry, base_offset, file_len) {
+            bail!("{}: {error}", options.layout.entry_location(index));
+        }
+        let checksum = checksum_range(
+            &mut source,
+            base_offset + entry.offset as u64,
+            entry.length as u64,
+            algorithm,
+        )
+        .with_context(|| format!("无法读取条目 '{name}'"))?;
+        entries.push(EntryInfo {
+            name,
+            offset: entry.offset,
+            length: entry.length,
+            checksum: Some(checksum),
+            data: None,
+        });
+    }
+    let hash = checksum_range(&mut source, 0, file_len, algorithm)
+        .with_context(|| format!("无法读取文件: {}", archive_path.display()))?;
+
+    let manifest = HashManifest {
+        algorithm: format!("{algorithm:?}").to_lowercase(),
+        archive: archive_path
+            .file_name()
+            .unwrap_or(archive_path.as_os_str())
+            .to_string_lossy()
+            .into_owned(),
+        size: file_len,
+        hash,
+        entries,
+    };
+    let count = manifest.entries.len();
+    let mut text = render(&manifest, OutputFormat::Json)?;
+    text.push('\n');
+    let temp = temp_path(output_path, "manifest");
+    fs::write(&temp, text)
+        .and_then(|()| fs::rename(&temp, output_path))
+        .with_context(|| format!("无法写入清单文件: {}", output_path.display()))
+        .inspect_err(|_| {
+            let _ = fs::remove_file(&temp);
+        })?;
+    Ok(count)
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+    use crate::testutil::{TempDir, make_padded_archive};
+
+    #[test]
+    fn entry_hashes_cover_entry_data_in_padded_archive() {
+        let dir = TempDir::new("manifest-padded");
+        let bytes = make_padded_archive(&[("a", b"abc")], "t.aos", b"ZZZZ");
+        let archive = dir.write("test.aos", &bytes);
+        let output = dir.path().join("manifest.json");
+        let count = hash_manifest(&archive, &output, &HashManifestOptions::default()).unwrap();
+        assert_eq!(count, 1);
+
+        let manifest: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&output).unwrap()).unwrap();
+        assert_eq!(manifest["size"], bytes.len());
+        assert_eq!(
+            manifest["entries"][0]["checksum"],
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! 解包目录旁的 `.aosmeta` 元数据文件，用于重新封包时恢复文件头中的字段
+//!
+//! 解包 `xxx.aos` 到目录 `xxx` 时写入 `xxx.aosmeta`，封包目录 `xxx` 时自动读取。
+//! 文件为 UTF-8 文本，每行一个 `键 = 值`，空行与以 `#` 开头的行被忽略，
+//! `unknown1` 的值为十进制或以 `0x` 开头的十六进制数；可选的 `pad_to` 为十进制正整数，
+//! 记录 `unpack --preserve-footer` 识别出的末尾对齐填充，封包时据此补齐，而不是把填充当作尾部数据。
+//! 未知的键给出警告后忽略。
+
+use std::{
+    fs,
+    io::ErrorKind,
+    num::NonZeroU64,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::events;
+
+/// 元数据文件的扩展名
+pub(crate) const META_EXTENSION: &str = "aosmeta";
+
+/// 元数据文件中记录的文件头字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ArchiveMeta {
+    pub unknown1: u32,
+    /// 文件末尾的空字节是补齐到该值整数倍的填充，对应 `pack --pad-to`
+    pub pad_to: Option<NonZeroU64>,
+}
+
+/// 目录对应的元数据文件路径，目录没有名称 (例如 `.`) 时为 `None`
+pub(crate) fn meta_path(dir: &Path) -> Option<PathBuf> {
+    let name = dir.file_name()?.to_string_lossy();
+    Some(dir.with_file_name(format!("{name}.{META_EXTENSION}")))
+}
+
+/// 写入目录旁的元数据文件
+pub(crate) fn write_meta(dir: &Path, meta: ArchiveMeta) -> Result<()> {
+    let Some(path) = meta_path(dir) else {
+        return Ok(());
+    };
+    let mut content = format!(
+        "# aos_up 元数据，封包目录 {} 时自动读取\nunknown1 = {:#010x}\n",
+        dir.file_name().unwrap_or_default().to_string_lossy(),
+        meta.unknown1
+    );
+    if let Some(pad_to) = meta.pad_to {
+        content += &format!("pad_to = {pad_to}\n");
+    }
+    match fs::write(&path, content) {
+        Err(err) if err.kind() == ErrorKind::InvalidFilename => {
+            events::warn(
+                "meta_skipped",
+                None,
+                &format!(
+                    "目录名过长，无法在旁边写入元数据文件，重新封包时需用 --tag {:#010x} 指定 unknown1",
+                    meta.unknown1
+                ),
+            );
+            Ok(())
+        }
+        result => result.with_context(|| format!("无法写入元数据文件: {}", path.display())),
+    }
+}
+
+/// 读取目录旁的元数据文件，文件不存在时返回 `None`
+pub(crate) fn read_meta(dir: &Path) -> Result<Option<(PathBuf, ArchiveMeta)>> {
+    let Some(path) = meta_path(dir) else {
+        return Ok(None);
+    };
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        // 目录名接近文件名长度上限时加上扩展名会过长，这样的元数据文件不可能存在
+        Err(err) if matches!(err.kind(), ErrorKind::NotFound | ErrorKind::InvalidFilename) => {
+            return Ok(None);
+        }
+        Err(err) => {
+            return Err(err).with_context(|| format!("无法读取元数据文件: {}", path.display()));
+        }
+    };
+    let (mut unknown1, mut pad_to) = (None, None);
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let number = number + 1;
+        let Some((key, value)) = line.split_once('=') else {
+            bail!("元数据文件 {} 第 {number} 行应为 `键 = 值`", path.display());
+        };
+        let (key, value) = (key.trim(), value.trim());
+        match key {
+            "unknown1" => {
+                let parsed = match value.strip_prefix("0x").or(value.strip_prefix("0X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16),
+                    None => value.parse(),
+                };
+                unknown1 = Some(parsed.with_context(|| {
+                    format!(
+                        "元数据文件 {} 第 {number} 行的 unknown1 '{value}' 无效",
+                        path.display()
+                    )
+                })?);
+            }
+            "pad_to" => {
+                pad_to = Some(value.parse::<NonZeroU64>().with_context(|| {
+                    format!(
+                        "元数据文件 {} 第 {number} 行的 pad_to '{value}' 无效",
+                        path.display()
+                    )
+                })?);
+            }
+            _ => events::warn(
+                "unknown_meta_key",
+                None,
+                &format!("元数据文件 {} 中的未知键 '{key}' 被忽略", path.display()),
+            ),
+        }
+    }
+    Ok(unknown1.map(|unknown1| (path, ArchiveMeta { unknown1, pad_to })))
+}
@@ -0,0 +1,276 @@
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    archive::{Archive, SortKey},
+    extended,
+    format::{ARCHIVE_NAME_SIZE, AosV2Entry, AosV2Hdr, FILENAME_SIZE, Layout, NameDecoder},
+    lock,
+    plan::DryRunPlan,
+    read_at::read_exact_at,
+    repack::same_file,
+    validate::check_entry_bounds,
+};
+
+/// 规范形式中条目的顺序
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, palc::ValueEnum)]
+pub enum NormalizeOrder {
+    /// 保持原目录表顺序
+    #[default]
+    Original,
+    /// 按文件名排序
+    Name,
+}
+
+/// 规范化选项
+#[derive(Debug, Default)]
+pub struct NormalizeOptions {
+    /// 输出文件路径，为 `None` 时输出到原文件旁的 `<名称>_normalized.aos`
+    pub output_archive: Option<PathBuf>,
+    /// 目录表布局，输出中的保留字段以 0 填充
+    pub layout: Layout,
+    /// 条目的顺序
+    pub order: NormalizeOrder,
+    /// 按名称排序时文件名的解码方式，名称本身按原始字节写入输出
+    pub names: NameDecoder,
+    /// 只打印偏移量与文件大小的变化，不写入输出文件
+    pub dry_run: bool,
+}
+
+/// 以规范形式重写 .aos 文件，返回输出文件路径
+///
+/// 条目按 `order` 排列，数据按条目顺序紧密排列、不留空隙，`data_offset` 设为文件头与
+/// 目录表的总大小，名称字段在结尾的空字符之后以 0 填充。引用同一段数据的条目仍共享数据，
+/// 全为 0 的填充条目被删除。对已是规范形式的文件，输出与原文件逐字节相同。
+/// `dry_run` 时只打印规划，返回的路径不会被创建。
+pub fn normalize_archive(archive_path: &Path, options: &NormalizeOptions) -> Result<PathBuf> {
+    let output_filename = options.output_archive.clone().unwrap_or_else(|| {
+        let stem = archive_path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        archive_path.with_file_name(format!("{stem}_normalized.aos"))
+    });
+    if output_filename.exists() && same_file(&output_filename, archive_path)? {
+        bail!("输出文件不能与原文件相同: {}", output_filename.display());
+    }
+
+    let mut file = lock::open_shared(archive_path)?;
+    let archive = Archive::from_reader_with_layout(&mut file, options.layout)?;
+    if extended::is_extended(&archive.header) {
+        bail!("暂不支持规范化扩展格式的封包");
+    }
+    let base_offset = archive.base_offset()?;
+    let file_len = file.metadata()?.len();
+    if let Some((_, length)) = archive.footer(file_len)? {
+        progress!("  警告: 最后一个条目之后的 {length} 字节尾部数据不会写入输出");
+    }
+
+    let order: Vec<usize> = match options.order {
+        NormalizeOrder::Original => (0..archive.entries.len()).collect(),
+        NormalizeOrder::Name => {
+            let names = options.names.resolve(&archive.entries)?;
+            archive.sorted_indices(SortKey::Name, false, &names)
+        }
+    };
+    let padding = order
+        .iter()
+        .filter(|&&index| archive.entries[index].is_padding())
+        .count();
+    if padding > 0 {
+        progress!("  删除了目录表中 {padding} 个全为 0 的填充条目");
+    }
+
+    // 按输出顺序为每段数据分配新的偏移量，相同的原数据范围只写入一次
+    let mut entries = Vec::with_capacity(order.len());
+    // 输出中每个条目在原目录表中的序号
+    let mut sources = Vec::with_capacity(order.len());
+    let mut ranges = Vec::new();
+    let mut placed: HashMap<(u32, u32), u32> = HashMap::new();
+    let mut data_size = 0u32;
+    for &index in &order {
+        let entry = &archive.entries[index];
+        if entry.is_padding() {
+            continue;
+        }
+        let name = String::from_utf8_lossy(entry.raw_filename());
+        if let Some(error) = check_entry_bounds(&name, entry, base_offset, file_len) {
+            bail!("{}: {error}", options.layout.entry_location(index));
+        }
+        let key = (entry.offset, entry.length);
+        let offset = match placed.get(&key) {
+            Some(&offset) => offset,
+            None => {
+                let offset = data_size;
+                data_size = data_size
+                    .checked_add(entry.length)
+                    .context("数据区超出 4 GiB，无法封包")?;
+                ranges.push((base_offset + entry.offset as u64, entry.length));
+                placed.insert(key, offset);
+                offset
+            }
+        };
+        let mut filename = [0u8; FILENAME_SIZE];
+        let raw = entry.raw_filename();
+        filename[..raw.len()].copy_from_slice(raw);
+        entries.push(AosV2Entry {
+            filename,
+            offset,
+            length: entry.length,
+        });
+        sources.push(index);
+    }
+
+    let toc_length = (entries.len() * options.layout.entry_size()) as u32;
+    let mut archive_name = [0u8; ARCHIVE_NAME_SIZE];
+    let name_len = archive
+        .header
+        .archive_name
+        .iter()
+        .position(|&c| c == 0)
+        .unwrap_or(ARCHIVE_NAME_SIZE);
+    archive_name[..name_len].copy_from_slice(&archive.header.archive_name[..name_len]);
+    let header = AosV2Hdr {
+        unknown1: archive.header.unknown1,
+        data_offset: std::mem::size_of::<AosV2Hdr>() as u32 + toc_length,
+        toc_length,
+        archive_name,
+    };
+
+    if options.dry_run {
+        let new_len = header.data_offset as u64
+            + entries
+                .iter()
+                .map(|entry| entry.offset as u64 + entry.length as u64)
+                .max()
+                .unwrap_or(0);
+        normalize_plan(&archive, &header, &entries, &sources, padding).print(
+            &output_filename,
+            file_len,
+            new_len,
+        );
+        return Ok(output_filename);
+    }
+
+    let output = File::create(&output_filename)
+        .with_context(|| format!("无法创建输出文件: {}", output_filename.display()))?;
+    let mut output = BufWriter::new(output);
+    output.write_all(&header.to_bytes())?;
+    for entry in &entries {
+        output.write_all(&options.layout.entry_to_bytes(entry))?;
+    }
+    let mut buffer = Vec::new();
+    for (start, length) in ranges {
+        buffer.resize(length as usize, 0);
+        read_exact_at(&file, &mut buffer, start)?;
+        output.write_all(&buffer)?;
+    }
+    output
+        .flush()
+        .with_context(|| format!("无法写入输出文件: {}", output_filename.display()))?;
+    Ok(output_filename)
+}
+
+/// `dry_run` 时规范化的规划，`sources` 为输出中每个条目在原目录表中的序号
+fn normalize_plan(
+    archive: &Archive,
+    header: &AosV2Hdr,
+    entries: &[AosV2Entry],
+    sources: &[usize],
+    padding: usize,
+) -> DryRunPlan {
+    let mut plan = DryRunPlan::default();
+    let (old_base, new_base) = ({ archive.header.data_offset }, { header.data_offset });
+    if old_base != new_base {
+        plan.line(format!("data_offset: {old_base} -> {new_base}"));
+    }
+    if padding > 0 {
+        plan.line(format!("删除 {padding} 个全为 0 的填充条目"));
+    }
+    let mut unchanged = 0usize;
+    for (position, (entry, &index)) in entries.iter().zip(sources).enumerate() {
+        let original = &archive.entries[index];
+        let (old_offset, new_offset) = ({ original.offset }, { entry.offset });
+        let name = String::from_utf8_lossy(entry.raw_filename());
+        if index != position {
+            plan.line(format!(
+                "条目 '{name}': 序号 {index} -> {position}，偏移量 {old_offset} -> {new_offset}"
+            ));
+        } else if old_offset != new_offset {
+            plan.line(format!(
+                "条目 '{name}': 偏移量 {old_offset} -> {new_offset}"
+            ));
+        } else {
+            unchanged += 1;
+        }
+    }
+    plan.line(format!("{unchanged} 个条目的位置不变"));
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        testutil::{Defect, TempDir, make_corrupt_archive, make_padded_archive, make_test_archive},
+        validate::{ValidateOptions, validate_archive},
+    };
+
+    const ENTRIES: &[(&str, &[u8])] = &[("a", b"abc"), ("b", b"de")];
+
+    fn normalize_bytes(dir: &TempDir, bytes: &[u8]) -> Vec<u8> {
+        let archive = dir.write("test.aos", bytes);
+        let output = normalize_archive(&archive, &NormalizeOptions::default()).unwrap();
+        fs::read(output).unwrap()
+    }
+
+    #[test]
+    fn canonical_archive_is_unchanged() {
+        let dir = TempDir::new("normalize-canonical");
+        let bytes = make_test_archive(ENTRIES, "t.aos");
+        assert_eq!(normalize_bytes(&dir, &bytes), bytes);
+    }
+
+    #[test]
+    fn padded_data_offset_is_removed() {
+        let dir = TempDir::new("normalize-padded");
+        let bytes = make_padded_archive(ENTRIES, "t.aos", b"ZZZZ");
+        assert_eq!(
+            normalize_bytes(&dir, &bytes),
+            make_test_archive(ENTRIES, "t.aos")
+        );
+    }
+
+    #[test]
+    fn dry_run_writes_nothing() {
+        let dir = TempDir::new("normalize-dry-run");
+        let bytes = make_padded_archive(ENTRIES, "t.aos", b"ZZZZ");
+        let archive = dir.write("test.aos", &bytes);
+        let options = NormalizeOptions {
+            dry_run: true,
+            ..Default::default()
+        };
+        let output = normalize_archive(&archive, &options).unwrap();
+        assert!(!output.exists());
+        assert_eq!(fs::read(archive).unwrap(), bytes);
+    }
+
+    #[test]
+    fn padding_entries_are_dropped() {
+        let dir = TempDir::new("normalize-zero-entries");
+        let bytes = make_corrupt_archive(ENTRIES, "t.aos", Defect::TrailingZeroEntries);
+        let output = normalize_bytes(&dir, &bytes);
+        assert_eq!(output, make_test_archive(ENTRIES, "t.aos"));
+        let path = dir.write("normalized.aos", &output);
+        let report = validate_archive(&path, &ValidateOptions::default()).unwrap();
+        assert!(report.is_ok(), "{:?}", report.errors);
+    }
+}
@@ -0,0 +1,168 @@
+//! `info` / `list` 等命令的结构化输出
+
+use anyhow::Result;
+
+/// 结构化输出的格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, palc::ValueEnum)]
+pub enum OutputFormat {
+    /// 供人阅读的文本
+    #[default]
+    Text,
+    Json,
+    Yaml,
+    Toml,
+}
+
+/// 可按 [`OutputFormat`] 序列化的文档
+///
+/// 未启用 `serde` 特性时任何类型都满足该约束，但只能以文本格式输出。
+#[cfg(feature = "serde")]
+pub trait Document: serde::Serialize {}
+#[cfg(feature = "serde")]
+impl<T: serde::Serialize> Document for T {}
+
+#[cfg(not(feature = "serde"))]
+pub trait Document {}
+#[cfg(not(feature = "serde"))]
+impl<T> Document for T {}
+
+/// 单个条目的信息
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct EntryInfo {
+    pub name: String,
+    pub offset: u32,
+    pub length: u32,
+    /// 条目数据的校验值，只在 `list --checksum` 与 `hash-manifest` 时计算
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub checksum: Option<String>,
+    /// Base64 编码的条目数据，只在 `list --inline-data` 时填写；超过大小上限的条目省略该字段，
+    /// 可按 `offset` 与 `length` 从封包中读取
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub data: Option<String>,
+}
+
+/// `list` 命令输出的文档
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ListDocument {
+    pub entries: Vec<EntryInfo>,
+}
+
+/// `info` 命令输出的文档
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct InfoDocument {
+    pub archive_name: String,
+    pub unknown1: u32,
+    pub data_offset: u32,
+    pub toc_length: u32,
+    pub entry_count: usize,
+    pub file_size: u64,
+    /// 最后一个条目之后的尾部数据字节数
+    pub footer_length: u64,
+    /// 扩展格式文件头中的注释，没有注释时各格式均省略该字段
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub comment: Option<String>,
+}
+
+/// `hash-manifest` 命令写入的清单
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct HashManifest {
+    /// 校验算法，为 `crc32`、`xxh3` 或 `sha256`
+    pub algorithm: String,
+    /// .aos 文件名，不含目录
+    pub archive: String,
+    /// 整个文件的字节数
+    pub size: u64,
+    /// 整个文件的校验值，覆盖文件头、目录表、数据区与尾部数据
+    pub hash: String,
+    /// 按目录表顺序排列的条目，`checksum` 为条目数据的校验值；全为 0 的填充条目不包括在内
+    pub entries: Vec<EntryInfo>,
+}
+
+/// 可以输出 JSON Schema 的文档
+#[derive(Debug, Clone, Copy, PartialEq, Eq, palc::ValueEnum)]
+pub enum SchemaDocument {
+    /// `info` 命令输出的 [`InfoDocument`]
+    Info,
+    /// `list` 命令与 `unpack --write-index` 输出的 [`ListDocument`]
+    List,
+    /// `hash-manifest` 命令写入的 [`HashManifest`]
+    HashManifest,
+}
+
+/// 由输出文档的类型生成其 JSON Schema，保证与实际序列化的结构一致
+#[cfg(feature = "schema")]
+pub fn json_schema(document: SchemaDocument) -> Result<String> {
+    let schema = match document {
+        SchemaDocument::Info => schemars::schema_for!(InfoDocument),
+        SchemaDocument::List => schemars::schema_for!(ListDocument),
+        SchemaDocument::HashManifest => schemars::schema_for!(HashManifest),
+    };
+    Ok(serde_json::to_string_pretty(&schema)?)
+}
+
+#[cfg(not(feature = "schema"))]
+pub fn json_schema(_document: SchemaDocument) -> Result<String> {
+    anyhow::bail!("此构建未启用 schema 特性，无法生成 JSON Schema")
+}
+
+/// 将文档序列化为指定的非文本格式，结果不带结尾的换行符
+pub(crate) fn render<T: Document>(document: &T, format: OutputFormat) -> Result<String> {
+    #[cfg(not(any(feature = "json", feature = "yaml", feature = "toml")))]
+    let _ = document;
+    match format {
+        OutputFormat::Text => unreachable!("文本格式由各命令自行输出"),
+        #[cfg(feature = "json")]
+        OutputFormat::Json => Ok(trimmed(serde_json::to_string_pretty(document)?)),
+        #[cfg(feature = "yaml")]
+        OutputFormat::Yaml => Ok(trimmed(serde_yaml::to_string(document)?)),
+        #[cfg(feature = "toml")]
+        OutputFormat::Toml => Ok(trimmed(toml::to_string_pretty(document)?)),
+        #[allow(unreachable_patterns)]
+        other => anyhow::bail!("此构建未启用 {} 特性", format!("{other:?}").to_lowercase()),
+    }
+}
+
+#[cfg(any(feature = "json", feature = "yaml", feature = "toml"))]
+fn trimmed(mut text: String) -> String {
+    text.truncate(text.trim_end().len());
+    text
+}
+
+#[cfg(all(test, feature = "json", feature = "yaml", feature = "toml"))]
+mod tests {
+    use super::*;
+
+    fn info(comment: Option<&str>) -> InfoDocument {
+        InfoDocument {
+            archive_name: "t.aos".to_owned(),
+            unknown1: 0,
+            data_offset: 313,
+            toc_length: 40,
+            entry_count: 1,
+            file_size: 317,
+            footer_length: 0,
+            comment: comment.map(str::to_owned),
+        }
+    }
+
+    #[test]
+    fn missing_comment_is_omitted_in_every_format() {
+        for format in [OutputFormat::Json, OutputFormat::Yaml, OutputFormat::Toml] {
+            let text = render(&info(None), format).unwrap();
+            assert!(!text.contains("comment"), "{format:?}: {text}");
+            let text = render(&info(Some("build 42")), format).unwrap();
+            assert!(
+                text.contains("comment") && text.contains("build 42"),
+                "{format:?}: {text}"
+            );
+        }
+    }
+}
@@ -0,0 +1,884 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs::{self, File},
+    io::Write,
+    num::NonZeroU64,
+    path::{Path, PathBuf},
+    sync::{Arc, atomic::AtomicBool},
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+use walkdir::WalkDir;
+use xxhash_rust::xxh3::xxh3_64;
+
+use crate::{
+    cancel,
+    events::{self, Event, Progress},
+    extended::{self, EXTENDED_MAGIC, extended_layout},
+    format::{ARCHIVE_NAME_SIZE, AosV2Entry, AosV2Hdr, FILENAME_SIZE, Layout, NameStyle},
+    limit::{OpenFileLimit, default_max_open_files},
+    meta, rename,
+    retry::with_retry,
+    size::ByteSize,
+    text,
+    timeout::with_timeout,
+};
+
+/// 封包选项
+#[derive(Debug, Default)]
+pub struct PackOptions {
+    /// 输出文件路径，为 `None` 时输出到目录同名的 .aos 文件
+    pub output_archive: Option<PathBuf>,
+    /// 目录表布局，保留字段以 0 填充
+    pub layout: Layout,
+    /// 读写文件遇到暂时性 IO 错误时总共最多尝试的次数，0 与 1 都表示不重试
+    pub retry: u32,
+    /// 读取单个文件 (包括重试) 超过该时间仍未完成时以超时错误中止，
+    /// 不限制最后写入整个封包的时间
+    pub op_timeout: Option<Duration>,
+    /// 封包后的总大小超过该值时在写入前中止
+    pub max_archive_size: Option<ByteSize>,
+    /// 内容完全相同的文件只存储一份数据，其条目共享同一偏移量
+    pub dedup: bool,
+    /// 写入本工具的扩展格式，每个条目带有一个标记字段，游戏引擎无法读取
+    pub extended: bool,
+    /// 扩展格式下各文件的标记，未列出的文件标记为 0
+    pub flags_file: Option<PathBuf>,
+    /// 重命名表，每行为以制表符分隔的原文件名与存储在封包中的新名称
+    pub rename_map: Option<PathBuf>,
+    /// 报告完成前调用 `sync_all` 将输出文件写入磁盘
+    pub sync: bool,
+    /// 追加到数据区之后的尾部数据文件，通常由 `unpack --preserve-footer` 保存
+    pub footer: Option<PathBuf>,
+    /// 在最后 (尾部数据之后) 追加空字节，使文件大小为该值的整数倍，用于要求按扇区对齐的引擎。
+    /// 填充位于所有条目的数据之后，解包时作为尾部数据处理。
+    /// 为 `None` 时使用目录旁 `.aosmeta` 中记录的值 (见 `unpack --preserve-footer`)
+    pub pad_to: Option<NonZeroU64>,
+    /// 扩展格式下写入文件头的注释，例如构建标识或时间
+    pub comment: Option<String>,
+    /// 标准格式下写入文件头 `unknown1` 的标识，游戏引擎不使用该字段。
+    /// 为 `None` 时使用目录旁 `.aosmeta` 文件中记录的值，没有该文件时为 0
+    pub tag: Option<u32>,
+    /// 文件名字段的存储方式
+    pub name_style: NameStyle,
+    /// 内嵌的封包名称使用实际输出的文件名，而不是 `<目录名>.aos`
+    pub sync_archive_name: bool,
+    /// 递归封包子目录中的文件，条目名称为以 `/` 分隔的相对路径
+    pub recursive: bool,
+    /// 条目名称为文件相对于该目录的路径，而不是相对于要封包的目录；文件必须位于其下
+    pub name_root: Option<PathBuf>,
+    /// 递归封包时只包含根目录以下至多 N 层的文件，1 表示只包含根目录中的文件
+    pub max_depth: Option<usize>,
+    /// 取消标记，每读入一个文件前以及写入输出前检查，被设置时以 [`Cancelled`](crate::Cancelled)
+    /// 错误中止；输出在全部文件读入后才写入，因此取消不会留下不完整的输出文件
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// 逐行列出文件名的顺序文件，目录表按其中的顺序排列；名称为重命名之前相对于名称根目录的路径。
+    /// 未列出的文件按名称排序后追加在最后
+    pub order_file: Option<PathBuf>,
+    /// 与 `order_file` 同时使用时，目录中存在未列出的文件则报错而不是追加
+    pub strict_order: bool,
+    /// 条目名称匹配该正则表达式的文件按 UTF-8 文本 (可带 BOM) 转换为 Shift-JIS 后存储，
+    /// 无法表示的字符替换为 `?` 并给出警告。大小上限与进度按转换前的文件大小计算
+    pub encode_text: Option<Regex>,
+    /// 同时打开的源文件数上限，为 `None` 时使用 [`default_max_open_files`]。
+    /// `op_timeout` 超时后仍在后台读取的文件继续占用名额，直到读取结束
+    pub max_open_files: Option<usize>,
+}
+
+impl PackOptions {
+    pub(crate) fn renames(&self) -> Result<BTreeMap<String, String>> {
+        self.rename_map
+            .as_deref()
+            .map_or_else(|| Ok(BTreeMap::new()), rename::read_rename_map)
+    }
+
+    /// 实际写入的目录表布局，扩展格式使用固定的条目大小
+    pub(crate) fn entry_layout(&self) -> Layout {
+        if self.extended {
+            extended_layout()
+        } else {
+            self.layout
+        }
+    }
+
+    /// 计算条目名称时的根目录，默认为要封包的目录
+    pub(crate) fn name_root<'a>(&'a self, dir_path: &'a Path) -> &'a Path {
+        self.name_root.as_deref().unwrap_or(dir_path)
+    }
+
+    /// 收集文件时的最大深度，不递归时只包含根目录中的文件
+    fn walk_depth(&self) -> usize {
+        if self.recursive {
+            self.max_depth.unwrap_or(usize::MAX)
+        } else {
+            1
+        }
+    }
+
+    pub(crate) fn output_filename(&self, dir_path: &Path) -> PathBuf {
+        self.output_archive
+            .clone()
+            .unwrap_or_else(|| dir_path.with_extension("aos"))
+    }
+}
+
+/// 收集目录中要封包的文件，递归时超出最大深度的文件被跳过并报告其数量
+pub(crate) fn collect_files(dir_path: &Path, options: &PackOptions) -> Result<Vec<PathBuf>> {
+    // 排除输出文件自身，避免读取即将被覆盖的旧封包并生成层层嵌套的封包
+    let output_canonical = canonicalize_output(&options.output_filename(dir_path));
+    let max_depth = options.walk_depth();
+    let mut too_deep = 0usize;
+    let mut files = Vec::new();
+    // 不递归时不进入子目录，与只读取根目录等价
+    let walker = WalkDir::new(dir_path)
+        .min_depth(1)
+        .max_depth(if options.recursive { usize::MAX } else { 1 });
+    for entry in walker {
+        let entry = entry.with_context(|| format!("无法读取目录: {}", dir_path.display()))?;
+        let path = entry.into_path();
+        if !path.is_file() {
+            continue;
+        }
+        if relative_depth(dir_path, &path) > max_depth {
+            too_deep += 1;
+            continue;
+        }
+        files.push(path);
+    }
+    if too_deep > 0 {
+        progress!("  注意: 跳过了 {too_deep} 个超出最大深度 {max_depth} 的文件");
+    }
+
+    let files_to_pack: Vec<PathBuf> = files
+        .into_iter()
+        .filter(|path| {
+            let is_output =
+                output_canonical.is_some() && fs::canonicalize(path).ok() == output_canonical;
+            if is_output {
+                events::warn(
+                    "skip_output",
+                    Some(&path.to_string_lossy()),
+                    &format!("跳过输出文件自身: {}", path.display()),
+                );
+            }
+            !is_output
+        })
+        .collect();
+
+    if files_to_pack.is_empty() {
+        bail!("目录为空，没有可封包的文件。");
+    }
+    match &options.order_file {
+        Some(order_file) => apply_order(
+            options.name_root(dir_path),
+            files_to_pack,
+            order_file,
+            options,
+        ),
+        None => Ok(files_to_pack),
+    }
+}
+
+/// 按顺序文件排列文件，未列出的文件按名称排序后追加，列出但不存在的文件给出警告
+fn apply_order(
+    root: &Path,
+    files: Vec<PathBuf>,
+    order_file: &Path,
+    options: &PackOptions,
+) -> Result<Vec<PathBuf>> {
+    let content = fs::read_to_string(order_file)
+        .with_context(|| format!("无法读取顺序文件: {}", order_file.display()))?;
+    let mut remaining: BTreeMap<String, PathBuf> = BTreeMap::new();
+    for path in files {
+        remaining.insert(relative_name(root, &path)?, path);
+    }
+    let mut listed = HashSet::new();
+    let mut ordered = Vec::with_capacity(remaining.len());
+    for (number, line) in content.lines().enumerate() {
+        let name = line.trim_end_matches('\r');
+        if name.trim().is_empty() {
+            continue;
+        }
+        if !listed.insert(name) {
+            bail!("顺序文件第 {} 行重复列出了 '{name}'", number + 1);
+        }
+        match remaining.remove(name) {
+            Some(path) => ordered.push(path),
+            None => events::warn(
+                "order_missing",
+                Some(name),
+                &format!("顺序文件中的 '{name}' 没有对应的文件"),
+            ),
+        }
+    }
+    if !remaining.is_empty() {
+        let names: Vec<&str> = remaining.keys().map(String::as_str).collect();
+        if options.strict_order {
+            bail!("以下文件没有在顺序文件中列出: {}", names.join(", "));
+        }
+        progress!(
+            "  注意: {} 个文件没有在顺序文件中列出，按名称排序后追加在最后",
+            names.len()
+        );
+    }
+    ordered.extend(remaining.into_values());
+    Ok(ordered)
+}
+
+fn relative_depth(dir_path: &Path, file_path: &Path) -> usize {
+    file_path
+        .strip_prefix(dir_path)
+        .map_or(1, |relative| relative.components().count())
+}
+
+/// 文件相对于名称根目录的路径，以 `/` 分隔
+///
+/// 无法直接去掉前缀时 (例如根目录为 `..`) 按规范路径计算，文件不在根目录之下时报错。
+pub(crate) fn relative_name(root: &Path, file_path: &Path) -> Result<String> {
+    let relative = match file_path.strip_prefix(root) {
+        Ok(relative) => relative.to_path_buf(),
+        Err(_) => {
+            let canonical_root = fs::canonicalize(root)
+                .with_context(|| format!("无法解析名称根目录: {}", root.display()))?;
+            // 只解析父目录，文件本身是符号链接时不跟随
+            let parent = match file_path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            let canonical = fs::canonicalize(parent)
+                .with_context(|| format!("无法解析路径: {}", file_path.display()))?
+                .join(file_path.file_name().context("文件名无效")?);
+            canonical
+                .strip_prefix(&canonical_root)
+                .ok()
+                .with_context(|| {
+                    format!(
+                        "文件 {} 不在名称根目录 {} 之下",
+                        file_path.display(),
+                        root.display()
+                    )
+                })?
+                .to_path_buf()
+        }
+    };
+    let parts = relative
+        .components()
+        .map(|part| part.as_os_str().to_str().context("文件名无效"))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(parts.join("/"))
+}
+
+/// 获取文件在封包中存储的名称，按重命名表替换后检查其长度
+pub(crate) fn stored_name(
+    root: &Path,
+    file_path: &Path,
+    renames: &BTreeMap<String, String>,
+) -> Result<String> {
+    let filename = relative_name(root, file_path)?;
+    let filename = renames.get(&filename).cloned().unwrap_or(filename);
+
+    if filename.len() >= FILENAME_SIZE {
+        bail!(
+            "文件名 '{}' 过长 (最大 {} 字节)",
+            filename,
+            FILENAME_SIZE - 1
+        );
+    }
+    Ok(filename)
+}
+
+/// 检查文件名并读取每个文件的大小
+pub(crate) fn file_sizes(
+    root: &Path,
+    files: &[PathBuf],
+    renames: &BTreeMap<String, String>,
+) -> Result<Vec<u64>> {
+    files
+        .iter()
+        .map(|file_path| {
+            stored_name(root, file_path, renames)?;
+            Ok(fs::metadata(file_path)
+                .with_context(|| format!("无法读取文件信息: {}", file_path.display()))?
+                .len())
+        })
+        .collect()
+}
+
+/// 封包后的文件头与目录表大小
+fn metadata_size(entry_count: usize, layout: Layout) -> (u64, u64) {
+    let header_size = std::mem::size_of::<AosV2Hdr>() as u64;
+    (header_size, (entry_count * layout.entry_size()) as u64)
+}
+
+/// 末尾填充的对齐值，未指定 `pad_to` 时沿用目录旁 .aosmeta 中记录的值
+fn pad_to(dir_path: &Path, options: &PackOptions) -> Result<Option<NonZeroU64>> {
+    if options.pad_to.is_some() {
+        return Ok(options.pad_to);
+    }
+    let recorded = meta::read_meta(dir_path)?
+        .and_then(|(path, meta)| meta.pad_to.map(|pad_to| (path, pad_to)));
+    Ok(recorded.map(|(path, pad_to)| {
+        progress!("从 {} 恢复 pad_to: {pad_to}", path.display());
+        pad_to
+    }))
+}
+
+/// 大小为 `size` 的文件补齐到 `pad_to` 的整数倍所需的空字节数
+fn padding_len(size: u64, pad_to: Option<NonZeroU64>) -> u64 {
+    pad_to.map_or(0, |align| (align.get() - size % align.get()) % align.get())
+}
+
+/// 封包后的总大小超过上限时报错，并列出占用最大的几个文件
+pub(crate) fn check_max_size(
+    files: &[PathBuf],
+    sizes: &[u64],
+    layout: Layout,
+    max: ByteSize,
+) -> Result<()> {
+    const LARGEST_SHOWN: usize = 5;
+
+    let (header_size, toc_length) = metadata_size(files.len(), layout);
+    let total = header_size + toc_length + sizes.iter().sum::<u64>();
+    if total <= max.0 {
+        return Ok(());
+    }
+
+    let mut largest: Vec<(&PathBuf, u64)> = files.iter().zip(sizes.iter().copied()).collect();
+    largest.sort_by_key(|&(_, size)| std::cmp::Reverse(size));
+    progress!("占用最大的文件:");
+    for (path, size) in largest.into_iter().take(LARGEST_SHOWN) {
+        progress!("  {} ({})", path.display(), ByteSize(size));
+    }
+    bail!(
+        "封包后的大小 {} 超过上限 {}，未写入任何文件",
+        ByteSize(total),
+        max
+    );
+}
+
+/// 只读取文件大小，估算封包后的文件大小，不读取任何文件内容
+pub fn estimate_pack(dir_path: &Path, options: &PackOptions) -> Result<()> {
+    let files_to_pack = collect_files(dir_path, options)?;
+
+    let data_size: u64 = file_sizes(
+        options.name_root(dir_path),
+        &files_to_pack,
+        &options.renames()?,
+    )?
+    .iter()
+    .sum();
+    let (header_size, toc_length) = metadata_size(files_to_pack.len(), options.entry_layout());
+    let footer_size = match &options.footer {
+        Some(path) => fs::metadata(path)
+            .with_context(|| format!("无法读取尾部数据文件: {}", path.display()))?
+            .len(),
+        None => 0,
+    };
+
+    let size = header_size + toc_length + data_size + footer_size;
+    let padding = padding_len(size, pad_to(dir_path, options)?);
+
+    println!("条目数量: {}", files_to_pack.len());
+    println!("文件头: {header_size} 字节");
+    println!("目录表: {toc_length} 字节");
+    println!("数据区: {data_size} 字节");
+    if footer_size > 0 {
+        println!("尾部数据: {footer_size} 字节");
+    }
+    if padding > 0 {
+        println!("末尾填充: {padding} 字节");
+    }
+    println!("封包后大小: {} 字节", size + padding);
+    Ok(())
+}
+
+/// 封包一个目录
+pub fn pack_directory(dir_path: &Path, options: &PackOptions) -> Result<()> {
+    pack(dir_path, options, None)
+}
+
+/// 封包一个目录，每读入一个文件后调用一次 `progress`
+///
+/// 总字节数为所有文件的大小之和，在读取前通过文件元数据得到。
+pub fn pack_directory_with_progress(
+    dir_path: &Path,
+    options: &PackOptions,
+    mut progress: impl FnMut(Progress),
+) -> Result<()> {
+    pack(dir_path, options, Some(&mut progress))
+}
+
+fn pack(
+    dir_path: &Path,
+    options: &PackOptions,
+    mut progress: Option<&mut dyn FnMut(Progress)>,
+) -> Result<()> {
+    progress!("正在封包目录: {}", dir_path.display());
+    Event {
+        event: "pack",
+        file: Some(&dir_path.to_string_lossy()),
+        ..Default::default()
+    }
+    .emit();
+
+    let output_filename = options.output_filename(dir_path);
+    let files_to_pack = collect_files(dir_path, options)?;
+    let renames = options.renames()?;
+    let mut bytes_total = 0u64;
+    if options.max_archive_size.is_some() || progress.is_some() {
+        let sizes = file_sizes(options.name_root(dir_path), &files_to_pack, &renames)?;
+        if let Some(max) = options.max_archive_size {
+            check_max_size(&files_to_pack, &sizes, options.entry_layout(), max)?;
+        }
+        bytes_total = sizes.iter().sum();
+    }
+    let mut bytes_done = 0u64;
+
+    // 1. 构建目录表 (TOC) 和计算数据区
+    let mut entries = Vec::new();
+    let mut data_blob = Vec::new();
+    let mut current_offset = 0u32;
+    // 去重时按内容摘要记录已写入数据的偏移量
+    let mut blobs: HashMap<u64, Vec<u32>> = HashMap::new();
+    let mut saved = 0u64;
+    let mut flags = match &options.flags_file {
+        Some(path) => extended::read_flags_file(path)?,
+        None => BTreeMap::new(),
+    };
+    let mut entry_flags = Vec::with_capacity(files_to_pack.len());
+    let limit = Arc::new(OpenFileLimit::new(
+        options
+            .max_open_files
+            .unwrap_or_else(default_max_open_files),
+    ));
+
+    for file_path in &files_to_pack {
+        cancel::check(options.cancel.as_deref())?;
+        let filename = stored_name(options.name_root(dir_path), file_path, &renames)?;
+        let filename = filename.as_str();
+
+        let (retry, path, limit) = (options.retry, file_path.clone(), Arc::clone(&limit));
+        let file_data = with_timeout(options.op_timeout, move || {
+            let _permit = limit.acquire();
+            with_retry(retry, || fs::read(&path))
+        })
+        .with_context(|| format!("无法读取文件: {}", file_path.display()))?;
+        let file_data = match &options.encode_text {
+            Some(pattern) if pattern.is_match(filename) => text::encode_sjis(filename, &file_data)?,
+            _ => file_data,
+        };
+        let file_length =
+            u32::try_from(file_data.len()).context(format!("文件 '{filename}' 过大"))?;
+
+        let filename_bytes = options.name_style.encode(filename.as_bytes())?;
+
+        // 摘要相同时再逐字节比较，避免哈希碰撞导致条目指向错误的数据
+        let shared = options.dedup.then(|| {
+            let candidates = blobs.entry(xxh3_64(&file_data)).or_default();
+            let found = candidates.iter().copied().find(|&offset: &u32| {
+                let start = offset as usize;
+                data_blob.get(start..start + file_data.len()) == Some(&file_data[..])
+            });
+            if found.is_none() {
+                candidates.push(current_offset);
+            }
+            found
+        });
+
+        let offset = match shared.flatten() {
+            Some(offset) => {
+                saved += file_length as u64;
+                offset
+            }
+            None => {
+                data_blob.extend_from_slice(&file_data);
+                let offset = current_offset;
+                current_offset = current_offset
+                    .checked_add(file_length)
+                    .context("数据区超出 4 GiB，无法封包")?;
+                offset
+            }
+        };
+        Event {
+            event: "add",
+            file: Some(filename),
+            offset: Some(offset as u64),
+            length: Some(file_length as u64),
+            ..Default::default()
+        }
+        .emit();
+        entry_flags.push(flags.remove(filename).unwrap_or(0));
+        entries.push(AosV2Entry {
+            filename: filename_bytes,
+            offset,
+            length: file_length,
+        });
+        bytes_done += file_length as u64;
+        if let Some(progress) = progress.as_mut() {
+            progress(Progress {
+                file: filename,
+                bytes_done,
+                bytes_total,
+            });
+        }
+    }
+    if options.dedup {
+        progress!("去重节省了 {}", ByteSize(saved));
+    }
+    for name in flags.keys() {
+        events::warn(
+            "unused_flags",
+            Some(name),
+            &format!("标记文件中的 '{name}' 没有对应的文件"),
+        );
+    }
+    let mut unused = renames.clone();
+    unused.retain(|old, _| {
+        !files_to_pack.iter().any(|path| {
+            relative_name(options.name_root(dir_path), path).is_ok_and(|name| name == *old)
+        })
+    });
+    rename::warn_unused(&unused);
+
+    // 2. 构建文件头
+    let layout = options.entry_layout();
+    let header = build_header(dir_path, entries.len(), options)?;
+
+    let footer = options
+        .footer
+        .as_deref()
+        .map(|path| {
+            fs::read(path).with_context(|| format!("无法读取尾部数据文件: {}", path.display()))
+        })
+        .transpose()?;
+    let (header_size, toc_length) = metadata_size(entries.len(), layout);
+    let padding = padding_len(
+        header_size
+            + toc_length
+            + data_blob.len() as u64
+            + footer.as_ref().map_or(0, |f| f.len() as u64),
+        pad_to(dir_path, options)?,
+    );
+    if padding > 0 {
+        progress!("末尾填充 {padding} 个空字节");
+    }
+
+    // 3. 先写入临时文件再替换输出文件，重试时重新创建文件以免残留部分写入的数据
+    cancel::check(options.cancel.as_deref())?;
+    let temp_filename = temp_path(&output_filename, "pack");
+    let written = with_retry(options.retry, || {
+        let mut output_file = File::create(&temp_filename)?;
+
+        // 写入文件头
+        output_file.write_all(&header.to_bytes())?;
+
+        // 写入目录表
+        for (entry, &flags) in entries.iter().zip(&entry_flags) {
+            let bytes = if options.extended {
+                extended::entry_to_bytes(entry, flags)
+            } else {
+                layout.entry_to_bytes(entry)
+            };
+            output_file.write_all(&bytes)?;
+        }
+
+        // 写入文件数据
+        output_file.write_all(&data_blob)?;
+        if let Some(footer) = &footer {
+            output_file.write_all(footer)?;
+        }
+        output_file.write_all(&vec![0u8; padding as usize])?;
+
+        if options.sync {
+            output_file.sync_all()?;
+        }
+        Ok(())
+    })
+    .with_context(|| format!("无法写入输出文件: {}", output_filename.display()))
+    // 写入期间收到取消请求时丢弃临时文件，不替换原有的输出文件
+    .and_then(|()| cancel::check(options.cancel.as_deref()));
+    if let Err(err) = written {
+        let _ = fs::remove_file(&temp_filename);
+        return Err(err);
+    }
+    fs::rename(&temp_filename, &output_filename)
+        .with_context(|| format!("无法写入输出文件: {}", output_filename.display()))?;
+
+    progress!("封包完成，输出文件: {}", output_filename.display());
+    Event {
+        event: "done",
+        file: Some(&output_filename.to_string_lossy()),
+        ..Default::default()
+    }
+    .emit();
+    Ok(())
+}
+
+/// 构建文件头，内嵌的封包名称为 `<目录名>.aos`，指定 `sync_archive_name` 时为输出文件名
+///
+/// 扩展格式的 `unknown1` 为标识，可以附带注释；标准格式的 `unknown1` 可以写入自定义标识。
+pub(crate) fn build_header(
+    dir_path: &Path,
+    entry_count: usize,
+    options: &PackOptions,
+) -> Result<AosV2Hdr> {
+    let archive_name_str = if options.sync_archive_name {
+        output_file_name(&options.output_filename(dir_path))
+    } else {
+        let dir_name = dir_path
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or("archive");
+        format!("{dir_name}.aos")
+    };
+    let mut archive_name_bytes = archive_name_field(&archive_name_str);
+
+    let unknown1 = match (options.extended, options.tag) {
+        (true, Some(_)) => bail!("扩展格式的 unknown1 用于格式标识，不能同时指定标识值"),
+        (true, None) => EXTENDED_MAGIC,
+        (false, Some(tag)) => tag,
+        // 未指定标识时沿用解包时记录在目录旁 .aosmeta 中的值
+        (false, None) => match meta::read_meta(dir_path)? {
+            Some((path, meta)) if meta.unknown1 == EXTENDED_MAGIC => {
+                events::warn(
+                    "meta_ignored",
+                    None,
+                    &format!(
+                        "{} 记录的是扩展格式的标识，按标准格式封包时不使用",
+                        path.display()
+                    ),
+                );
+                0
+            }
+            Some((path, meta)) => {
+                progress!(
+                    "从 {} 恢复 unknown1: {:#010x}",
+                    path.display(),
+                    meta.unknown1
+                );
+                meta.unknown1
+            }
+            None => 0,
+        },
+    };
+    if let Some(comment) = &options.comment {
+        if !options.extended {
+            bail!("只有扩展格式可以写入注释");
+        }
+        extended::write_comment(&mut archive_name_bytes, comment)?;
+    }
+
+    let header_size = std::mem::size_of::<AosV2Hdr>() as u32;
+    let toc_length = entry_count
+        .checked_mul(options.entry_layout().entry_size())
+        .and_then(|length| u32::try_from(length).ok())
+        .filter(|length| length.checked_add(header_size).is_some())
+        .context(format!("{entry_count} 个条目的目录表超出 4 GiB，无法封包"))?;
+
+    Ok(AosV2Hdr {
+        unknown1,
+        data_offset: header_size + toc_length,
+        toc_length,
+        archive_name: archive_name_bytes,
+    })
+}
+
+/// 与 `path` 位于同一目录的临时文件，保证最后的重命名不跨文件系统
+pub(crate) fn temp_path(path: &Path, purpose: &str) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{name}.{purpose}.tmp"))
+}
+
+/// 输出文件的文件名部分，用于写入内嵌的封包名称
+pub(crate) fn output_file_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// 将封包名称写入定长字段
+pub(crate) fn archive_name_field(name: &str) -> [u8; ARCHIVE_NAME_SIZE] {
+    let mut field = [0u8; ARCHIVE_NAME_SIZE];
+    // 确保不会因为文件名过长而 panic，且截断位置落在字符边界上以免产生无效的 UTF-8
+    let name_len = name.floor_char_boundary(ARCHIVE_NAME_SIZE - 1);
+    field[..name_len].copy_from_slice(&name.as_bytes()[..name_len]);
+    field
+}
+
+/// 获取输出文件的规范路径，输出文件尚不存在时基于其父目录计算
+pub(crate) fn canonicalize_output(path: &Path) -> Option<PathBuf> {
+    if let Ok(canonical) = fs::canonicalize(path) {
+        return Some(canonical);
+    }
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    Some(fs::canonicalize(parent).ok()?.join(path.file_name()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeSet, sync::atomic::Ordering};
+
+    use super::*;
+    use crate::{
+        archive::Archive,
+        testutil::{GOLDEN_ARCHIVE, TempDir, make_test_archive},
+    };
+
+    #[test]
+    fn pack_reproduces_golden_archive() {
+        let dir = TempDir::new("pack-golden");
+        dir.write("golden/hello.txt", b"hi!\n");
+        let output = dir.path().join("out.aos");
+        let options = PackOptions {
+            output_archive: Some(output.clone()),
+            ..Default::default()
+        };
+        pack_directory(&dir.path().join("golden"), &options).unwrap();
+        assert_eq!(fs::read(output).unwrap(), GOLDEN_ARCHIVE);
+    }
+
+    #[test]
+    fn test_archive_builder_matches_golden_archive() {
+        assert_eq!(
+            make_test_archive(&[("hello.txt", b"hi!\n")], "golden.aos"),
+            GOLDEN_ARCHIVE
+        );
+    }
+
+    #[test]
+    fn dedup_stores_identical_files_once() {
+        let dir = TempDir::new("pack-dedup");
+        for name in ["a.png", "b.png", "c.png"] {
+            dir.write(&format!("assets/{name}"), b"placeholder");
+        }
+        dir.write("assets/d.txt", b"other");
+        let data_region = |dedup: bool| {
+            let output = dir.path().join(format!("dedup-{dedup}.aos"));
+            let options = PackOptions {
+                output_archive: Some(output.clone()),
+                dedup,
+                ..Default::default()
+            };
+            pack_directory(&dir.path().join("assets"), &options).unwrap();
+            let bytes = fs::read(&output).unwrap();
+            let archive = Archive::from_reader(&mut bytes.as_slice()).unwrap();
+            let offsets: BTreeSet<u32> = archive.entries.iter().map(|entry| entry.offset).collect();
+            assert_eq!(archive.entries.len(), 4);
+            (
+                bytes.len() as u64 - archive.base_offset().unwrap(),
+                offsets.len(),
+            )
+        };
+        assert_eq!(data_region(false), (38, 4));
+        assert_eq!(data_region(true), (16, 2));
+    }
+
+    #[test]
+    fn many_files_pack_with_a_low_open_file_limit() {
+        let dir = TempDir::new("pack-limit");
+        for index in 0..300 {
+            dir.write(
+                &format!("many/{index:03}.bin"),
+                index.to_string().as_bytes(),
+            );
+        }
+        let output = dir.path().join("many.aos");
+        let options = PackOptions {
+            output_archive: Some(output.clone()),
+            // 超时时每个文件在单独的线程中读取，许可随线程一起释放
+            op_timeout: Some(Duration::from_secs(30)),
+            max_open_files: Some(1),
+            ..Default::default()
+        };
+        pack_directory(&dir.path().join("many"), &options).unwrap();
+        let bytes = fs::read(&output).unwrap();
+        let archive = Archive::from_reader(&mut bytes.as_slice()).unwrap();
+        assert_eq!(archive.entries.len(), 300);
+        let base = archive.base_offset().unwrap() as usize;
+        for entry in &archive.entries {
+            let name = entry.get_filename_str().unwrap();
+            let start = base + entry.offset as usize;
+            let data = &bytes[start..start + entry.length as usize];
+            let expected = name.trim_end_matches(".bin").parse::<u32>().unwrap();
+            assert_eq!(data, expected.to_string().as_bytes(), "{name}");
+        }
+    }
+
+    #[test]
+    fn cancel_mid_pack_keeps_existing_output() {
+        let dir = TempDir::new("pack-cancel");
+        for name in ["a", "b", "c"] {
+            dir.write(&format!("files/{name}"), b"abc");
+        }
+        let output = dir.write("out.aos", b"old");
+        let cancel = Arc::new(AtomicBool::new(false));
+        let options = PackOptions {
+            output_archive: Some(output.clone()),
+            cancel: Some(cancel.clone()),
+            ..Default::default()
+        };
+        let mut packed = 0;
+        let err = pack_directory_with_progress(&dir.path().join("files"), &options, |_| {
+            packed += 1;
+            cancel.store(true, Ordering::Relaxed);
+        })
+        .unwrap_err();
+        assert!(err.is::<crate::Cancelled>(), "{err:#}");
+        assert_eq!(packed, 1);
+        assert_eq!(fs::read(&output).unwrap(), b"old");
+        assert!(!temp_path(&output, "pack").exists());
+    }
+
+    #[test]
+    fn oversized_archive_is_rejected_before_writing() {
+        let dir = TempDir::new("pack-max-size");
+        dir.write("files/a", &[0; 400]);
+        let output = dir.path().join("out.aos");
+        let options = PackOptions {
+            output_archive: Some(output.clone()),
+            max_archive_size: Some(ByteSize(400)),
+            ..Default::default()
+        };
+        let err = pack_directory(&dir.path().join("files"), &options).unwrap_err();
+        assert!(format!("{err:#}").contains("超过上限"), "{err:#}");
+        assert!(!output.exists());
+    }
+
+    #[test]
+    fn archive_name_is_truncated_on_a_char_boundary() {
+        // 前缀让 3 字节字符在不同位置跨过 260 字节的上限
+        for prefix in ["", "x", "xx"] {
+            let name = format!("{prefix}{}.aos", "あ".repeat(90));
+            let field = archive_name_field(&name);
+            let len = field.iter().position(|&b| b == 0).unwrap();
+            let stored = std::str::from_utf8(&field[..len]).unwrap();
+            assert!(name.starts_with(stored));
+            assert!(len < ARCHIVE_NAME_SIZE && len > ARCHIVE_NAME_SIZE - 4);
+        }
+    }
+
+    #[test]
+    fn pack_with_long_multibyte_directory_name() {
+        let dir = TempDir::new("pack-long-name");
+        // 84 个 3 字节字符，加上 .aos 共 256 字节，接近文件名与封包名称字段的上限
+        let dir_name = "あ".repeat(84);
+        dir.write(&format!("{dir_name}/a"), b"abc");
+        let output = dir.path().join("out.aos");
+        let options = PackOptions {
+            output_archive: Some(output.clone()),
+            ..Default::default()
+        };
+        pack_directory(&dir.path().join(&dir_name), &options).unwrap();
+        let archive = Archive::from_reader(&mut fs::File::open(output).unwrap()).unwrap();
+        assert_eq!(
+            archive.header.archive_name_lossy(),
+            format!("{dir_name}.aos")
+        );
+    }
+}
@@ -0,0 +1,170 @@
+//! 解包的读写流水线: 调用方读取条目数据，后台线程写出文件
+//!
+//! 源文件与输出目录位于不同的设备时，读取下一个条目与写入上一个条目可以同时进行。
+//! 队列有固定的长度，读取比写入快时调用方会等待，内存占用不超过队列长度个条目的大小。
+
+use std::{
+    mem,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, SyncSender},
+    },
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{Result, anyhow};
+
+use crate::events;
+
+/// 队列中最多等待写入的条目数
+pub(crate) const PIPELINE_DEPTH: usize = 8;
+
+type Job = (String, Box<dyn FnOnce() -> Result<()> + Send>);
+
+/// 在后台线程中按提交顺序执行写入任务
+pub(crate) struct WriteQueue {
+    sender: Option<SyncSender<Job>>,
+    handle: Option<JoinHandle<Result<usize>>>,
+    /// 已写入成功的条目名称，按完成顺序排列
+    written: Arc<Mutex<Vec<String>>>,
+    /// 被设置后写入线程丢弃队列中尚未开始的任务
+    aborted: Arc<AtomicBool>,
+}
+
+impl WriteQueue {
+    /// 创建写入线程。`best_effort` 时写入失败的条目给出警告后跳过，否则任务失败后线程退出
+    pub(crate) fn new(best_effort: bool) -> Self {
+        let (sender, receiver) = mpsc::sync_channel::<Job>(PIPELINE_DEPTH);
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let aborted = Arc::new(AtomicBool::new(false));
+        let (confirmed, abort) = (Arc::clone(&written), Arc::clone(&aborted));
+        let handle = thread::spawn(move || {
+            let mut failed = 0;
+            for (name, job) in receiver {
+                if abort.load(Ordering::Relaxed) {
+                    continue;
+                }
+                match job() {
+                    Ok(()) => confirmed.lock().unwrap().push(name),
+                    Err(err) if best_effort => {
+                        events::warn(
+                            "skip_entry",
+                            Some(&name),
+                            &format!("跳过条目 '{name}': {err:#}"),
+                        );
+                        failed += 1;
+                    }
+                    Err(err) => return Err(err),
+                }
+            }
+            Ok(failed)
+        });
+        Self {
+            sender: Some(sender),
+            handle: Some(handle),
+            written,
+            aborted,
+        }
+    }
+
+    /// 提交条目 `name` 的写入任务，队列已满时等待。写入线程此前已失败时返回其错误
+    pub(crate) fn push(
+        &mut self,
+        name: String,
+        job: impl FnOnce() -> Result<()> + Send + 'static,
+    ) -> Result<()> {
+        let sender = self.sender.as_ref().expect("写入队列已关闭");
+        if sender.send((name, Box::new(job))).is_ok() {
+            return Ok(());
+        }
+        // 接收端只会在写入线程退出时关闭
+        self.sender = None;
+        match self.join()? {
+            Ok(_) => Err(anyhow!("写入线程已退出")),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// 等待所有任务完成，返回 `best_effort` 时写入失败的条目数
+    pub(crate) fn finish(mut self) -> Result<usize> {
+        self.sender = None;
+        self.join()?
+    }
+
+    /// 取消写入: 正在执行的任务完成后，丢弃队列中尚未开始的任务，
+    /// 返回确认已写入的条目名称。写入线程的错误被忽略，调用方正在因取消而中止
+    pub(crate) fn cancel(mut self) -> Vec<String> {
+        self.aborted.store(true, Ordering::Relaxed);
+        self.sender = None;
+        let _ = self.join();
+        mem::take(&mut *self.written.lock().unwrap())
+    }
+
+    fn join(&mut self) -> Result<Result<usize>> {
+        let handle = self.handle.take().expect("写入线程已结束");
+        handle.join().map_err(|_| anyhow!("写入线程异常退出"))
+    }
+}
+
+/// 提前返回时也等待已提交的任务完成，不在后台留下仍在写入的线程
+impl Drop for WriteQueue {
+    fn drop(&mut self) {
+        self.sender = None;
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 执行时记录名称的任务
+    fn job(
+        ran: &Arc<Mutex<Vec<&'static str>>>,
+        name: &'static str,
+    ) -> impl FnOnce() -> Result<()> + Send + 'static {
+        let ran = Arc::clone(ran);
+        move || {
+            ran.lock().unwrap().push(name);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn finish_runs_every_job_in_order() {
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let mut queue = WriteQueue::new(false);
+        for name in ["a", "b", "c"] {
+            queue.push(name.into(), job(&ran, name)).unwrap();
+        }
+        assert_eq!(queue.finish().unwrap(), 0);
+        assert_eq!(*ran.lock().unwrap(), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn cancel_reports_only_confirmed_writes_and_drops_queued_jobs() {
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let (started, running) = mpsc::channel();
+        let (release, gate) = mpsc::channel::<()>();
+        let first = job(&ran, "a");
+        let mut queue = WriteQueue::new(false);
+        queue
+            .push("a".into(), move || {
+                started.send(()).unwrap();
+                gate.recv().unwrap();
+                first()
+            })
+            .unwrap();
+        queue.push("b".into(), job(&ran, "b")).unwrap();
+        queue.push("c".into(), job(&ran, "c")).unwrap();
+        // "a" 正在执行时取消，排在其后的任务不再执行
+        running.recv().unwrap();
+        queue.aborted.store(true, Ordering::Relaxed);
+        release.send(()).unwrap();
+        assert_eq!(queue.cancel(), ["a"]);
+        assert_eq!(*ran.lock().unwrap(), ["a"]);
+    }
+}
@@ -0,0 +1,50 @@
+//! `--dry-run` 时打印的写入规划
+
+use std::{fmt::Write, path::Path};
+
+/// 预演的规划: 逐行说明各条目的变化，打印时加上目标文件与文件大小的变化
+#[derive(Debug, Default)]
+pub(crate) struct DryRunPlan {
+    lines: Vec<String>,
+}
+
+impl DryRunPlan {
+    pub(crate) fn line(&mut self, line: impl Into<String>) {
+        self.lines.push(line.into());
+    }
+
+    /// 渲染为打印的文本，每行以换行结尾
+    pub(crate) fn render(&self, target: &Path, old_len: u64, new_len: u64) -> String {
+        let mut text = format!("预演: 不会写入 {}\n", target.display());
+        for line in &self.lines {
+            let _ = writeln!(text, "  {line}");
+        }
+        let _ = writeln!(
+            text,
+            "  文件大小: {old_len} -> {new_len} 字节 ({:+})",
+            new_len as i64 - old_len as i64
+        );
+        text
+    }
+
+    /// 打印到标准输出，预演的输出不受静默模式影响
+    pub(crate) fn print(&self, target: &Path, old_len: u64, new_len: u64) {
+        print!("{}", self.render(target, old_len, new_len));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn render_wraps_lines_with_target_and_size() {
+        let mut plan = DryRunPlan::default();
+        plan.line("新增条目 'a': 偏移量 0，长度 4");
+        plan.line(format!("{} 个条目的位置不变", 2));
+        assert_eq!(
+            plan.render(Path::new("x.aos"), 100, 96),
+            "预演: 不会写入 x.aos\n  新增条目 'a': 偏移量 0，长度 4\n  2 个条目的位置不变\n  文件大小: 100 -> 96 字节 (-4)\n"
+        );
+    }
+}
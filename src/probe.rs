@@ -0,0 +1,153 @@
+//! 只读取文件头与第一个条目，判断任意文件是否为 .aos 封包
+
+use std::{fmt, path::Path};
+
+use anyhow::Result;
+
+use crate::{
+    extended,
+    format::{AosV2Entry, AosV2Hdr, Layout, NameDecoder, NameEncoding},
+    source::Source,
+};
+
+/// 文件的分类结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeClass {
+    /// 文件头各字段相互一致的 AOS V2 封包，包括本工具的扩展格式
+    V2,
+    /// 结构上像 AOS V2，但有字段不符合标准格式，例如条目带有保留字段
+    Nonstandard,
+    /// 不是 .aos 封包
+    NotAos,
+}
+
+impl fmt::Display for ProbeClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::V2 => "AOS V2",
+            Self::Nonstandard => "possibly AOS (nonstandard)",
+            Self::NotAos => "not AOS",
+        })
+    }
+}
+
+/// 分类结果及其依据
+#[derive(Debug)]
+pub struct ProbeReport {
+    pub class: ProbeClass,
+    /// 判断依据，分类为 [`ProbeClass::V2`] 时为需要注意的细节
+    pub findings: Vec<String>,
+}
+
+/// 判断文件是否为 .aos 封包
+///
+/// 只读取文件头与第一个条目，不读取整个目录表与数据区。文件头或目录表超出文件范围、
+/// 封包名称没有结尾的空字符时判定为不是封包；其余字段不一致时判定为非标准的封包。
+/// 本工具只实现了 V2 格式，不会识别其他版本。
+pub fn probe_file(path: &Path) -> Result<ProbeReport> {
+    let mut source = Source::open(path)?;
+    let file_len = source.len()?;
+    let mut fatal = Vec::new();
+    let mut problems = Vec::new();
+    let mut notes = Vec::new();
+
+    let header_size = std::mem::size_of::<AosV2Hdr>() as u64;
+    if file_len < header_size {
+        fatal.push(format!(
+            "文件只有 {file_len} 字节，小于文件头的 {header_size} 字节"
+        ));
+        return Ok(ProbeReport {
+            class: ProbeClass::NotAos,
+            findings: fatal,
+        });
+    }
+    let mut buffer = vec![0u8; header_size as usize];
+    source.read_exact_at(&mut buffer, 0)?;
+    let header = AosV2Hdr::from_reader(&mut &buffer[..])?;
+
+    let is_extended = extended::is_extended(&header);
+    let layout = if is_extended {
+        notes.push("本工具的扩展格式".to_owned());
+        extended::extended_layout()
+    } else {
+        Layout::default()
+    };
+    let toc_length = header.toc_length;
+    let base_offset = header.toc_end();
+    if base_offset > file_len {
+        fatal.push(format!("目录表长度 {toc_length} 超出文件范围"));
+    } else if !(toc_length as usize).is_multiple_of(layout.entry_size()) {
+        problems.push(format!(
+            "目录表长度 {toc_length} 不是 {} 字节条目的整数倍，条目可能带有保留字段",
+            layout.entry_size()
+        ));
+    }
+
+    let data_offset = header.data_offset as u64;
+    if data_offset != 0 {
+        if data_offset < base_offset || data_offset > file_len {
+            problems.push(format!(
+                "data_offset {data_offset} 不在目录表末尾 ({base_offset}) 与文件末尾 ({file_len}) 之间"
+            ));
+        } else if data_offset > base_offset {
+            notes.push(format!(
+                "目录表之后有 {} 字节填充",
+                data_offset - base_offset
+            ));
+        }
+    }
+
+    // 封包名称与条目名称都应是以空字符结尾、可以解码的文本
+    let names = NameDecoder {
+        encoding: NameEncoding::Auto,
+        ..Default::default()
+    };
+    let archive_name = { header.archive_name };
+    match archive_name.iter().position(|&b| b == 0) {
+        None => fatal.push("封包名称没有结尾的空字符".to_owned()),
+        Some(len) if names.decode_raw(&archive_name[..len]).is_err() => {
+            problems.push("封包名称不是有效的 UTF-8 或 Shift-JIS 文本".to_owned())
+        }
+        Some(_) => {}
+    }
+
+    let entry_size = layout.entry_size() as u64;
+    if fatal.is_empty() && toc_length as u64 >= entry_size {
+        let mut buffer = vec![0u8; std::mem::size_of::<AosV2Entry>()];
+        source.read_exact_at(&mut buffer, header_size)?;
+        let entry = AosV2Entry::from_reader(&mut &buffer[..])?;
+        let filename = { entry.filename };
+        match filename.iter().position(|&b| b == 0) {
+            None | Some(0) => problems.push("第一个条目的名称为空或没有结尾的空字符".to_owned()),
+            Some(len) if names.decode_raw(&filename[..len]).is_err() => {
+                problems.push("第一个条目的名称不是有效的 UTF-8 或 Shift-JIS 文本".to_owned())
+            }
+            Some(_) => {}
+        }
+        let data_base = data_offset.max(base_offset);
+        let end = data_base + entry.offset as u64 + entry.length as u64;
+        if end > file_len {
+            problems.push(format!(
+                "第一个条目的数据 (到偏移量 {end}) 超出文件末尾 ({file_len})"
+            ));
+        }
+    }
+
+    Ok(if !fatal.is_empty() {
+        fatal.extend(problems);
+        ProbeReport {
+            class: ProbeClass::NotAos,
+            findings: fatal,
+        }
+    } else if !problems.is_empty() {
+        ProbeReport {
+            class: ProbeClass::Nonstandard,
+            findings: problems,
+        }
+    } else {
+        ProbeReport {
+            class: ProbeClass::V2,
+            findings: notes,
+        }
+    })
+}
@@ -0,0 +1,72 @@
+use std::{fs::File, io};
+
+/// 从绝对偏移量读取恰好填满 `buf` 的数据
+///
+/// Unix 上使用 `pread`，只需一次系统调用且不改变文件游标，因此同一个 `File`
+/// 可以安全地在多个线程间共享；其他平台退回到 seek 后读取。
+#[cfg(unix)]
+pub fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+}
+
+/// 从绝对偏移量读取恰好填满 `buf` 的数据
+///
+/// 当前平台不支持定位读取，退回到 seek 后读取，会改变文件游标。
+#[cfg(not(unix))]
+pub fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    seek_read_exact_at(file, buf, offset)
+}
+
+/// 不支持定位读取的平台上 [`read_exact_at`] 的实现，Unix 上只在测试中使用
+#[cfg(any(test, not(unix)))]
+fn seek_read_exact_at(mut file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::ErrorKind;
+
+    use super::*;
+    use crate::testutil::TempDir;
+
+    #[test]
+    fn positioned_and_seek_reads_match() {
+        let dir = TempDir::new("read-at");
+        let data: Vec<u8> = (0..=255u8).cycle().take(10_000).collect();
+        let file = File::open(dir.write("data.bin", &data)).unwrap();
+        for (offset, len) in [(0, 0), (0, 16), (255, 300), (9_990, 10)] {
+            let mut positioned = vec![0u8; len];
+            let mut seeked = vec![0u8; len];
+            read_exact_at(&file, &mut positioned, offset as u64).unwrap();
+            seek_read_exact_at(&file, &mut seeked, offset as u64).unwrap();
+            assert_eq!(positioned, seeked);
+            assert_eq!(positioned, &data[offset..offset + len]);
+        }
+        // 读取超出文件末尾时两者都报告 UnexpectedEof
+        let mut buf = [0u8; 20];
+        for result in [
+            read_exact_at(&file, &mut buf, 9_990),
+            seek_read_exact_at(&file, &mut buf, 9_990),
+        ] {
+            assert_eq!(result.unwrap_err().kind(), ErrorKind::UnexpectedEof);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn positioned_read_keeps_the_cursor() {
+        use std::io::{Seek, SeekFrom};
+
+        let dir = TempDir::new("read-at-cursor");
+        let mut file = File::open(dir.write("data.bin", b"0123456789")).unwrap();
+        file.seek(SeekFrom::Start(3)).unwrap();
+        let mut buf = [0u8; 4];
+        read_exact_at(&file, &mut buf, 6).unwrap();
+        assert_eq!(&buf, b"6789");
+        assert_eq!(file.stream_position().unwrap(), 3);
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    events,
+    format::{AosV2Entry, AosV2Hdr, Layout, NameStyle},
+    pack::{archive_name_field, output_file_name, temp_path},
+};
+
+/// 由原始数据区与条目布局文件重建 .aos 文件，返回条目数
+///
+/// 布局文件每行为以制表符分隔的条目名称、相对数据区的偏移量与长度，空行被忽略；
+/// 目录表按布局文件中的顺序写入。数据区原样复制到目录表之后，空隙与条目未引用的
+/// 字节都保留，因此与 `dump-data` 导出的数据区互逆。每个条目都必须落在数据区范围内，
+/// 允许多个条目引用同一段数据。
+pub fn rebuild_toc(
+    data_path: &Path,
+    layout_path: &Path,
+    output_path: &Path,
+    layout: Layout,
+) -> Result<usize> {
+    let data_len = fs::metadata(data_path)
+        .with_context(|| format!("无法读取数据区文件: {}", data_path.display()))?
+        .len();
+    if data_len > u32::MAX as u64 {
+        bail!("数据区超出 4 GiB，无法封包");
+    }
+    let content = fs::read_to_string(layout_path)
+        .with_context(|| format!("无法读取布局文件: {}", layout_path.display()))?;
+
+    let mut entries = Vec::new();
+    let mut seen = HashSet::new();
+    for (number, line) in content.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() {
+            continue;
+        }
+        let number = number + 1;
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [name, offset, length] = fields[..] else {
+            bail!("布局文件第 {number} 行应为以制表符分隔的名称、偏移量与长度");
+        };
+        let offset: u32 = offset
+            .trim()
+            .parse()
+            .with_context(|| format!("布局文件第 {number} 行的偏移量 '{offset}' 无效"))?;
+        let length: u32 = length
+            .trim()
+            .parse()
+            .with_context(|| format!("布局文件第 {number} 行的长度 '{length}' 无效"))?;
+        if name.is_empty() || name.contains('\0') {
+            bail!("布局文件第 {number} 行的名称为空或包含空字符");
+        }
+        let end = offset as u64 + length as u64;
+        if end > data_len {
+            bail!(
+                "布局文件第 {number} 行的条目 '{name}' (偏移量 {offset}，长度 {length}) 超出数据区大小 {data_len}"
+            );
+        }
+        if !seen.insert(name) {
+            events::warn(
+                "duplicate_name",
+                Some(name),
+                &format!("布局文件第 {number} 行重复使用了名称 '{name}'"),
+            );
+        }
+        let filename = NameStyle::Nul
+            .encode(name.as_bytes())
+            .with_context(|| format!("布局文件第 {number} 行的名称 '{name}' 过长"))?;
+        entries.push(AosV2Entry {
+            filename,
+            offset,
+            length,
+        });
+    }
+    if entries.is_empty() {
+        bail!("布局文件中没有条目: {}", layout_path.display());
+    }
+
+    let toc_length = u32::try_from(entries.len() * layout.entry_size()).context("目录表过大")?;
+    let header = AosV2Hdr {
+        unknown1: 0,
+        data_offset: std::mem::size_of::<AosV2Hdr>() as u32 + toc_length,
+        toc_length,
+        archive_name: archive_name_field(&output_file_name(output_path)),
+    };
+
+    let temp_path = temp_path(output_path, "rebuild");
+    let result = (|| -> Result<()> {
+        let output = File::create(&temp_path)
+            .with_context(|| format!("无法创建临时文件: {}", temp_path.display()))?;
+        let mut output = BufWriter::new(output);
+        output.write_all(&header.to_bytes())?;
+        for entry in &entries {
+            output.write_all(&layout.entry_to_bytes(entry))?;
+        }
+        let mut data = File::open(data_path)
+            .with_context(|| format!("无法打开数据区文件: {}", data_path.display()))?;
+        io::copy(&mut data, &mut output)?;
+        output.flush()?;
+        Ok(())
+    })();
+    if let Err(err) = result {
+        let _ = fs::remove_file(&temp_path);
+        return Err(err.context(format!("无法写入 {}", output_path.display())));
+    }
+    fs::rename(&temp_path, output_path)
+        .with_context(|| format!("无法写入输出文件: {}", output_path.display()))?;
+    Ok(entries.len())
+}
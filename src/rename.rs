@@ -0,0 +1,44 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{events, format::FILENAME_SIZE};
+
+/// 读取重命名表，每行为以制表符分隔的原名称与新名称，空行被忽略
+///
+/// 新名称的长度在读取时检查，不能超过文件名字段的容量。
+pub(crate) fn read_rename_map(path: &Path) -> Result<BTreeMap<String, String>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("无法读取重命名表: {}", path.display()))?;
+    let mut map = BTreeMap::new();
+    for (number, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some((old, new)) = line.split_once('\t') else {
+            bail!("重命名表第 {} 行缺少制表符分隔的新名称", number + 1);
+        };
+        if new.is_empty() || new.len() >= FILENAME_SIZE {
+            bail!(
+                "重命名表第 {} 行的新名称 '{new}' 为空或过长 (最大 {} 字节)",
+                number + 1,
+                FILENAME_SIZE - 1
+            );
+        }
+        if map.insert(old.to_owned(), new.to_owned()).is_some() {
+            bail!("重命名表第 {} 行重复指定了 '{old}'", number + 1);
+        }
+    }
+    Ok(map)
+}
+
+/// 报告重命名表中没有匹配任何文件的条目
+pub(crate) fn warn_unused(map: &BTreeMap<String, String>) {
+    for (old, new) in map {
+        events::warn(
+            "unused_rename",
+            Some(old),
+            &format!("重命名表中的 '{old}' -> '{new}' 没有匹配的文件"),
+        );
+    }
+}
@@ -0,0 +1,403 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    archive::Archive,
+    events::{self, Event},
+    extended,
+    format::{ARCHIVE_NAME_SIZE, AosV2Entry, FILENAME_SIZE, Layout},
+    lock,
+    pack::{archive_name_field, output_file_name},
+    plan::DryRunPlan,
+    rename,
+};
+
+/// 重新封包选项
+#[derive(Debug, Default)]
+pub struct RepackOptions {
+    /// 输出文件路径，为 `None` 时输出到原文件旁的 `<名称>_repack.aos`
+    pub output_archive: Option<PathBuf>,
+    /// 目录表布局，保留字段写回时以 0 填充
+    pub layout: Layout,
+    /// 重命名表，每行为以制表符分隔的原条目名称与新名称；替换文件仍按原名称查找
+    pub rename_map: Option<PathBuf>,
+    /// 将原文件最后一个条目之后的尾部数据追加到输出末尾
+    pub preserve_footer: bool,
+    /// 将内嵌的封包名称改为输出文件名，扩展格式的注释保持不变
+    pub sync_archive_name: bool,
+    /// 只打印被替换、顺延与重命名的条目以及输出文件大小，不写入输出文件
+    pub dry_run: bool,
+}
+
+/// 单个条目的数据来源
+enum Source {
+    /// 原文件中的数据，记录其绝对偏移量
+    Original(u64),
+    /// 目录中的替换文件
+    File(PathBuf),
+}
+
+/// 重新封包时单个条目的规划结果
+struct Planned {
+    /// 该条目之前需要保留的原始填充字节，记录其绝对偏移量与长度
+    gap: (u64, u64),
+    source: Source,
+    offset: u32,
+    length: u32,
+}
+
+/// 以原 .aos 文件为模板重新封包
+///
+/// 复用原文件的文件头 (包括 `data_offset`)、条目顺序、目录表与数据区之间的填充以及条目之间的
+/// 填充字节，仅替换目录中存在的同名文件。大小不变的条目保持原偏移量；大小变化时其后的条目依次顺延。
+pub fn repack_archive(archive_path: &Path, dir_path: &Path, options: &RepackOptions) -> Result<()> {
+    progress!(
+        "正在以 {} 为模板重新封包目录: {}",
+        archive_path.display(),
+        dir_path.display()
+    );
+    Event {
+        event: "repack",
+        file: Some(&archive_path.to_string_lossy()),
+        ..Default::default()
+    }
+    .emit();
+
+    let output_filename = options.output_archive.clone().unwrap_or_else(|| {
+        let stem = archive_path
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        archive_path.with_file_name(format!("{stem}_repack.aos"))
+    });
+    if output_filename.exists() && same_file(&output_filename, archive_path)? {
+        bail!("输出文件不能与原文件相同: {}", output_filename.display());
+    }
+
+    let mut file = lock::open_shared(archive_path)?;
+    let archive = Archive::from_reader_with_layout(&mut file, options.layout)?;
+    let base_offset = archive.base_offset()?;
+
+    // 1. 按数据区中的物理顺序规划每个条目的新位置
+    let mut order: Vec<usize> = (0..archive.entries.len()).collect();
+    order.sort_by_key(|&i| archive.entries[i].offset);
+
+    let mut plans: Vec<Option<Planned>> = (0..archive.entries.len()).map(|_| None).collect();
+    let mut original_end = 0u64;
+    let mut new_end = 0u64;
+    for &index in &order {
+        let entry = &archive.entries[index];
+        let filename_str = entry.get_filename_str()?;
+        let entry_offset = entry.offset as u64;
+        if entry_offset < original_end {
+            bail!("条目 '{filename_str}' 与前一个条目的数据重叠，无法保持原始布局");
+        }
+
+        let gap = entry_offset - original_end;
+        let replacement = dir_path.join(&filename_str);
+        let (source, length) = if replacement.is_file() {
+            let length = fs::metadata(&replacement)
+                .with_context(|| format!("无法读取文件信息: {}", replacement.display()))?
+                .len();
+            (Source::File(replacement), length)
+        } else {
+            events::warn(
+                "missing_file",
+                Some(&filename_str),
+                &format!("目录中缺少 '{filename_str}'，保留原数据"),
+            );
+            (
+                Source::Original(base_offset + entry_offset),
+                entry.length as u64,
+            )
+        };
+        if length != entry.length as u64 {
+            events::warn(
+                "size_changed",
+                Some(&filename_str),
+                &format!(
+                    "'{filename_str}' 大小由 {} 变为 {length} 字节，其后条目的偏移量将重新计算",
+                    { entry.length }
+                ),
+            );
+        }
+
+        let offset = new_end + gap;
+        let length = u32::try_from(length).context(format!("文件 '{filename_str}' 过大"))?;
+        let offset = u32::try_from(offset).context("数据区超出 4 GiB，无法封包")?;
+        Event {
+            event: match source {
+                Source::Original(_) => "keep",
+                Source::File(_) => "replace",
+            },
+            file: Some(&filename_str),
+            offset: Some(offset as u64),
+            length: Some(length as u64),
+            ..Default::default()
+        }
+        .emit();
+        plans[index] = Some(Planned {
+            gap: (base_offset + original_end, gap),
+            source,
+            offset,
+            length,
+        });
+        original_end = entry_offset + entry.length as u64;
+        new_end = offset as u64 + length as u64;
+    }
+    let plans: Vec<Planned> = plans.into_iter().flatten().collect();
+    let mut renames = match &options.rename_map {
+        Some(path) => rename::read_rename_map(path)?,
+        None => BTreeMap::new(),
+    };
+
+    if options.dry_run {
+        let file_len = file.metadata()?.len();
+        let footer_length = match archive.footer(file_len)? {
+            Some((_, length)) if options.preserve_footer => length,
+            _ => 0,
+        };
+        let new_len = base_offset + new_end + footer_length;
+        repack_plan(&archive, &plans, &mut renames)?.print(&output_filename, file_len, new_len);
+        rename::warn_unused(&renames);
+        return Ok(());
+    }
+
+    // 2. 写入文件头与目录表
+    let output = File::create(&output_filename)
+        .with_context(|| format!("无法创建输出文件: {}", output_filename.display()))?;
+    let mut output = BufWriter::new(output);
+    let mut header = archive.header.to_bytes();
+    if options.sync_archive_name {
+        let mut archive_name = archive_name_field(&output_file_name(&output_filename));
+        if extended::is_extended(&archive.header)
+            && let Some(comment) = extended::read_comment(&archive.header)
+        {
+            extended::write_comment(&mut archive_name, &comment)?;
+        }
+        // archive_name 是文件头的最后一个字段
+        let start = header.len() - ARCHIVE_NAME_SIZE;
+        header[start..].copy_from_slice(&archive_name);
+    }
+    output.write_all(&header)?;
+    let toc_start = header.len() as u64;
+    for (entry, plan) in archive.entries.iter().zip(&plans) {
+        let mut filename = entry.filename;
+        if let Some(new_name) = renames.remove(&entry.get_filename_str()?) {
+            filename = [0u8; FILENAME_SIZE];
+            filename[..new_name.len()].copy_from_slice(new_name.as_bytes());
+        }
+        let new_entry = AosV2Entry {
+            filename,
+            offset: plan.offset,
+            length: plan.length,
+        };
+        output.write_all(&options.layout.entry_to_bytes(&new_entry))?;
+    }
+    // 目录表之后到数据区起始之间的字节原样保留，data_offset 因此仍然有效
+    let toc_end = toc_start + (archive.entries.len() * options.layout.entry_size()) as u64;
+    copy_range(
+        &mut file,
+        toc_end,
+        base_offset.saturating_sub(toc_end),
+        &mut output,
+    )?;
+
+    // 3. 按物理顺序写入填充字节与文件数据
+    let mut sorted: Vec<&Planned> = plans.iter().collect();
+    sorted.sort_by_key(|plan| plan.offset);
+    for plan in sorted {
+        let (gap_start, gap_len) = plan.gap;
+        copy_range(&mut file, gap_start, gap_len, &mut output)?;
+        match &plan.source {
+            Source::Original(start) => {
+                copy_range(&mut file, *start, plan.length as u64, &mut output)?
+            }
+            Source::File(path) => {
+                let mut source = File::open(path)
+                    .with_context(|| format!("无法打开文件: {}", path.display()))?;
+                copy_range(&mut source, 0, plan.length as u64, &mut output)?;
+            }
+        }
+    }
+    match (
+        archive.footer(file.metadata()?.len())?,
+        options.preserve_footer,
+    ) {
+        (Some((offset, length)), true) => {
+            copy_range(&mut file, offset, length, &mut output)?;
+            progress!("  -> 保留了 {length} 字节的尾部数据");
+        }
+        (Some((_, length)), false) => events::warn(
+            "footer_dropped",
+            None,
+            &format!(
+                "原文件最后一个条目之后的 {length} 字节尾部数据未被保留，可使用 --preserve-footer 保留"
+            ),
+        ),
+        (None, _) => {}
+    }
+    output.flush()?;
+    rename::warn_unused(&renames);
+
+    progress!("重新封包完成，输出文件: {}", output_filename.display());
+    Event {
+        event: "done",
+        file: Some(&output_filename.to_string_lossy()),
+        ..Default::default()
+    }
+    .emit();
+    Ok(())
+}
+
+/// `dry_run` 时各条目的规划，匹配的重命名从 `renames` 中移除
+fn repack_plan(
+    archive: &Archive,
+    plans: &[Planned],
+    renames: &mut BTreeMap<String, String>,
+) -> Result<DryRunPlan> {
+    let mut plan_lines = DryRunPlan::default();
+    let mut unchanged = 0usize;
+    for (entry, plan) in archive.entries.iter().zip(plans) {
+        let name = entry.get_filename_str()?;
+        let (old_offset, old_length) = ({ entry.offset }, { entry.length });
+        let moved = format!(
+            "偏移量 {old_offset} -> {}，长度 {old_length} -> {}",
+            plan.offset, plan.length
+        );
+        match &plan.source {
+            Source::File(_) => plan_lines.line(format!("替换 '{name}': {moved}")),
+            Source::Original(_) if old_offset != plan.offset => {
+                plan_lines.line(format!("顺延 '{name}': {moved}"))
+            }
+            Source::Original(_) => unchanged += 1,
+        }
+        if let Some(new_name) = renames.remove(&name) {
+            plan_lines.line(format!("重命名 '{name}' -> '{new_name}'"));
+        }
+    }
+    plan_lines.line(format!("{unchanged} 个条目保留原数据与偏移量"));
+    Ok(plan_lines)
+}
+
+/// 将 `reader` 中 `[start, start + len)` 范围内的字节复制到 `writer`
+pub(crate) fn copy_range<R: Read + Seek, W: Write>(
+    reader: &mut R,
+    start: u64,
+    len: u64,
+    writer: &mut W,
+) -> Result<()> {
+    reader.seek(SeekFrom::Start(start))?;
+    let copied = io::copy(&mut reader.by_ref().take(len), writer)?;
+    if copied != len {
+        bail!("偏移量 {start} 处的数据不足 {len} 字节");
+    }
+    Ok(())
+}
+
+/// 判断两个路径是否指向同一个文件
+pub(crate) fn same_file(a: &Path, b: &Path) -> Result<bool> {
+    Ok(fs::canonicalize(a)? == fs::canonicalize(b)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        testutil::{TempDir, make_padded_archive, make_test_archive},
+        unpack::{UnpackOptions, unpack_archive},
+    };
+
+    fn repack_bytes(dir: &TempDir, bytes: &[u8], files: &[(&str, &[u8])]) -> Vec<u8> {
+        repack_with(dir, bytes, files, RepackOptions::default())
+    }
+
+    fn repack_with(
+        dir: &TempDir,
+        bytes: &[u8],
+        files: &[(&str, &[u8])],
+        options: RepackOptions,
+    ) -> Vec<u8> {
+        let archive = dir.write("test.aos", bytes);
+        let files_dir = dir.path().join("files");
+        fs::create_dir_all(&files_dir).unwrap();
+        for (name, content) in files {
+            dir.write(&format!("files/{name}"), content);
+        }
+        let output = dir.path().join("out.aos");
+        let options = RepackOptions {
+            output_archive: Some(output.clone()),
+            ..options
+        };
+        repack_archive(&archive, &files_dir, &options).unwrap();
+        fs::read(output).unwrap()
+    }
+
+    #[test]
+    fn repack_keeps_padded_data_offset_byte_for_byte() {
+        let dir = TempDir::new("repack-padded");
+        let bytes = make_padded_archive(&[("a", b"abc"), ("b", b"de")], "t.aos", b"ZZZZ");
+        let output = repack_bytes(&dir, &bytes, &[("a", b"abc"), ("b", b"de")]);
+        assert_eq!(output, bytes);
+    }
+
+    #[test]
+    fn footer_is_kept_only_with_preserve_footer() {
+        let dir = TempDir::new("repack-footer");
+        let original = make_test_archive(&[("a", b"abc"), ("b", b"de")], "t.aos");
+        let mut bytes = original.clone();
+        bytes.extend_from_slice(&[0xee; 16]);
+        let options = RepackOptions {
+            preserve_footer: true,
+            ..Default::default()
+        };
+        assert_eq!(repack_with(&dir, &bytes, &[], options), bytes);
+        assert_eq!(repack_bytes(&dir, &bytes, &[]), original);
+    }
+
+    #[test]
+    fn dry_run_writes_nothing() {
+        let dir = TempDir::new("repack-dry-run");
+        let bytes = make_test_archive(&[("a", b"abc"), ("b", b"de")], "t.aos");
+        let archive = dir.write("test.aos", &bytes);
+        let files_dir = dir.path().join("files");
+        dir.write("files/a", b"abcdef");
+        let output = dir.path().join("out.aos");
+        let options = RepackOptions {
+            output_archive: Some(output.clone()),
+            dry_run: true,
+            ..Default::default()
+        };
+        repack_archive(&archive, &files_dir, &options).unwrap();
+        assert!(!output.exists());
+        assert_eq!(fs::read(archive).unwrap(), bytes);
+    }
+
+    #[test]
+    fn repack_with_changed_size_keeps_padding_and_unpacks() {
+        let dir = TempDir::new("repack-resized");
+        let bytes = make_padded_archive(&[("a", b"abc"), ("b", b"de")], "t.aos", b"ZZZZ");
+        let output = repack_bytes(&dir, &bytes, &[("a", b"abcdef")]);
+        let toc_end = bytes.len() - 4 - 5;
+        // 文件头、目录表之后的填充不变，b 顺延 3 字节
+        assert_eq!(output[4..8], bytes[4..8]);
+        assert_eq!(&output[toc_end..toc_end + 4], b"ZZZZ");
+        assert_eq!(output.len(), bytes.len() + 3);
+
+        let archive = dir.write("resized.aos", &output);
+        let unpacked = dir.path().join("unpacked");
+        let options = UnpackOptions {
+            output_dir: Some(unpacked.clone()),
+            ..Default::default()
+        };
+        unpack_archive(&archive, &options).unwrap();
+        assert_eq!(fs::read(unpacked.join("a")).unwrap(), b"abcdef");
+        assert_eq!(fs::read(unpacked.join("b")).unwrap(), b"de");
+    }
+}
@@ -0,0 +1,92 @@
+use std::{
+    io::{self, ErrorKind},
+    thread,
+    time::Duration,
+};
+
+/// 首次重试前的等待时间，之后每次翻倍
+const INITIAL_BACKOFF: Duration = Duration::from_millis(100);
+/// 单次等待时间的上限
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+/// 判断错误是否可能是暂时性的 (例如网络文件系统的瞬时故障)
+fn is_transient(kind: ErrorKind) -> bool {
+    matches!(
+        kind,
+        ErrorKind::Interrupted
+            | ErrorKind::WouldBlock
+            | ErrorKind::TimedOut
+            | ErrorKind::ResourceBusy
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+    )
+}
+
+/// 执行 IO 操作，遇到暂时性错误时以指数退避重试，总共最多尝试 `attempts` 次
+///
+/// `attempts` 为 0 或 1 时只执行一次。非暂时性错误 (例如 `NotFound`) 会立即返回。
+pub fn with_retry<T>(attempts: u32, mut op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let attempts = attempts.max(1);
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Err(err) if attempt < attempts && is_transient(err.kind()) => {
+                attempt += 1;
+                crate::events::warn(
+                    "retry",
+                    None,
+                    &format!("IO 错误 ({err})，{backoff:?} 后进行第 {attempt}/{attempts} 次尝试"),
+                );
+                thread::sleep(backoff);
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    /// 按给定的错误类型始终失败，返回实际调用的次数
+    fn calls_until_giving_up(attempts: u32, kind: ErrorKind) -> u32 {
+        let calls = Cell::new(0);
+        let result: io::Result<()> = with_retry(attempts, || {
+            calls.set(calls.get() + 1);
+            Err(io::Error::from(kind))
+        });
+        assert_eq!(result.unwrap_err().kind(), kind);
+        calls.get()
+    }
+
+    #[test]
+    fn attempts_is_the_total_number_of_calls() {
+        assert_eq!(calls_until_giving_up(0, ErrorKind::Interrupted), 1);
+        assert_eq!(calls_until_giving_up(1, ErrorKind::Interrupted), 1);
+        assert_eq!(calls_until_giving_up(3, ErrorKind::Interrupted), 3);
+    }
+
+    #[test]
+    fn permanent_errors_are_not_retried() {
+        assert_eq!(calls_until_giving_up(3, ErrorKind::NotFound), 1);
+    }
+
+    #[test]
+    fn success_after_a_transient_error_is_returned() {
+        let calls = Cell::new(0);
+        let result = with_retry(2, || {
+            calls.set(calls.get() + 1);
+            if calls.get() == 1 {
+                Err(io::Error::from(ErrorKind::TimedOut))
+            } else {
+                Ok(7)
+            }
+        });
+        assert_eq!(result.unwrap(), 7);
+        assert_eq!(calls.get(), 2);
+    }
+}
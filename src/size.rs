@@ -0,0 +1,60 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{Context, bail};
+
+/// 可带单位后缀的字节数，例如 `512`、`64K`、`1.5MiB`、`4GB`
+///
+/// `K` / `KiB` 等以 1024 为底，`KB` 等以 1000 为底，单位不区分大小写。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ByteSize(pub u64);
+
+impl FromStr for ByteSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let split = s
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(s.len());
+        let (number, unit) = s.split_at(split);
+        let number: f64 = number
+            .parse()
+            .with_context(|| format!("无效的大小: '{s}'"))?;
+        let multiplier: u64 = match unit.trim().to_ascii_lowercase().as_str() {
+            "" | "b" => 1,
+            "k" | "kib" => 1 << 10,
+            "m" | "mib" => 1 << 20,
+            "g" | "gib" => 1 << 30,
+            "t" | "tib" => 1 << 40,
+            "kb" => 1_000,
+            "mb" => 1_000_000,
+            "gb" => 1_000_000_000,
+            "tb" => 1_000_000_000_000,
+            other => bail!("未知的大小单位: '{other}'"),
+        };
+        let bytes = number * multiplier as f64;
+        if bytes > u64::MAX as f64 {
+            bail!("大小 '{s}' 过大");
+        }
+        Ok(Self(bytes.round() as u64))
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+        if self.0 < 1024 {
+            return write!(f, "{} 字节", self.0);
+        }
+        let mut value = self.0 as f64;
+        let mut unit = UNITS[0];
+        for next in UNITS {
+            value /= 1024.0;
+            unit = next;
+            if value < 1024.0 {
+                break;
+            }
+        }
+        write!(f, "{value:.1} {unit}")
+    }
+}
@@ -0,0 +1,212 @@
+//! 读取 .aos 文件的数据来源：本地文件，或启用 `http` 特性时的远程 URL
+
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom},
+    path::Path,
+};
+
+use anyhow::Result;
+
+use crate::{lock, read_at::read_exact_at};
+
+/// 路径是否为 `http://` 或 `https://` URL
+pub fn is_url(path: &Path) -> bool {
+    path.to_str()
+        .is_some_and(|s| s.starts_with("http://") || s.starts_with("https://"))
+}
+
+/// 可随机读取的 .aos 文件来源
+pub(crate) enum Source {
+    File(File),
+    /// 服务器不支持范围请求时完整下载到内存中的数据
+    #[cfg(feature = "http")]
+    Memory(io::Cursor<Vec<u8>>),
+    #[cfg(feature = "http")]
+    Http(http::RangeReader),
+}
+
+impl Source {
+    /// 打开本地文件或 URL
+    pub(crate) fn open(path: &Path) -> Result<Self> {
+        if is_url(path) {
+            return Self::open_url(path.to_str().unwrap_or_default());
+        }
+        Ok(Self::File(lock::open_shared(path)?))
+    }
+
+    #[cfg(feature = "http")]
+    fn open_url(url: &str) -> Result<Self> {
+        use anyhow::Context;
+
+        http::open(url).with_context(|| format!("无法打开 URL: {url}"))
+    }
+
+    #[cfg(not(feature = "http"))]
+    fn open_url(_url: &str) -> Result<Self> {
+        anyhow::bail!("此构建未启用 http 特性，无法打开 URL")
+    }
+
+    /// 数据的总长度
+    pub(crate) fn len(&self) -> io::Result<u64> {
+        match self {
+            Self::File(file) => Ok(file.metadata()?.len()),
+            #[cfg(feature = "http")]
+            Self::Memory(cursor) => Ok(cursor.get_ref().len() as u64),
+            #[cfg(feature = "http")]
+            Self::Http(reader) => Ok(reader.len),
+        }
+    }
+
+    /// 从绝对偏移量读取恰好填满 `buf` 的数据，本地文件使用定位读取
+    pub(crate) fn read_exact_at(&mut self, buf: &mut [u8], offset: u64) -> io::Result<()> {
+        match self {
+            Self::File(file) => read_exact_at(file, buf, offset),
+            #[cfg(feature = "http")]
+            other => {
+                other.seek(SeekFrom::Start(offset))?;
+                other.read_exact(buf)
+            }
+        }
+    }
+}
+
+impl Read for Source {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::File(file) => file.read(buf),
+            #[cfg(feature = "http")]
+            Self::Memory(cursor) => cursor.read(buf),
+            #[cfg(feature = "http")]
+            Self::Http(reader) => reader.read(buf),
+        }
+    }
+}
+
+impl Seek for Source {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match self {
+            Self::File(file) => file.seek(pos),
+            #[cfg(feature = "http")]
+            Self::Memory(cursor) => cursor.seek(pos),
+            #[cfg(feature = "http")]
+            Self::Http(reader) => reader.seek(pos),
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+mod http {
+    use std::io::{self, Read, Seek, SeekFrom};
+
+    use anyhow::Result;
+
+    use super::Source;
+
+    /// 每次范围请求至少读取的字节数，文件头与目录表通常一次即可读完
+    const MIN_FETCH: u64 = 64 * 1024;
+
+    /// 通过 HTTP 范围请求按需读取远程文件，并缓存最近一次请求的数据
+    pub(crate) struct RangeReader {
+        url: String,
+        pub(super) len: u64,
+        pos: u64,
+        cache_start: u64,
+        cache: Vec<u8>,
+    }
+
+    /// 打开 URL，服务器不支持范围请求时退回到完整下载
+    pub(super) fn open(url: &str) -> Result<Source> {
+        let response = ureq::head(url).call()?;
+        let accepts_ranges = response
+            .headers()
+            .get("accept-ranges")
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.eq_ignore_ascii_case("bytes"));
+        let len = response
+            .headers()
+            .get("content-length")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok());
+
+        match len {
+            Some(len) if accepts_ranges => Ok(Source::Http(RangeReader {
+                url: url.to_owned(),
+                len,
+                pos: 0,
+                cache_start: 0,
+                cache: Vec::new(),
+            })),
+            _ => {
+                crate::events::warn(
+                    "full_download",
+                    Some(url),
+                    "服务器不支持范围请求，将下载完整文件",
+                );
+                let mut data = Vec::new();
+                ureq::get(url)
+                    .call()?
+                    .into_body()
+                    .into_reader()
+                    .read_to_end(&mut data)?;
+                Ok(Source::Memory(io::Cursor::new(data)))
+            }
+        }
+    }
+
+    impl RangeReader {
+        fn fetch(&mut self, start: u64, wanted: u64) -> io::Result<()> {
+            let end = start.saturating_add(wanted.max(MIN_FETCH)).min(self.len);
+            let response = ureq::get(&self.url)
+                .header("Range", format!("bytes={start}-{}", end - 1))
+                .call()
+                .map_err(io::Error::other)?;
+            if response.status() != 206 {
+                return Err(io::Error::other(format!(
+                    "服务器未按范围请求返回数据 (状态码 {})",
+                    response.status()
+                )));
+            }
+            let mut data = Vec::with_capacity((end - start) as usize);
+            response.into_body().into_reader().read_to_end(&mut data)?;
+            self.cache_start = start;
+            self.cache = data;
+            Ok(())
+        }
+    }
+
+    impl Read for RangeReader {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.pos >= self.len || buf.is_empty() {
+                return Ok(0);
+            }
+            let cache_end = self.cache_start + self.cache.len() as u64;
+            if self.pos < self.cache_start || self.pos >= cache_end {
+                self.fetch(self.pos, buf.len() as u64)?;
+            }
+            let start = (self.pos - self.cache_start) as usize;
+            let available = &self.cache[start..];
+            if available.is_empty() {
+                return Ok(0);
+            }
+            let n = available.len().min(buf.len());
+            buf[..n].copy_from_slice(&available[..n]);
+            self.pos += n as u64;
+            Ok(n)
+        }
+    }
+
+    impl Seek for RangeReader {
+        fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+            let target = match pos {
+                SeekFrom::Start(offset) => Some(offset),
+                SeekFrom::End(delta) => self.len.checked_add_signed(delta),
+                SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+            };
+            self.pos = target.ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "seek 到了文件开头之前")
+            })?;
+            Ok(self.pos)
+        }
+    }
+}
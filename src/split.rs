@@ -0,0 +1,195 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    archive::Archive,
+    events, extended,
+    format::{ARCHIVE_NAME_SIZE, AosV2Entry, AosV2Hdr, FILENAME_SIZE, Layout},
+    lock,
+    read_at::read_exact_at,
+    size::ByteSize,
+    validate::check_entry_bounds,
+};
+
+/// 拆分选项
+#[derive(Debug)]
+pub struct SplitOptions {
+    /// 每个输出文件的大小上限，包括文件头与目录表
+    pub max_size: ByteSize,
+    /// 输出文件名前缀，输出为 `<前缀>_001.aos`、`<前缀>_002.aos` 等，索引为 `<前缀>.index`
+    pub prefix: PathBuf,
+    /// 目录表布局，输出使用同样的布局，保留字段以 0 填充
+    pub layout: Layout,
+}
+
+/// 将 .aos 文件的条目按顺序贪心地分配到多个不超过大小上限的封包中，返回输出文件路径
+///
+/// 条目不会被拆开，当前封包放不下下一个条目时开始新的封包。各封包中的数据紧密排列，
+/// 文件头的 `unknown1` 沿用原文件。同时写入索引文件，每行为以制表符分隔的条目名称与所在封包。
+pub fn split_archive(archive_path: &Path, options: &SplitOptions) -> Result<Vec<PathBuf>> {
+    let mut file = lock::open_shared(archive_path)?;
+    let archive = Archive::from_reader_with_layout(&mut file, options.layout)?;
+    if extended::is_extended(&archive.header) {
+        bail!("暂不支持拆分扩展格式的封包");
+    }
+    let base_offset = archive.base_offset()?;
+    let file_len = file.metadata()?.len();
+    if let Some((_, length)) = archive.footer(file_len)? {
+        events::warn(
+            "footer_dropped",
+            None,
+            &format!("最后一个条目之后的 {length} 字节尾部数据不会写入输出"),
+        );
+    }
+
+    // 1. 按目录表顺序分配条目，放不下时开始新的封包
+    let header_size = std::mem::size_of::<AosV2Hdr>() as u64;
+    let entry_size = options.layout.entry_size() as u64;
+    let max = options.max_size.0;
+    let mut parts: Vec<Vec<usize>> = Vec::new();
+    let mut current: Vec<usize> = Vec::new();
+    let mut current_size = header_size;
+    for (index, entry) in archive.entries.iter().enumerate() {
+        let name = String::from_utf8_lossy(entry.raw_filename());
+        if let Some(error) = check_entry_bounds(&name, entry, base_offset, file_len) {
+            bail!("{}: {error}", options.layout.entry_location(index));
+        }
+        let needed = entry_size + entry.length as u64;
+        if header_size + needed > max {
+            bail!(
+                "条目 '{name}' 单独封包后为 {}，超过上限 {}",
+                ByteSize(header_size + needed),
+                options.max_size
+            );
+        }
+        if current_size + needed > max {
+            parts.push(std::mem::take(&mut current));
+            current_size = header_size;
+        }
+        current.push(index);
+        current_size += needed;
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+
+    // 2. 写入各个封包与索引
+    let mut outputs = Vec::with_capacity(parts.len());
+    let mut index_lines = String::new();
+    for (number, indices) in parts.iter().enumerate() {
+        let output_path = part_path(&options.prefix, number + 1);
+        let part_name = output_path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        write_part(&file, &archive, indices, &output_path, &part_name, options)?;
+        for &index in indices {
+            let name = String::from_utf8_lossy(archive.entries[index].raw_filename());
+            index_lines.push_str(&format!("{name}\t{part_name}\n"));
+        }
+        progress!("  -> {}: {} 个条目", output_path.display(), indices.len());
+        outputs.push(output_path);
+    }
+    let index_path = PathBuf::from(format!("{}.index", options.prefix.display()));
+    std::fs::write(&index_path, index_lines)
+        .with_context(|| format!("无法写入索引文件: {}", index_path.display()))?;
+    progress!(
+        "拆分完成，共 {} 个封包，索引文件: {}",
+        outputs.len(),
+        index_path.display()
+    );
+    Ok(outputs)
+}
+
+fn part_path(prefix: &Path, number: usize) -> PathBuf {
+    PathBuf::from(format!("{}_{number:03}.aos", prefix.display()))
+}
+
+fn write_part(
+    file: &File,
+    archive: &Archive,
+    indices: &[usize],
+    output_path: &Path,
+    part_name: &str,
+    options: &SplitOptions,
+) -> Result<()> {
+    let mut archive_name = [0u8; ARCHIVE_NAME_SIZE];
+    let name_len = part_name.floor_char_boundary(ARCHIVE_NAME_SIZE - 1);
+    archive_name[..name_len].copy_from_slice(&part_name.as_bytes()[..name_len]);
+    let toc_length = (indices.len() * options.layout.entry_size()) as u32;
+    let header = AosV2Hdr {
+        unknown1: archive.header.unknown1,
+        data_offset: std::mem::size_of::<AosV2Hdr>() as u32 + toc_length,
+        toc_length,
+        archive_name,
+    };
+
+    let output = File::create(output_path)
+        .with_context(|| format!("无法创建输出文件: {}", output_path.display()))?;
+    let mut output = BufWriter::new(output);
+    output.write_all(&header.to_bytes())?;
+    let mut offset = 0u32;
+    for &index in indices {
+        let entry = &archive.entries[index];
+        let mut filename = [0u8; FILENAME_SIZE];
+        let raw = entry.raw_filename();
+        filename[..raw.len()].copy_from_slice(raw);
+        let new_entry = AosV2Entry {
+            filename,
+            offset,
+            length: entry.length,
+        };
+        output.write_all(&options.layout.entry_to_bytes(&new_entry))?;
+        offset = offset
+            .checked_add(entry.length)
+            .context("数据区超出 4 GiB，无法封包")?;
+    }
+    let mut buffer = Vec::new();
+    for &index in indices {
+        let entry = &archive.entries[index];
+        buffer.resize(entry.length as usize, 0);
+        read_exact_at(file, &mut buffer, archive.entry_file_offset(entry)?)?;
+        output.write_all(&buffer)?;
+    }
+    output
+        .flush()
+        .with_context(|| format!("无法写入输出文件: {}", output_path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::testutil::{TempDir, make_padded_archive, make_test_archive};
+
+    #[test]
+    fn split_reads_entries_from_padded_data_offset() {
+        let dir = TempDir::new("split-padded");
+        let bytes = make_padded_archive(&[("a", b"abc"), ("b", b"de")], "t.aos", b"ZZZZ");
+        let archive = dir.write("test.aos", &bytes);
+        let options = SplitOptions {
+            // 文件头 273 + 目录表 40 + 3 字节数据，两个条目放不进同一个封包
+            max_size: ByteSize(320),
+            prefix: dir.path().join("part"),
+            layout: Layout::default(),
+        };
+        let outputs = split_archive(&archive, &options).unwrap();
+        assert_eq!(outputs.len(), 2);
+        assert_eq!(
+            fs::read(&outputs[0]).unwrap(),
+            make_test_archive(&[("a", b"abc")], "part_001.aos")
+        );
+        assert_eq!(
+            fs::read(&outputs[1]).unwrap(),
+            make_test_archive(&[("b", b"de")], "part_002.aos")
+        );
+    }
+}
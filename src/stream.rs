@@ -0,0 +1,151 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs::{self, File},
+    io::{self, Cursor, Read},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result, bail};
+
+use crate::{
+    events, extended,
+    format::AosV2Entry,
+    pack::{
+        PackOptions, build_header, check_max_size, collect_files, file_sizes, relative_name,
+        stored_name,
+    },
+    rename,
+};
+
+/// 按需产生封包字节的读取器，由 [`pack_stream`] 创建
+///
+/// 文件头、目录表与尾部数据在创建时生成，各文件的数据在读取到时才打开并按顺序读取，
+/// 任何时刻最多只打开一个文件。
+pub struct PackStream {
+    metadata: Cursor<Vec<u8>>,
+    /// 尚未读取的文件及其在预扫描时的大小
+    pending: VecDeque<(PathBuf, u64)>,
+    /// 正在读取的文件、其路径与剩余的字节数
+    current: Option<(File, PathBuf, u64)>,
+    footer: Cursor<Vec<u8>>,
+}
+
+/// 以流的形式封包目录，不在内存或磁盘上保存完整的封包
+///
+/// 先扫描文件大小以生成目录表，之后每个文件的数据在读取时才读入。预扫描之后文件大小发生变化时，
+/// 读取会返回 `InvalidData` 错误而不是产生损坏的封包。不支持 `dedup`；`output_archive`
+/// 只用于在扫描时跳过同名文件，`retry` 与 `sync` 不起作用。
+pub fn pack_stream(dir_path: &Path, options: &PackOptions) -> Result<PackStream> {
+    if options.dedup {
+        bail!("流式封包需要预先确定每个条目的位置，不支持去重");
+    }
+    let files = collect_files(dir_path, options)?;
+    let renames = options.renames()?;
+    let sizes = file_sizes(options.name_root(dir_path), &files, &renames)?;
+    if let Some(max) = options.max_archive_size {
+        check_max_size(&files, &sizes, options.entry_layout(), max)?;
+    }
+    let mut flags = match &options.flags_file {
+        Some(path) => extended::read_flags_file(path)?,
+        None => BTreeMap::new(),
+    };
+
+    let header = build_header(dir_path, files.len(), options)?;
+    let layout = options.entry_layout();
+    let mut metadata = header.to_bytes();
+    let mut offset = 0u32;
+    for (path, &size) in files.iter().zip(&sizes) {
+        let name = stored_name(options.name_root(dir_path), path, &renames)?;
+        let length = u32::try_from(size).context(format!("文件 '{name}' 过大"))?;
+        let filename = options.name_style.encode(name.as_bytes())?;
+        let entry = AosV2Entry {
+            filename,
+            offset,
+            length,
+        };
+        let bytes = if options.extended {
+            extended::entry_to_bytes(&entry, flags.remove(&name).unwrap_or(0))
+        } else {
+            layout.entry_to_bytes(&entry)
+        };
+        metadata.extend_from_slice(&bytes);
+        offset = offset
+            .checked_add(length)
+            .context("数据区超出 4 GiB，无法封包")?;
+    }
+    for name in flags.keys() {
+        events::warn(
+            "unused_flags",
+            Some(name),
+            &format!("标记文件中的 '{name}' 没有对应的文件"),
+        );
+    }
+    let mut unused = renames;
+    unused.retain(|old, _| {
+        !files.iter().any(|path| {
+            relative_name(options.name_root(dir_path), path).is_ok_and(|name| name == *old)
+        })
+    });
+    rename::warn_unused(&unused);
+
+    let footer = match &options.footer {
+        Some(path) => {
+            fs::read(path).with_context(|| format!("无法读取尾部数据文件: {}", path.display()))?
+        }
+        None => Vec::new(),
+    };
+
+    Ok(PackStream {
+        metadata: Cursor::new(metadata),
+        pending: files.into_iter().zip(sizes).collect(),
+        current: None,
+        footer: Cursor::new(footer),
+    })
+}
+
+impl Read for PackStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let n = self.metadata.read(buf)?;
+        if n > 0 {
+            return Ok(n);
+        }
+        loop {
+            if let Some((file, path, remaining)) = &mut self.current {
+                if *remaining == 0 {
+                    // 文件在预扫描之后变大
+                    if file.read(&mut [0u8])? > 0 {
+                        return Err(size_changed(path));
+                    }
+                    self.current = None;
+                    continue;
+                }
+                let wanted = buf
+                    .len()
+                    .min(usize::try_from(*remaining).unwrap_or(usize::MAX));
+                let n = file.read(&mut buf[..wanted])?;
+                if n == 0 {
+                    return Err(size_changed(path));
+                }
+                *remaining -= n as u64;
+                return Ok(n);
+            }
+            match self.pending.pop_front() {
+                Some((path, size)) => {
+                    let file = File::open(&path)?;
+                    self.current = Some((file, path, size));
+                }
+                None => return self.footer.read(buf),
+            }
+        }
+    }
+}
+
+fn size_changed(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("文件在封包过程中大小发生了变化: {}", path.display()),
+    )
+}
@@ -0,0 +1,190 @@
+//! 测试用的 .aos 文件构造工具，需要启用 `testutil` 特性
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::format::{ARCHIVE_NAME_SIZE, AosV2Entry, AosV2Hdr, FILENAME_SIZE};
+
+/// 只有一个条目的最小封包，由独立于本工具的脚本生成，用于检查封包与解包的字节布局:
+///
+/// | 偏移量 | 长度 | 内容                                                  |
+/// | -----: | ---: | :---------------------------------------------------- |
+/// |      0 |    4 | `unknown1` = 0 (以下整数均为小端序 u32)               |
+/// |      4 |    4 | `data_offset` = 313 (0x139)，即文件头与目录表的总大小 |
+/// |      8 |    4 | `toc_length` = 40，一个条目                           |
+/// |     12 |  261 | 封包名称 `golden.aos`，其后以 0 填充                  |
+/// |    273 |   32 | 条目名称 `hello.txt`，其后以 0 填充                   |
+/// |    305 |    4 | 条目偏移量 0，相对于数据区                            |
+/// |    309 |    4 | 条目长度 4                                            |
+/// |    313 |    4 | 条目数据 `hi!\n`                                      |
+pub const GOLDEN_ARCHIVE: &[u8] = include_bytes!("../tests/fixtures/golden.aos");
+
+/// [`make_corrupt_archive`] 可构造的结构缺陷
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Defect {
+    /// `toc_length` 不是条目大小的整数倍
+    BadTocLength,
+    /// 最后一个条目的偏移量刚好超出文件末尾
+    OffsetOutOfBounds,
+    /// 最后一个条目的长度超出文件末尾
+    LengthOutOfBounds,
+    /// 最后一个条目与第一个条目的数据重叠，至少需要两个条目
+    OverlappingEntries,
+    /// 目录表末尾带有两个全为 0 的填充条目，`toc_length` 与 `data_offset` 包括它们
+    TrailingZeroEntries,
+    /// 最后一个条目的文件名全为 0，偏移量与长度保持不变
+    UnnamedEntry,
+}
+
+/// 在内存中构造一个有效的 AOS V2 封包，条目按给定顺序连续存放
+///
+/// 文件名或封包名称超出格式允许的长度时 panic。
+pub fn make_test_archive(entries: &[(&str, &[u8])], archive_name: &str) -> Vec<u8> {
+    let (header, toc, data) = build(entries, archive_name);
+    let mut bytes = header.to_bytes();
+    for entry in &toc {
+        bytes.extend_from_slice(&entry.to_bytes());
+    }
+    bytes.extend_from_slice(&data);
+    bytes
+}
+
+/// 在内存中构造一个目录表与数据区之间带有填充的 AOS V2 封包
+///
+/// `data_offset` 指向 `padding` 之后，其余部分与 [`make_test_archive`] 相同。
+pub fn make_padded_archive(
+    entries: &[(&str, &[u8])],
+    archive_name: &str,
+    padding: &[u8],
+) -> Vec<u8> {
+    let (mut header, toc, data) = build(entries, archive_name);
+    header.data_offset += padding.len() as u32;
+    let mut bytes = header.to_bytes();
+    for entry in &toc {
+        bytes.extend_from_slice(&entry.to_bytes());
+    }
+    bytes.extend_from_slice(padding);
+    bytes.extend_from_slice(&data);
+    bytes
+}
+
+/// 将封包数据中文件头的 `data_offset` 改为 `data_offset`
+pub fn set_data_offset(bytes: &mut [u8], data_offset: u32) {
+    bytes[4..8].copy_from_slice(&data_offset.to_le_bytes());
+}
+
+/// 在内存中构造一个带有指定缺陷的 AOS V2 封包
+///
+/// 除缺陷之外的部分与 [`make_test_archive`] 相同。`entries` 为空，
+/// 或构造 [`Defect::OverlappingEntries`] 时条目少于两个会 panic。
+pub fn make_corrupt_archive(
+    entries: &[(&str, &[u8])],
+    archive_name: &str,
+    defect: Defect,
+) -> Vec<u8> {
+    assert!(!entries.is_empty(), "至少需要一个条目");
+    let (mut header, mut toc, data) = build(entries, archive_name);
+    let last = toc.len() - 1;
+    match defect {
+        Defect::BadTocLength => header.toc_length += 1,
+        Defect::OffsetOutOfBounds => toc[last].offset = data.len() as u32 + 1,
+        Defect::LengthOutOfBounds => toc[last].length += 1,
+        Defect::UnnamedEntry => toc[last].filename = [0; FILENAME_SIZE],
+        Defect::OverlappingEntries => {
+            assert!(last > 0, "构造重叠条目至少需要两个条目");
+            toc[last].offset = 0;
+        }
+        Defect::TrailingZeroEntries => {
+            for _ in 0..2 {
+                toc.push(AosV2Entry {
+                    filename: [0; FILENAME_SIZE],
+                    offset: 0,
+                    length: 0,
+                });
+            }
+            let added = 2 * std::mem::size_of::<AosV2Entry>() as u32;
+            header.toc_length += added;
+            header.data_offset += added;
+        }
+    }
+
+    let mut bytes = header.to_bytes();
+    for entry in &toc {
+        bytes.extend_from_slice(&entry.to_bytes());
+    }
+    bytes.extend_from_slice(&data);
+    bytes
+}
+
+fn build(entries: &[(&str, &[u8])], archive_name: &str) -> (AosV2Hdr, Vec<AosV2Entry>, Vec<u8>) {
+    assert!(
+        archive_name.len() < ARCHIVE_NAME_SIZE,
+        "封包名称 '{archive_name}' 过长"
+    );
+    let mut archive_name_bytes = [0u8; ARCHIVE_NAME_SIZE];
+    archive_name_bytes[..archive_name.len()].copy_from_slice(archive_name.as_bytes());
+
+    let mut toc = Vec::with_capacity(entries.len());
+    let mut data = Vec::new();
+    for (name, content) in entries {
+        assert!(name.len() < FILENAME_SIZE, "文件名 '{name}' 过长");
+        let mut filename = [0u8; FILENAME_SIZE];
+        filename[..name.len()].copy_from_slice(name.as_bytes());
+        toc.push(AosV2Entry {
+            filename,
+            offset: data.len() as u32,
+            length: content.len() as u32,
+        });
+        data.extend_from_slice(content);
+    }
+
+    let header_size = std::mem::size_of::<AosV2Hdr>() as u32;
+    let toc_length = (toc.len() * std::mem::size_of::<AosV2Entry>()) as u32;
+    let header = AosV2Hdr {
+        unknown1: 0,
+        data_offset: header_size + toc_length,
+        toc_length,
+        archive_name: archive_name_bytes,
+    };
+    (header, toc, data)
+}
+
+/// 系统临时目录下的独立目录，离开作用域时连同其内容一起删除
+#[derive(Debug)]
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// 创建一个新的空目录，名称包含 `prefix`、进程号与计数器，并行的测试之间互不影响
+    pub fn new(prefix: &str) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let id = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let path =
+            std::env::temp_dir().join(format!("aos_up-{prefix}-{}-{id}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("无法创建临时目录");
+        Self(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// 将 `bytes` 写入目录下的 `name`，返回其路径
+    pub fn write(&self, name: &str, bytes: &[u8]) -> PathBuf {
+        let path = self.0.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("无法创建目录");
+        }
+        fs::write(&path, bytes).expect("无法写入文件");
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,70 @@
+//! 文本条目在 Shift-JIS 与 UTF-8 之间的转换，用于 `unpack --decode-text` 与 `pack --encode-text`
+
+use anyhow::{Result, bail};
+use encoding_rs::{EncoderResult, SHIFT_JIS};
+
+use crate::events;
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// 将 Shift-JIS 文本转换为 UTF-8，`bom` 时在开头写入 BOM
+///
+/// 数据不是有效的 Shift-JIS，或转换后无法逐字节还原为原数据 (例如 NEC 与 IBM 扩展字符的重复编码)
+/// 时返回错误，调用方应原样写出数据。
+pub(crate) fn decode_sjis(data: &[u8], bom: bool) -> Result<Vec<u8>> {
+    let Some(text) = SHIFT_JIS.decode_without_bom_handling_and_without_replacement(data) else {
+        bail!("不是有效的 Shift-JIS 文本");
+    };
+    let (encoded, _, unmappable) = SHIFT_JIS.encode(&text);
+    if unmappable || encoded[..] != *data {
+        bail!("转换为 UTF-8 后无法还原为相同的 Shift-JIS 字节");
+    }
+    let mut output = Vec::with_capacity(text.len() + UTF8_BOM.len());
+    if bom {
+        output.extend_from_slice(UTF8_BOM);
+    }
+    output.extend_from_slice(text.as_bytes());
+    Ok(output)
+}
+
+/// 将 UTF-8 文本 (可带 BOM) 转换为 Shift-JIS
+///
+/// 无法以 Shift-JIS 表示的字符替换为 `?`，并以条目 `name` 的名义给出警告。
+pub(crate) fn encode_sjis(name: &str, data: &[u8]) -> Result<Vec<u8>> {
+    let data = data.strip_prefix(UTF8_BOM).unwrap_or(data);
+    let Ok(mut input) = std::str::from_utf8(data) else {
+        bail!("'{name}' 不是有效的 UTF-8 文本");
+    };
+    let mut encoder = SHIFT_JIS.new_encoder();
+    let mut output = Vec::with_capacity(
+        encoder
+            .max_buffer_length_from_utf8_without_replacement(input.len())
+            .unwrap_or(input.len()),
+    );
+    let mut unmappable = Vec::new();
+    loop {
+        let (result, read) =
+            encoder.encode_from_utf8_to_vec_without_replacement(input, &mut output, true);
+        input = &input[read..];
+        match result {
+            EncoderResult::InputEmpty => break,
+            EncoderResult::OutputFull => output.reserve(input.len() * 2 + 16),
+            EncoderResult::Unmappable(c) => {
+                unmappable.push(c);
+                output.push(b'?');
+            }
+        }
+    }
+    if !unmappable.is_empty() {
+        let chars: String = unmappable.iter().take(16).collect();
+        events::warn(
+            "unencodable_text",
+            Some(name),
+            &format!(
+                "'{name}' 中有 {} 个字符无法以 Shift-JIS 表示，已替换为 '?': {chars}",
+                unmappable.len()
+            ),
+        );
+    }
+    Ok(output)
+}
@@ -0,0 +1,105 @@
+//! 并行命令使用的线程数
+//!
+//! 目前只有 `validate` 会并行处理：`pack` 依次读取文件，`unpack --pipeline` 固定使用一个后台写出线程，
+//! 二者都不受线程数影响。
+
+use std::{fmt, num::NonZeroUsize, str::FromStr, thread};
+
+use anyhow::{Context, bail};
+
+use crate::events::{self, Event, Level};
+
+/// 线程数上限，更大的值没有意义，只会耗尽系统资源
+pub const MAX_THREADS: usize = 256;
+
+/// 线程数: `auto` 或正整数，`1` 表示不创建工作线程
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Threads {
+    /// 使用 [`std::thread::available_parallelism`]，无法检测时为 1
+    #[default]
+    Auto,
+    Count(NonZeroUsize),
+}
+
+impl Threads {
+    /// 实际使用的线程数，超出 [`MAX_THREADS`] 时给出警告并截断
+    ///
+    /// 结果以 `threads` 调试事件的形式写出。
+    pub fn resolve(self) -> usize {
+        let count = match self {
+            Self::Auto => match thread::available_parallelism() {
+                Ok(count) => count.get().min(MAX_THREADS),
+                Err(err) => {
+                    events::warn(
+                        "threads",
+                        None,
+                        &format!("无法检测可用的并行度: {err}，使用单线程"),
+                    );
+                    1
+                }
+            },
+            Self::Count(count) if count.get() > MAX_THREADS => {
+                events::warn(
+                    "threads",
+                    None,
+                    &format!("线程数 {count} 过大，使用 {MAX_THREADS}"),
+                );
+                MAX_THREADS
+            }
+            Self::Count(count) => count.get(),
+        };
+        Event {
+            level: Level::Debug,
+            event: "threads",
+            message: Some(&format!("使用 {count} 个线程 (--threads {self})")),
+            ..Default::default()
+        }
+        .emit();
+        count
+    }
+}
+
+impl FromStr for Threads {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(Self::Auto);
+        }
+        let count: usize = s
+            .parse()
+            .with_context(|| format!("无效的线程数: '{s}'，应为 auto 或正整数"))?;
+        match NonZeroUsize::new(count) {
+            Some(count) => Ok(Self::Count(count)),
+            None => bail!("线程数必须大于 0"),
+        }
+    }
+}
+
+impl fmt::Display for Threads {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Auto => f.write_str("auto"),
+            Self::Count(count) => write!(f, "{count}"),
+        }
+    }
+}
+
+/// 配置文件中可以写为 `"auto"` 或正整数
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Threads {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Count(usize),
+            Text(String),
+        }
+        let text = match Raw::deserialize(deserializer)? {
+            Raw::Count(count) => count.to_string(),
+            Raw::Text(text) => text,
+        };
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,38 @@
+//! 单个文件操作的超时，用于防止挂起的网络文件系统让整个操作永远停住
+//!
+//! 标准库无法取消一个阻塞中的系统调用，超时的操作只是不再被等待: 执行它的线程
+//! 会一直留在后台，直到调用返回或进程退出，超时之后仍可能写入文件。
+
+use std::{
+    io::{self, ErrorKind},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Duration,
+};
+
+/// 在单独的线程中执行 `op`，超过 `timeout` 仍未完成时返回 [`ErrorKind::TimedOut`] 错误
+///
+/// `timeout` 为 `None` 时直接在当前线程中执行，不创建线程。
+pub(crate) fn with_timeout<T: Send + 'static>(
+    timeout: Option<Duration>,
+    op: impl FnOnce() -> io::Result<T> + Send + 'static,
+) -> io::Result<T> {
+    let Some(timeout) = timeout else {
+        return op();
+    };
+    let (sender, receiver) = mpsc::sync_channel(1);
+    thread::Builder::new()
+        .name("aos-io".to_owned())
+        .spawn(move || {
+            // 超时后接收端已被丢弃，结果无人接收
+            let _ = sender.send(op());
+        })?;
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(RecvTimeoutError::Timeout) => Err(io::Error::new(
+            ErrorKind::TimedOut,
+            format!("操作超过 {} 秒仍未完成", timeout.as_secs_f64()),
+        )),
+        Err(RecvTimeoutError::Disconnected) => Err(io::Error::other("执行 IO 操作的线程异常退出")),
+    }
+}
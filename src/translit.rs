@@ -0,0 +1,183 @@
+//! 将文件名转写为 ASCII，用于不支持非 ASCII 文件名的文件系统或工具
+//!
+//! 假名按平文式罗马字转写，全角字母、数字与符号转为对应的半角字符，常见的带变音符号的
+//! 拉丁字母去掉变音符号，其余字符 (包括汉字) 写为 `u` 加十六进制码位，例如 `u6f22`。
+//! 转写是有损的，不同的名称可能得到相同的结果。
+
+/// 平假名 U+3041 到 U+3096 的罗马字，小写假名与对应的普通假名相同
+const HIRAGANA: [&str; 86] = [
+    "a", "a", "i", "i", "u", "u", "e", "e", "o", "o", // ぁ - お
+    "ka", "ga", "ki", "gi", "ku", "gu", "ke", "ge", "ko", "go", // か - ご
+    "sa", "za", "shi", "ji", "su", "zu", "se", "ze", "so", "zo", // さ - ぞ
+    "ta", "da", "chi", "ji", "tsu", "tsu", "zu", "te", "de", "to", "do", // た - ど
+    "na", "ni", "nu", "ne", "no", // な - の
+    "ha", "ba", "pa", "hi", "bi", "pi", "fu", "bu", "pu", "he", "be", "pe", "ho", "bo",
+    "po", // は - ぽ
+    "ma", "mi", "mu", "me", "mo", // ま - も
+    "ya", "ya", "yu", "yu", "yo", "yo", // ゃ - よ
+    "ra", "ri", "ru", "re", "ro", // ら - ろ
+    "wa", "wa", "wi", "we", "wo", "n", "vu", "ka", "ke", // ゎ - ゖ
+];
+
+/// 去掉变音符号的拉丁字母
+const LATIN: &[(char, &str)] = &[
+    ('À', "A"),
+    ('Á', "A"),
+    ('Â', "A"),
+    ('Ã', "A"),
+    ('Ä', "A"),
+    ('Å', "A"),
+    ('Æ', "AE"),
+    ('Ç', "C"),
+    ('È', "E"),
+    ('É', "E"),
+    ('Ê', "E"),
+    ('Ë', "E"),
+    ('Ì', "I"),
+    ('Í', "I"),
+    ('Î', "I"),
+    ('Ï', "I"),
+    ('Ñ', "N"),
+    ('Ò', "O"),
+    ('Ó', "O"),
+    ('Ô', "O"),
+    ('Õ', "O"),
+    ('Ö', "O"),
+    ('Ø', "O"),
+    ('Ù', "U"),
+    ('Ú', "U"),
+    ('Û', "U"),
+    ('Ü', "U"),
+    ('Ý', "Y"),
+    ('ß', "ss"),
+    ('à', "a"),
+    ('á', "a"),
+    ('â', "a"),
+    ('ã', "a"),
+    ('ä', "a"),
+    ('å', "a"),
+    ('æ', "ae"),
+    ('ç', "c"),
+    ('è', "e"),
+    ('é', "e"),
+    ('ê', "e"),
+    ('ë', "e"),
+    ('ì', "i"),
+    ('í', "i"),
+    ('î', "i"),
+    ('ï', "i"),
+    ('ñ', "n"),
+    ('ò', "o"),
+    ('ó', "o"),
+    ('ô', "o"),
+    ('õ', "o"),
+    ('ö', "o"),
+    ('ø', "o"),
+    ('ù', "u"),
+    ('ú', "u"),
+    ('û', "u"),
+    ('ü', "u"),
+    ('ý', "y"),
+    ('ÿ', "y"),
+];
+
+/// 日文标点
+const PUNCTUATION: &[(char, &str)] = &[
+    ('\u{3000}', " "),
+    ('、', ","),
+    ('。', "."),
+    ('「', "["),
+    ('」', "]"),
+    ('『', "["),
+    ('』', "]"),
+    ('【', "["),
+    ('】', "]"),
+    ('〜', "~"),
+    ('・', "."),
+    ('ー', "-"),
+];
+
+/// 将名称转写为 ASCII，ASCII 字符 (包括路径分隔符) 保持不变
+pub(crate) fn to_ascii(name: &str) -> String {
+    let mut out = String::with_capacity(name.len());
+    // 促音使下一个假名的辅音重复
+    let mut sokuon = false;
+    let mut after_kana = false;
+    for c in name.chars() {
+        let Some(index) = kana_index(c) else {
+            if sokuon {
+                out.push_str("tsu");
+                sokuon = false;
+            }
+            after_kana = false;
+            push_other(&mut out, c);
+            continue;
+        };
+        // っ、ッ
+        if index == 0x22 {
+            sokuon = true;
+            continue;
+        }
+        let romaji = HIRAGANA[index];
+        let small = matches!(index, 0x00 | 0x02 | 0x04 | 0x06 | 0x08 | 0x42 | 0x44 | 0x46);
+        if small && after_kana {
+            // 拗音与外来语的小写元音: きゃ -> kya、しゃ -> sha、ファ -> fa
+            let vowel = &romaji[romaji.len() - 1..];
+            if romaji.starts_with('y') && out.ends_with('i') {
+                out.pop();
+                if !(out.ends_with("sh") || out.ends_with("ch") || out.ends_with('j')) {
+                    out.push('y');
+                }
+            } else if out.ends_with(['a', 'i', 'u', 'e', 'o']) {
+                out.pop();
+            }
+            out.push_str(vowel);
+            continue;
+        }
+        if sokuon {
+            if romaji.starts_with("ch") {
+                out.push('t');
+            } else if let Some(first) = romaji.chars().next().filter(|c| !"aiueon".contains(*c)) {
+                out.push(first);
+            }
+            sokuon = false;
+        }
+        out.push_str(romaji);
+        after_kana = true;
+    }
+    if sokuon {
+        out.push_str("tsu");
+    }
+    out
+}
+
+/// 平假名或片假名在 [`HIRAGANA`] 中的序号
+fn kana_index(c: char) -> Option<usize> {
+    let code = c as u32;
+    let hiragana = match code {
+        0x3041..=0x3096 => code,
+        // 片假名与平假名相差 0x60
+        0x30a1..=0x30f6 => code - 0x60,
+        _ => return None,
+    };
+    Some((hiragana - 0x3041) as usize)
+}
+
+fn push_other(out: &mut String, c: char) {
+    if c.is_ascii() {
+        out.push(c);
+    } else if let '\u{ff01}'..='\u{ff5e}' = c {
+        // 全角 ASCII
+        out.push(char::from_u32(c as u32 - 0xfee0).unwrap_or('_'));
+    } else if let Some((_, s)) = LATIN.iter().chain(PUNCTUATION).find(|(k, _)| *k == c) {
+        out.push_str(s);
+    } else {
+        match c {
+            'ヷ' => out.push_str("va"),
+            'ヸ' => out.push_str("vi"),
+            'ヹ' => out.push_str("ve"),
+            'ヺ' => out.push_str("vo"),
+            _ => out.push_str(&format!("u{:x}", c as u32)),
+        }
+    }
+}
@@ -0,0 +1,1724 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fs,
+    io::{self, BufReader, Read, Seek, Write},
+    num::{NonZeroU64, NonZeroUsize},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, atomic::AtomicBool},
+    time::Duration,
+};
+
+use anyhow::{Context, Result, bail};
+use regex::Regex;
+
+use crate::{
+    archive::{Archive, footer_range, toc_entries},
+    cancel,
+    events::{self, Event, Progress},
+    extended::{self, extended_layout},
+    filter::EntryFilter,
+    format::{AUTO_SAMPLE_SIZE, AosV2Entry, AosV2Hdr, Layout, NameDecoder, NameEncoding},
+    hash::hash_range,
+    magic::{self, MAGIC_LEN},
+    meta::{self, ArchiveMeta},
+    output::{EntryInfo, ListDocument, OutputFormat, render},
+    pack::temp_path,
+    pipeline::WriteQueue,
+    probe::{self, ProbeClass},
+    read_at::read_exact_at,
+    retry::with_retry,
+    source::{Source, is_url},
+    text,
+    timeout::with_timeout,
+    translit,
+    validate::{check_data_align, check_entry_bounds},
+    zip::ZipWriter,
+};
+
+/// 解包选项
+#[derive(Debug, Default)]
+pub struct UnpackOptions {
+    /// 输出目录，为 `None` 时解包到与 .aos 文件同名的目录
+    pub output_dir: Option<PathBuf>,
+    /// 在输出目录下再嵌套一层的子目录
+    pub prefix: Option<PathBuf>,
+    /// 目录表布局
+    pub layout: Layout,
+    /// 文件名解码方式
+    pub names: NameDecoder,
+    /// 只提取被选中的条目
+    pub filter: EntryFilter,
+    /// 读写单个条目遇到暂时性 IO 错误时总共最多尝试的次数，0 与 1 都表示不重试
+    pub retry: u32,
+    /// 读取或写入单个条目 (包括重试) 超过该时间仍未完成时以超时错误中止，
+    /// 与 `best_effort` 同时使用时跳过该条目。只限制本地文件，见 [`with_timeout`]
+    pub op_timeout: Option<Duration>,
+    /// 数据区起始偏移量不是该值的整数倍时给出警告
+    pub expect_data_align: Option<NonZeroU64>,
+    /// 解包完成后将已提取条目的索引写入该文件
+    pub index: Option<PathBuf>,
+    /// 按偏移量升序提取条目，使读取成为一次顺序扫描
+    pub sort_by_offset: bool,
+    /// 尽力提取：跳过无法提取的条目并给出警告，而不是中止，输出可能不完整
+    pub best_effort: bool,
+    /// 将文件名中在 Windows 上非法的字符替换为该字符
+    pub sanitize: Option<char>,
+    /// 按本工具的扩展格式读取，忽略 `layout`
+    pub extended: bool,
+    /// 扩展格式下将已提取条目的标记写入该文件
+    pub flags_file: Option<PathBuf>,
+    /// 文件名匹配该正则表达式的条目在写入前去掉数据末尾的空字节
+    pub trim_trailing_nulls: Option<Regex>,
+    /// 只提取该文件中逐行列出的条目，为 `-` 时从标准输入读取
+    pub names_from: Option<PathBuf>,
+    /// 与 `names_from` 同时使用时，列出的名称在封包中不存在则报错而不是警告
+    pub strict_names: bool,
+    /// 将最后一个条目之后的尾部数据写入该文件
+    pub footer: Option<PathBuf>,
+    /// 只提取数据开头的魔数识别为该类型的条目，例如 `png`，不区分大小写；
+    /// 没有扩展名的条目写出时补上识别到的扩展名
+    pub content_type: Option<String>,
+    /// 忽略文件头中的 `data_offset`，始终按文件头与目录表大小计算数据区起始偏移量
+    pub compute_base: bool,
+    /// 取消标记，每提取一个条目前检查，被设置时以 [`Cancelled`](crate::Cancelled) 错误中止；
+    /// 已提取的条目保留在输出目录中，不会留下只写入了一部分的文件；`pipeline` 时队列中尚未写入的条目被丢弃
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// 每写入一个条目后立即向标准输出写入一行描述该条目的 JSON 对象并刷新
+    pub json_stream: bool,
+    /// 将条目名称统一为小写后写出，只有大小写不同的条目加上 `~N` 后缀以免相互覆盖，
+    /// 并将磁盘上的名称与原名称写入该文件，格式与 `pack --rename-map` 的重命名表相同，
+    /// 用于在不区分大小写的文件系统上解包后重新封包时恢复原名称
+    pub case_db: Option<PathBuf>,
+    /// 提取每个条目时打印其文件内绝对偏移量、相对数据区的偏移量与长度，用于排查偏移量计算问题
+    pub debug_offsets: bool,
+    /// 将条目名称转写为 ASCII 后写出，例如假名转为罗马字。转写是有损的，
+    /// 应与 `case_db` 同时使用，以便记录原名称并为转写结果相同的条目加上后缀
+    pub transliterate: bool,
+    /// 在后台线程中写出文件，读取下一个条目与写入上一个条目同时进行，适用于源文件与输出目录
+    /// 位于不同设备的情况。进度回调与取消检查在条目被读入时进行，写入失败在之后提交条目或
+    /// 解包结束时报告
+    pub pipeline: bool,
+    /// 跳过名称为空 (但偏移量或长度不为 0) 的条目并给出警告，而不是报错。
+    /// 全为 0 的填充条目由 `filter` 处理
+    pub skip_unnamed: bool,
+    /// 提取完成后检查每个条目是否本身也是 .aos 封包 (判断方式与 `probe` 相同)，是则解包到条目旁
+    /// 以条目名称命名的目录中，最多嵌套该层数。嵌套的封包使用相同的名称解码、重试与容错选项，
+    /// 不使用筛选条件
+    pub recursive: Option<NonZeroUsize>,
+    /// 将条目写入该 .zip 文件而不是输出目录，名称以 UTF-8 存储并保留路径的各部分，
+    /// 不压缩且时间戳固定，相同的输入得到逐字节相同的 zip 文件。不能与 `pipeline`、`recursive` 同时使用
+    pub zip: Option<PathBuf>,
+    /// 文件名匹配该正则表达式的条目按 Shift-JIS 文本转换为 UTF-8 后写出，其余条目原样写出。
+    /// 数据不是有效的 Shift-JIS 或无法逐字节还原时给出警告并原样写出
+    pub decode_text: Option<Regex>,
+    /// 与 `decode_text` 同时使用时在转换后的文本开头写入 UTF-8 BOM
+    pub text_bom: bool,
+}
+
+/// 解包 .aos 文件
+pub fn unpack_archive(archive_path: &Path, options: &UnpackOptions) -> Result<()> {
+    unpack(archive_path, options, None, 0)?;
+    Ok(())
+}
+
+/// 解包 .aos 文件，每提取一个条目后调用一次 `progress`
+///
+/// 总字节数为被选中条目的大小之和，在提取前额外读取一遍目录表得到；按 `content_type`
+/// 筛选时无法预先得知条目类型，总字节数只是上限。
+pub fn unpack_archive_with_progress(
+    archive_path: &Path,
+    options: &UnpackOptions,
+    mut progress: impl FnMut(Progress),
+) -> Result<()> {
+    unpack(archive_path, options, Some(&mut progress), 0)?;
+    Ok(())
+}
+
+/// 从绝对偏移量读取 `len` 字节，指定 `timeout` 时本地文件的读取在单独的线程中进行
+fn read_entry_data(
+    file: &mut Source,
+    offset: u64,
+    len: usize,
+    retry: u32,
+    timeout: Option<Duration>,
+) -> io::Result<Vec<u8>> {
+    match (file, timeout) {
+        (Source::File(file), Some(_)) => {
+            let file = file.try_clone()?;
+            with_timeout(timeout, move || {
+                let mut buffer = vec![0u8; len];
+                with_retry(retry, || read_exact_at(&file, &mut buffer, offset))?;
+                Ok(buffer)
+            })
+        }
+        (file, _) => {
+            let mut buffer = vec![0u8; len];
+            with_retry(retry, || file.read_exact_at(&mut buffer, offset))?;
+            Ok(buffer)
+        }
+    }
+}
+
+/// 离开作用域时删除其中的临时文件，用于出错或取消时清理未完成的输出
+struct TempGuard(Option<PathBuf>);
+
+impl Drop for TempGuard {
+    fn drop(&mut self) {
+        if let Some(path) = self.0.take() {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// 嵌套封包的解包结果
+#[derive(Debug, Default, Clone, Copy)]
+struct Nesting {
+    /// 解包的嵌套封包数，包括更深层的
+    archives: usize,
+    /// 到达的最深层数，最外层为 0
+    depth: usize,
+}
+
+/// 解包第 `level` 层 (最外层为 0) 的封包
+fn unpack(
+    archive_path: &Path,
+    options: &UnpackOptions,
+    mut progress: Option<&mut dyn FnMut(Progress)>,
+    level: usize,
+) -> Result<Nesting> {
+    let wanted = options.names_from.as_deref().map(read_names).transpose()?;
+    let missing = wanted.clone().unwrap_or_default();
+    let content_type = options.content_type.as_deref().map(magic::normalize_type);
+
+    progress!("正在解包: {}", archive_path.display());
+    Event {
+        event: "unpack",
+        file: Some(&archive_path.to_string_lossy()),
+        ..Default::default()
+    }
+    .emit();
+
+    let mut file = Source::open(archive_path)?;
+
+    // 1. 读取文件头，目录表 (TOC) 在提取时通过另一个文件句柄逐个读取
+    let header = AosV2Hdr::from_reader(&mut file)?;
+    extended::check_variant(&header, options.extended)?;
+    let layout = if options.extended {
+        extended_layout()
+    } else {
+        options.layout
+    };
+    let mut toc_reader = BufReader::new(Source::open(archive_path)?);
+
+    // 2. 创建输出目录，写入 zip 时改为创建临时的 zip 文件
+    let output_dir = output_dir(archive_path, options)?;
+    let zip_path = options.zip.clone().unwrap_or_default();
+    let mut zip_temp = TempGuard(None);
+    let zip = open_output(options, &output_dir, &mut zip_temp)?;
+
+    // 3. 确定数据区基地址并提取文件，data_offset 非零时以其为准
+    let base_offset = if options.compute_base {
+        header.toc_end()
+    } else {
+        header.declared_base_offset()?
+    };
+    if base_offset != header.toc_end() {
+        let padding = base_offset - header.toc_end();
+        progress!("  注意: data_offset 为 {base_offset}，目录表之后有 {padding} 字节填充");
+    }
+    let file_len = file.len()?;
+    if options.debug_offsets {
+        progress!(
+            "  数据区起始偏移量: {base_offset} (文件头 {} + 目录表 {} 字节，data_offset 为 {})",
+            std::mem::size_of::<AosV2Hdr>(),
+            { header.toc_length },
+            { header.data_offset }
+        );
+    }
+    let entry_count = header.entry_count(layout);
+    options.filter.check_count(entry_count)?;
+    // 自动选择编码时以目录表开头的若干条目为样本，之后的提取会重新定位到目录表开头
+    let names = if options.names.encoding == NameEncoding::Auto {
+        let sample: Vec<AosV2Entry> = toc_entries(&mut toc_reader, entry_count, layout)
+            .take(AUTO_SAMPLE_SIZE)
+            .map_while(Result::ok)
+            .collect();
+        options.names.resolve(&sample)?
+    } else {
+        options.names
+    };
+    let flags = if options.extended {
+        extended::read_flags(&mut file, entry_count)?
+    } else {
+        Vec::new()
+    };
+    if let Some(align) = options.expect_data_align
+        && let Some(warning) = check_data_align(base_offset, align)
+    {
+        events::warn("data_align", None, &warning);
+    }
+
+    let bytes_total = match progress {
+        Some(_) => selected_size(
+            archive_path,
+            entry_count,
+            layout,
+            options,
+            &names,
+            wanted.as_ref(),
+        )?,
+        None => 0,
+    };
+    let mut bytes_done = 0u64;
+
+    // 按偏移量排序需要先读入整个目录表，否则逐个读取条目
+    let toc = toc_entries(&mut toc_reader, entry_count, layout).enumerate();
+    let entries: Box<dyn Iterator<Item = (usize, Result<AosV2Entry>)>> = if options.sort_by_offset {
+        let mut entries = Vec::with_capacity(entry_count);
+        for (index, entry) in toc {
+            match entry {
+                Ok(entry) => entries.push((index, entry)),
+                Err(err) if options.best_effort => {
+                    warn_truncated_toc(index, &err);
+                    break;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        entries.sort_by_key(|(_, entry)| entry.offset);
+        Box::new(entries.into_iter().map(|(index, entry)| (index, Ok(entry))))
+    } else {
+        Box::new(toc)
+    };
+
+    let mut extractor = Extractor {
+        options,
+        names,
+        layout,
+        base_offset,
+        file_len,
+        entry_count,
+        output_dir: &output_dir,
+        zip_path: &zip_path,
+        wanted: wanted.as_ref(),
+        content_type: content_type.as_deref(),
+        flags: &flags,
+        file,
+        zip,
+        writer: options
+            .pipeline
+            .then(|| WriteQueue::new(options.best_effort)),
+        missing,
+        case_names: HashMap::new(),
+        case_db: String::new(),
+        padding: 0,
+        unnamed: Vec::new(),
+        size_filtered: 0,
+        nested: Vec::new(),
+    };
+
+    let mut extracted: Vec<(usize, EntryInfo)> = Vec::new();
+    let mut skipped = 0usize;
+    // 用于检测最后一个条目之后的尾部数据，只有完整读取目录表时才可靠
+    let mut data_end = 0u64;
+    let mut seen = 0usize;
+    for (index, entry) in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) if options.best_effort => {
+                warn_truncated_toc(index, &err);
+                break;
+            }
+            Err(err) => return Err(err),
+        };
+        data_end = data_end.max(entry.offset as u64 + entry.length as u64);
+        seen += 1;
+        if let Err(err) = cancel::check(options.cancel.as_deref()) {
+            // 后台线程中排队的条目尚未写入，只列出已确认写入的条目，其余的不再写入
+            let written: Vec<String> = match extractor.writer.take() {
+                Some(writer) => writer.cancel(),
+                None => extracted
+                    .iter()
+                    .map(|(_, info)| info.name.clone())
+                    .collect(),
+            };
+            progress!("已取消，此前已写入 {} 个条目:", written.len());
+            for name in &written {
+                progress!("  {name}");
+            }
+            return Err(err);
+        }
+        match extractor.extract(index, &entry) {
+            Ok(Some(info)) => {
+                bytes_done += info.length as u64;
+                if let Some(progress) = progress.as_mut() {
+                    progress(Progress {
+                        file: &info.name,
+                        bytes_done,
+                        bytes_total,
+                    });
+                }
+                extracted.push((index, info));
+            }
+            Ok(None) => {}
+            // 尽力模式下每个条目独立提取，失败的条目跳过
+            Err(err) if options.best_effort => {
+                events::warn("skip_entry", None, &format!("跳过条目 #{index}: {err:#}"));
+                skipped += 1;
+            }
+            Err(err) => return Err(err),
+        }
+    }
+    let Extractor {
+        mut file,
+        zip,
+        writer,
+        missing,
+        case_db,
+        padding,
+        unnamed,
+        size_filtered,
+        nested,
+        ..
+    } = extractor;
+    if let Some(writer) = writer {
+        skipped += writer.finish()?;
+    }
+    if let (Some((zip, _)), Some(temp)) = (zip, zip_temp.0.take()) {
+        let file = zip.finish()?.into_inner().map_err(|err| err.into_error())?;
+        file.sync_all()?;
+        drop(file);
+        fs::rename(&temp, &zip_path)
+            .with_context(|| format!("无法写入 zip 文件: {}", zip_path.display()))?;
+    }
+    let nesting = match options.recursive {
+        Some(max_depth) => unpack_nested(&nested, options, max_depth, level)?,
+        None => Nesting {
+            archives: 0,
+            depth: level,
+        },
+    };
+    if level == 0 && nesting.archives > 0 {
+        progress!(
+            "共解包了 {} 个嵌套的封包，最深嵌套 {} 层",
+            nesting.archives,
+            nesting.depth
+        );
+    }
+    extracted.sort_by_key(|(index, _)| *index);
+    print_skipped(padding, size_filtered, &unnamed);
+    if let Some(content_type) = &content_type {
+        progress!("类型为 {content_type} 的条目: {} 个", extracted.len());
+    }
+
+    let footer = footer_range(base_offset + data_end, file_len).filter(|_| seen == entry_count);
+    let pad_to = save_footer(&mut file, footer, file_len, options.footer.as_deref())?;
+
+    if !missing.is_empty() {
+        let names: Vec<&str> = missing.iter().map(String::as_str).collect();
+        if options.strict_names {
+            bail!("以下名称在封包中不存在: {}", names.join(", "));
+        }
+        for name in names {
+            events::warn(
+                "missing_name",
+                Some(name),
+                &format!("封包中不存在条目 '{name}'"),
+            );
+        }
+    }
+
+    if let Some(db_path) = &options.case_db {
+        fs::write(db_path, &case_db)
+            .with_context(|| format!("无法写入大小写数据库: {}", db_path.display()))?;
+        progress!(
+            "  -> 名称与原名称不同的条目: {} 个，已写入 {}，重新封包时可用 pack --rename-map 恢复",
+            case_db.lines().count(),
+            db_path.display()
+        );
+    }
+
+    // 记录文件头中封包时无法从目录得到的字段，写入 zip 时没有可供重新封包的目录
+    if options.zip.is_none() {
+        meta::write_meta(
+            &output_dir,
+            ArchiveMeta {
+                unknown1: header.unknown1,
+                pad_to,
+            },
+        )?;
+    }
+
+    if let Some(flags_path) = &options.flags_file {
+        let extracted_flags: Vec<(String, u32)> = extracted
+            .iter()
+            .map(|(index, info)| (info.name.clone(), flags.get(*index).copied().unwrap_or(0)))
+            .collect();
+        extended::write_flags_file(flags_path, &extracted_flags)?;
+    }
+    let extracted = extracted.into_iter().map(|(_, info)| info).collect();
+
+    if let Some(index_path) = &options.index {
+        write_index(index_path, extracted)
+            .with_context(|| format!("无法写入索引文件: {}", index_path.display()))?;
+    }
+
+    if skipped > 0 {
+        progress!("解包完成，跳过了 {skipped} 个条目，输出可能不完整。");
+    } else {
+        progress!("解包完成。");
+    }
+    Event {
+        event: "done",
+        file: Some(&output_dir.to_string_lossy()),
+        ..Default::default()
+    }
+    .emit();
+    Ok(nesting)
+}
+
+/// 写入 zip 时的 zip 文件与其中已有的条目名称
+type ZipOutput = (ZipWriter<io::BufWriter<fs::File>>, HashSet<String>);
+
+/// 准备输出: 写入 zip 时创建临时的 zip 文件并由 `zip_temp` 在出错时删除，否则创建输出目录
+fn open_output(
+    options: &UnpackOptions,
+    output_dir: &Path,
+    zip_temp: &mut TempGuard,
+) -> Result<Option<ZipOutput>> {
+    let Some(path) = &options.zip else {
+        fs::create_dir_all(output_dir)
+            .with_context(|| format!("无法创建目录: {}", output_dir.display()))?;
+        ensure_writable(output_dir)?;
+        progress!("解包到目录: {}", output_dir.display());
+        return Ok(None);
+    };
+    if options.pipeline || options.recursive.is_some() {
+        bail!("写入 zip 文件时不支持流水线写入与递归解包");
+    }
+    let temp = temp_path(path, "unpack");
+    let file =
+        fs::File::create(&temp).with_context(|| format!("无法创建临时文件: {}", temp.display()))?;
+    zip_temp.0 = Some(temp);
+    progress!("解包到 zip 文件: {}", path.display());
+    Ok(Some((
+        ZipWriter::new(io::BufWriter::new(file)),
+        HashSet::new(),
+    )))
+}
+
+/// 打印因筛选或无法提取而跳过的条目数
+fn print_skipped(padding: usize, size_filtered: usize, unnamed: &[usize]) {
+    if padding > 0 {
+        progress!("  注意: 跳过了 {padding} 个全为 0 的填充条目，可使用 --keep-empty-entries 保留");
+    }
+    if size_filtered > 0 {
+        progress!("  注意: 按大小筛选排除了 {size_filtered} 个条目");
+    }
+    if !unnamed.is_empty() {
+        let indices: Vec<String> = unnamed.iter().map(|index| format!("#{index}")).collect();
+        progress!(
+            "  注意: 跳过了 {} 个名称为空的条目: {}",
+            unnamed.len(),
+            indices.join(", ")
+        );
+    }
+}
+
+/// 将尾部数据 `footer` 写入 `footer_path`，返回识别为对齐填充时的对齐值
+fn save_footer(
+    file: &mut Source,
+    footer: Option<(u64, u64)>,
+    file_len: u64,
+    footer_path: Option<&Path>,
+) -> Result<Option<NonZeroU64>> {
+    let mut pad_to = None;
+    match (footer, footer_path) {
+        (Some((offset, length)), Some(footer_path)) => {
+            let mut buffer = vec![0u8; length as usize];
+            file.read_exact_at(&mut buffer, offset)?;
+            // 对齐填充记录在 .aosmeta 中，尾部数据文件留空，pack --footer 仍可使用
+            pad_to = padding_alignment(file_len, &buffer);
+            if pad_to.is_some() {
+                buffer.clear();
+            }
+            fs::write(footer_path, &buffer)
+                .with_context(|| format!("无法写入尾部数据: {}", footer_path.display()))?;
+            match pad_to {
+                Some(align) => progress!(
+                    "  -> 最后 {length} 字节为 --pad-to {align} 的对齐填充，已记录在 .aosmeta 中，封包时自动补齐；{} 为空",
+                    footer_path.display()
+                ),
+                None => progress!(
+                    "  -> 保存了 {length} 字节的尾部数据: {}",
+                    footer_path.display()
+                ),
+            }
+            Event {
+                event: "footer",
+                file: Some(&footer_path.to_string_lossy()),
+                offset: Some(offset),
+                length: Some(length),
+                ..Default::default()
+            }
+            .emit();
+        }
+        (Some((_, length)), None) => progress!(
+            "  注意: 最后一个条目之后有 {length} 字节的尾部数据，可使用 --preserve-footer 保存"
+        ),
+        (None, Some(_)) => progress!("  注意: 封包没有尾部数据"),
+        (None, None) => {}
+    }
+    Ok(pad_to)
+}
+
+/// 逐个提取条目时的状态，各写出方式 (zip、后台线程、直接写入) 分别由单独的方法处理
+struct Extractor<'a> {
+    options: &'a UnpackOptions,
+    names: NameDecoder,
+    layout: Layout,
+    base_offset: u64,
+    file_len: u64,
+    entry_count: usize,
+    output_dir: &'a Path,
+    zip_path: &'a Path,
+    wanted: Option<&'a BTreeSet<String>>,
+    content_type: Option<&'a str>,
+    flags: &'a [u32],
+    file: Source,
+    zip: Option<ZipOutput>,
+    /// `pipeline` 时的后台写入线程
+    writer: Option<WriteQueue>,
+    /// `names_from` 中列出但尚未遇到的名称
+    missing: BTreeSet<String>,
+    /// 小写的磁盘名称 -> 最先写入该名称的条目，用于检测只有大小写不同的条目
+    case_names: HashMap<String, String>,
+    case_db: String,
+    padding: usize,
+    unnamed: Vec<usize>,
+    size_filtered: usize,
+    /// 已写出的条目，启用 `recursive` 时在提取完成后检查其是否为封包
+    nested: Vec<PathBuf>,
+}
+
+impl Extractor<'_> {
+    /// 提取一个条目，被筛选掉或跳过时返回 `None`
+    fn extract(&mut self, index: usize, entry: &AosV2Entry) -> Result<Option<EntryInfo>> {
+        let Some(name) = self.select(index, entry)? else {
+            return Ok(None);
+        };
+        let Some(relative) = self.disk_path(index, entry, &name)? else {
+            return Ok(None);
+        };
+        let zip_name = slash_path(&relative);
+        let output_path = self.output_dir.join(relative);
+        if let Some((_, zip_names)) = &mut self.zip {
+            // zip 中的条目写入后无法替换，同名的条目只保留第一个
+            if !zip_names.insert(zip_name.clone()) {
+                events::warn(
+                    "duplicate_name",
+                    Some(&name),
+                    &format!("zip 中已有条目 '{zip_name}'，跳过条目 #{index}"),
+                );
+                return Ok(None);
+            }
+        } else {
+            prepare_parent(self.output_dir, &output_path, &name)?;
+        }
+
+        self.announce(index, entry, &name);
+        let buffer = self.read_data(index, entry, &name)?;
+        if self.options.recursive.is_some() {
+            self.nested.push(output_path.clone());
+        }
+        let json_entry = self.options.json_stream.then_some(AosV2Entry {
+            filename: entry.filename,
+            offset: entry.offset,
+            length: entry.length,
+        });
+        if self.zip.is_some() {
+            self.write_to_zip(index, &name, &zip_name, &buffer, json_entry)?;
+        } else {
+            let write = self.file_writer(index, &name, output_path, buffer, json_entry);
+            match self.writer.as_mut() {
+                Some(writer) => writer.push(name.clone(), write)?,
+                None => write()?,
+            }
+        }
+        Ok(Some(EntryInfo {
+            name,
+            offset: entry.offset,
+            length: entry.length,
+            checksum: None,
+            data: None,
+        }))
+    }
+
+    /// 解码条目名称并应用筛选，不需要提取时返回 `None`
+    fn select(&mut self, index: usize, entry: &AosV2Entry) -> Result<Option<String>> {
+        let options = self.options;
+        if options.filter.skips_padding(entry) {
+            self.padding += 1;
+            return Ok(None);
+        }
+        if options.filter.excludes_size(entry) {
+            self.size_filtered += 1;
+            return Ok(None);
+        }
+        let name = self
+            .names
+            .decode(entry)
+            .with_context(|| self.layout.entry_location(index))?;
+        if !options.filter.matches(index, &name, entry)
+            || self.wanted.is_some_and(|wanted| !wanted.contains(&name))
+        {
+            return Ok(None);
+        }
+        self.missing.remove(&name);
+        if name.is_empty() {
+            let location = format!(
+                "条目 #{index} 的名称为空 (偏移量 {}，长度 {})",
+                { entry.offset },
+                { entry.length }
+            );
+            if !options.skip_unnamed {
+                bail!("{location}，可使用 --skip-unnamed 跳过");
+            }
+            events::warn("unnamed_entry", None, &format!("{location}，已跳过"));
+            self.unnamed.push(index);
+            return Ok(None);
+        }
+        if let Some(error) = check_entry_bounds(&name, entry, self.base_offset, self.file_len) {
+            bail!("{}: {error}", self.layout.entry_location(index));
+        }
+        Ok(Some(name))
+    }
+
+    /// 条目相对于输出目录的路径，应用 `sanitize`、`content_type`、`transliterate` 与大小写处理；
+    /// 数据的类型与 `content_type` 不符时返回 `None`
+    fn disk_path(
+        &mut self,
+        index: usize,
+        entry: &AosV2Entry,
+        name: &str,
+    ) -> Result<Option<PathBuf>> {
+        let options = self.options;
+        let mut output_name = output_name(name, options.sanitize)?;
+        if let Some(content_type) = self.content_type {
+            let magic =
+                self.read_range(index, entry, name, (entry.length as usize).min(MAGIC_LEN))?;
+            if magic::detect(&magic) != Some(content_type) {
+                return Ok(None);
+            }
+            if Path::new(&output_name).extension().is_none() {
+                output_name = format!("{output_name}.{content_type}");
+            }
+        }
+        if options.transliterate {
+            output_name = translit::to_ascii(&output_name);
+        }
+        let mut relative = entry_path(&output_name)?;
+        let folded = slash_path(&relative).to_lowercase();
+        if let Some(other) = self.case_names.get(&folded)
+            && other != name
+        {
+            let message = if options.transliterate {
+                format!("条目 '{name}' 与 '{other}' 转写后的名称相同，将加上后缀写出")
+            } else if options.case_db.is_some() {
+                format!("条目 '{name}' 与 '{other}' 只有大小写不同，将加上后缀写出")
+            } else {
+                format!(
+                    "条目 '{name}' 与 '{other}' 只有大小写不同，在不区分大小写的文件系统上会相互覆盖，可使用 --preserve-case-db"
+                )
+            };
+            events::warn("case_collision", Some(name), &message);
+        }
+        if options.case_db.is_some() {
+            let mut disk_name = folded.clone();
+            let mut number = 1;
+            while self.case_names.contains_key(&disk_name) {
+                number += 1;
+                disk_name = case_suffixed(&folded, number);
+            }
+            if disk_name != name {
+                self.case_db.push_str(&format!("{disk_name}\t{name}\n"));
+            }
+            relative = entry_path(&disk_name)?;
+            self.case_names.insert(disk_name, name.to_owned());
+        } else {
+            self.case_names
+                .entry(folded)
+                .or_insert_with(|| name.to_owned());
+        }
+        Ok(Some(relative))
+    }
+
+    /// 打印并记录开始提取的条目
+    fn announce(&self, index: usize, entry: &AosV2Entry, name: &str) {
+        match self.flags.get(index) {
+            Some(flags) => progress!("  -> 提取: {name} (标记 {flags:#010x})"),
+            None => progress!("  -> 提取: {name}"),
+        }
+        if self.options.debug_offsets {
+            let base_offset = self.base_offset;
+            let start = base_offset + entry.offset as u64;
+            progress!(
+                "     文件内偏移量 {start} ({start:#x}) = 数据区起始 {base_offset} + 条目偏移量 {}，长度 {}，结束于 {}",
+                { entry.offset },
+                { entry.length },
+                start + entry.length as u64
+            );
+        }
+        Event {
+            event: "extract",
+            file: Some(name),
+            offset: Some(entry.offset as u64),
+            length: Some(entry.length as u64),
+            ..Default::default()
+        }
+        .emit();
+    }
+
+    /// 读取条目数据开头的 `len` 字节
+    fn read_range(
+        &mut self,
+        index: usize,
+        entry: &AosV2Entry,
+        name: &str,
+        len: usize,
+    ) -> Result<Vec<u8>> {
+        let start = self.base_offset + entry.offset as u64;
+        read_entry_data(
+            &mut self.file,
+            start,
+            len,
+            self.options.retry,
+            self.options.op_timeout,
+        )
+        .with_context(|| {
+            format!(
+                "无法读取{} '{name}' 位于文件偏移量 {start:#x} 的数据",
+                self.layout.entry_location(index)
+            )
+        })
+    }
+
+    /// 读取条目的数据，并按 `trim_trailing_nulls` 与 `decode_text` 转换
+    fn read_data(&mut self, index: usize, entry: &AosV2Entry, name: &str) -> Result<Vec<u8>> {
+        let options = self.options;
+        let mut buffer = self.read_range(index, entry, name, entry.length as usize)?;
+        if let Some(pattern) = &options.trim_trailing_nulls
+            && pattern.is_match(name)
+        {
+            let len = buffer
+                .iter()
+                .rposition(|&b| b != 0)
+                .map_or(0, |last| last + 1);
+            if len < buffer.len() {
+                progress!("     去掉了末尾的 {} 个空字节", buffer.len() - len);
+                buffer.truncate(len);
+            }
+        }
+        if let Some(pattern) = &options.decode_text
+            && pattern.is_match(name)
+        {
+            match text::decode_sjis(&buffer, options.text_bom) {
+                Ok(converted) => buffer = converted,
+                Err(err) => events::warn(
+                    "decode_text",
+                    Some(name),
+                    &format!("'{name}' 未转换为 UTF-8，原样写出: {err}"),
+                ),
+            }
+        }
+        Ok(buffer)
+    }
+
+    /// 将条目写入 zip 文件
+    fn write_to_zip(
+        &mut self,
+        index: usize,
+        name: &str,
+        zip_name: &str,
+        buffer: &[u8],
+        json_entry: Option<AosV2Entry>,
+    ) -> Result<()> {
+        let (zip, _) = self.zip.as_mut().expect("未写入 zip 文件");
+        zip.add(zip_name, buffer)
+            .with_context(|| format!("无法写入 zip 文件: {}", self.zip_path.display()))?;
+        if let Some(entry) = json_entry {
+            write_json_line(
+                index,
+                self.entry_count,
+                name,
+                &entry,
+                &self.zip_path.join(zip_name),
+            )?;
+        }
+        Ok(())
+    }
+
+    /// 将条目写为文件的任务，直接写入时立即执行，`pipeline` 时交给后台线程
+    fn file_writer(
+        &self,
+        index: usize,
+        name: &str,
+        output_path: PathBuf,
+        buffer: Vec<u8>,
+        json_entry: Option<AosV2Entry>,
+    ) -> impl FnOnce() -> Result<()> + Send + 'static {
+        let (retry, timeout) = (self.options.retry, self.options.op_timeout);
+        let (entry_count, name) = (self.entry_count, name.to_owned());
+        move || {
+            let path = output_path.clone();
+            with_timeout(timeout, move || {
+                with_retry(retry, || fs::write(&path, &buffer))
+            })
+            .with_context(|| format!("无法写入文件: {}", output_path.display()))?;
+            if let Some(entry) = json_entry {
+                write_json_line(index, entry_count, &name, &entry, &output_path)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// 解包的输出目录，包括 `prefix`
+fn output_dir(archive_path: &Path, options: &UnpackOptions) -> Result<PathBuf> {
+    let mut output_dir = match &options.output_dir {
+        Some(dir) => dir.clone(),
+        // 远程文件解包到当前目录下的同名目录
+        None if is_url(archive_path) => PathBuf::from(archive_path.file_stem().unwrap_or_default()),
+        None => archive_path.with_file_name(archive_path.file_stem().unwrap_or_default()),
+    };
+    if let Some(prefix) = &options.prefix {
+        output_dir.push(sanitize_prefix(prefix)?);
+    }
+    Ok(output_dir)
+}
+
+/// 不解包，将被选中的条目与输出目录中已有的文件比较，返回两者是否存在差异
+///
+/// 输出目录与文件路径的确定方式与 [`unpack_archive`] 相同 (`output_dir`、`prefix`、`sanitize`)，
+/// 数据区起始的确定方式也与之相同 (`compute_base`)。先比较大小，大小相同时再流式比较 xxh3 摘要。
+/// 只报告与条目对应的文件，目录中多出的文件不影响结果。
+/// `content_type`、`transliterate` 与 `case_db` 会改变写出的文件名，与之同时使用时报错。
+pub fn compare_with_dir(archive_path: &Path, options: &UnpackOptions) -> Result<bool> {
+    if options.content_type.is_some() || options.transliterate || options.case_db.is_some() {
+        bail!("与输出目录比较时不支持按类型提取、转写名称或大小写数据库");
+    }
+    if options.decode_text.is_some() {
+        bail!("与输出目录比较时不支持转换文本编码");
+    }
+    let mut source = Source::open(archive_path)?;
+    let header = AosV2Hdr::from_reader(&mut source)?;
+    extended::check_variant(&header, options.extended)?;
+    let layout = if options.extended {
+        extended_layout()
+    } else {
+        options.layout
+    };
+    source.rewind()?;
+    let mut archive = Archive::from_reader_with_layout(&mut source, layout)?;
+    if options.compute_base {
+        archive.ignore_data_offset();
+    }
+    options.filter.check_count(archive.entries.len())?;
+    let names = options
+        .names
+        .resolve(archive.entries.iter().take(AUTO_SAMPLE_SIZE))?;
+    let output_dir = output_dir(archive_path, options)?;
+    progress!("与目录比较: {}", output_dir.display());
+
+    // 多个条目写到同一路径时，解包后留下的是最后一个
+    let mut targets: Vec<(String, &AosV2Entry, PathBuf)> = Vec::new();
+    let mut positions: HashMap<PathBuf, usize> = HashMap::new();
+    for (index, entry) in archive.entries.iter().enumerate() {
+        if options.filter.skips_padding(entry) {
+            continue;
+        }
+        let name = names
+            .decode(entry)
+            .with_context(|| layout.entry_location(index))?;
+        if !options.filter.matches(index, &name, entry) {
+            continue;
+        }
+        let path = output_dir.join(entry_path(&output_name(&name, options.sanitize)?)?);
+        match positions.get(&path) {
+            Some(&position) => targets[position] = (name, entry, path),
+            None => {
+                positions.insert(path.clone(), targets.len());
+                targets.push((name, entry, path));
+            }
+        }
+    }
+
+    let (mut matching, mut changed, mut missing) = (0usize, 0usize, 0usize);
+    for (name, entry, path) in &targets {
+        let entry_len = entry.length as u64;
+        let file_len = match fs::metadata(path) {
+            Ok(metadata) if metadata.is_file() => metadata.len(),
+            _ => {
+                println!("- {name} ({entry_len} 字节，目录中不存在)");
+                missing += 1;
+                continue;
+            }
+        };
+        if file_len != entry_len {
+            println!("M {name}: 封包中 {entry_len} 字节，目录中 {file_len} 字节");
+            changed += 1;
+            continue;
+        }
+        let start = archive.entry_file_offset(entry)?;
+        let entry_digest = hash_range(&mut source, start, entry_len)
+            .with_context(|| format!("无法读取条目 '{name}'"))?;
+        let mut file =
+            fs::File::open(path).with_context(|| format!("无法打开文件: {}", path.display()))?;
+        let file_digest = hash_range(&mut file, 0, file_len)
+            .with_context(|| format!("无法读取文件: {}", path.display()))?;
+        if entry_digest == file_digest {
+            matching += 1;
+        } else {
+            println!("M {name}: {entry_len} 字节，xxh3 {entry_digest:016x} -> {file_digest:016x}");
+            changed += 1;
+        }
+    }
+
+    if changed == 0 && missing == 0 {
+        println!("目录已是最新，{matching} 个条目均一致。");
+        return Ok(false);
+    }
+    println!("{matching} 个条目一致，{changed} 个不同，{missing} 个缺失。");
+    Ok(true)
+}
+
+/// 解包已写出的条目中本身是 .aos 封包的文件
+fn unpack_nested(
+    paths: &[PathBuf],
+    options: &UnpackOptions,
+    max_depth: NonZeroUsize,
+    level: usize,
+) -> Result<Nesting> {
+    let mut nesting = Nesting {
+        archives: 0,
+        depth: level,
+    };
+    for path in paths {
+        let is_archive = probe::probe_file(path).is_ok_and(|report| report.class == ProbeClass::V2);
+        if !is_archive {
+            continue;
+        }
+        if level >= max_depth.get() {
+            events::warn(
+                "nested_depth",
+                Some(&path.to_string_lossy()),
+                &format!(
+                    "已达到最大嵌套深度 {max_depth}，未解包嵌套的封包: {}",
+                    path.display()
+                ),
+            );
+            continue;
+        }
+        let mut source = Source::open(path)?;
+        let nested_options = UnpackOptions {
+            output_dir: Some(nested_dir(path)),
+            layout: options.layout,
+            names: options.names,
+            retry: options.retry,
+            op_timeout: options.op_timeout,
+            best_effort: options.best_effort,
+            sanitize: options.sanitize,
+            extended: extended::is_extended(&AosV2Hdr::from_reader(&mut source)?),
+            compute_base: options.compute_base,
+            cancel: options.cancel.clone(),
+            debug_offsets: options.debug_offsets,
+            pipeline: options.pipeline,
+            skip_unnamed: options.skip_unnamed,
+            recursive: options.recursive,
+            ..Default::default()
+        };
+        drop(source);
+        let inner = unpack(path, &nested_options, None, level + 1)?;
+        nesting.archives += 1 + inner.archives;
+        nesting.depth = nesting.depth.max(inner.depth);
+    }
+    Ok(nesting)
+}
+
+/// 嵌套封包的输出目录: 条目旁去掉扩展名的同名目录，条目没有扩展名时加上 `_unpacked` 后缀
+fn nested_dir(path: &Path) -> PathBuf {
+    let file_name = path.file_name().unwrap_or_default();
+    let stem = path.file_stem().unwrap_or_default();
+    if stem == file_name {
+        let mut name = file_name.to_os_string();
+        name.push("_unpacked");
+        path.with_file_name(name)
+    } else {
+        path.with_file_name(stem)
+    }
+}
+
+/// 在解包前扫描目录表，打印解码后名称相同的条目，返回重名的名称数
+///
+/// 同名条目解包时后写入的会覆盖先写入的。只检查被 `filter` 选中的条目，
+/// 名称中的无效字符直接替换，目录表无法完整读取时只检查可读取的部分。
+pub fn report_duplicate_names(archive_path: &Path, options: &UnpackOptions) -> Result<usize> {
+    let mut reader = BufReader::new(Source::open(archive_path)?);
+    let header = AosV2Hdr::from_reader(&mut reader)?;
+    extended::check_variant(&header, options.extended)?;
+    let layout = if options.extended {
+        extended_layout()
+    } else {
+        options.layout
+    };
+    let entry_count = header.entry_count(layout);
+    let entries: Vec<AosV2Entry> = toc_entries(&mut reader, entry_count, layout)
+        .map_while(Result::ok)
+        .collect();
+    let names = if options.names.encoding == NameEncoding::Auto {
+        options
+            .names
+            .resolve(entries.iter().take(AUTO_SAMPLE_SIZE))?
+    } else {
+        options.names
+    };
+
+    // 名称 -> 使用该名称的条目，按名称首次出现的顺序输出
+    let mut groups: Vec<(String, Vec<usize>)> = Vec::new();
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for (index, entry) in entries.iter().enumerate() {
+        if options.filter.skips_padding(entry) {
+            continue;
+        }
+        let Ok(raw) = entry.name_bytes(names.style) else {
+            continue;
+        };
+        let name = names.decode_raw(raw).unwrap_or_else(|name| name);
+        if !options.filter.matches(index, &name, entry) {
+            continue;
+        }
+        match positions.get(&name) {
+            Some(&position) => groups[position].1.push(index),
+            None => {
+                positions.insert(name.clone(), groups.len());
+                groups.push((name, vec![index]));
+            }
+        }
+    }
+    groups.retain(|(_, indices)| indices.len() > 1);
+
+    if groups.is_empty() {
+        progress!("没有重名的条目");
+        return Ok(0);
+    }
+    progress!(
+        "重名的条目: {} 个名称，解包时后写入的条目会覆盖先写入的",
+        groups.len()
+    );
+    for (name, indices) in &groups {
+        progress!("  {name}:");
+        for &index in indices {
+            let entry = &entries[index];
+            progress!("    #{index}: 偏移量 {}，长度 {}", { entry.offset }, {
+                entry.length
+            });
+        }
+        events::warn(
+            "duplicate_name",
+            Some(name),
+            &format!("名称 '{name}' 被 {} 个条目使用", indices.len()),
+        );
+    }
+    Ok(groups.len())
+}
+
+/// 向标准输出写入一行已提取条目的 JSON 对象并立即刷新，供前端实时显示进度
+fn write_json_line(
+    index: usize,
+    total: usize,
+    name: &str,
+    entry: &AosV2Entry,
+    destination: &Path,
+) -> Result<()> {
+    let mut stdout = io::stdout().lock();
+    writeln!(
+        stdout,
+        "{{\"index\":{index},\"total\":{total},\"name\":{},\"offset\":{},\"length\":{},\"destination\":{}}}",
+        events::quote(name),
+        { entry.offset },
+        { entry.length },
+        events::quote(&destination.to_string_lossy())
+    )?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// 被选中条目的大小之和，目录表无法完整读取时只统计可读取的部分
+///
+/// 名称只用于筛选，无效字符直接替换而不给出警告，警告留给实际提取时给出。
+fn selected_size(
+    archive_path: &Path,
+    entry_count: usize,
+    layout: Layout,
+    options: &UnpackOptions,
+    names: &NameDecoder,
+    wanted: Option<&BTreeSet<String>>,
+) -> Result<u64> {
+    let mut reader = BufReader::new(Source::open(archive_path)?);
+    let mut total = 0u64;
+    for (index, entry) in toc_entries(&mut reader, entry_count, layout).enumerate() {
+        let Ok(entry) = entry else {
+            break;
+        };
+        let Ok(raw) = entry.name_bytes(names.style) else {
+            continue;
+        };
+        let name = names.decode_raw(raw).unwrap_or_else(|name| name);
+        if options.filter.matches(index, &name, &entry)
+            && wanted.is_none_or(|wanted| wanted.contains(&name))
+        {
+            total += entry.length as u64;
+        }
+    }
+    Ok(total)
+}
+
+/// 读取逐行列出的条目名称，忽略空行与行尾的 `\r`，路径为 `-` 时从标准输入读取
+fn read_names(path: &Path) -> Result<BTreeSet<String>> {
+    let content = if path == Path::new("-") {
+        let mut content = String::new();
+        io::stdin()
+            .read_to_string(&mut content)
+            .context("无法从标准输入读取名称列表")?;
+        content
+    } else {
+        fs::read_to_string(path).with_context(|| format!("无法读取名称列表: {}", path.display()))?
+    };
+    Ok(content
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect())
+}
+
+fn warn_truncated_toc(index: usize, err: &anyhow::Error) {
+    events::warn(
+        "truncated_toc",
+        None,
+        &format!("读取第 {index} 个条目时出错，忽略目录表的剩余部分: {err:#}"),
+    );
+}
+
+/// 按目录表顺序写入已提取条目的索引
+///
+/// 扩展名为 .json、.yaml / .yml 或 .toml 时以对应格式写入 (需要启用同名特性)，
+/// 否则每行写入以制表符分隔的偏移量、长度与文件名。
+fn write_index(path: &Path, entries: Vec<EntryInfo>) -> Result<()> {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase());
+    let format = match extension.as_deref() {
+        Some("json") => OutputFormat::Json,
+        Some("yaml" | "yml") => OutputFormat::Yaml,
+        Some("toml") => OutputFormat::Toml,
+        _ => OutputFormat::Text,
+    };
+
+    let content = if format == OutputFormat::Text {
+        entries
+            .iter()
+            .map(|entry| format!("{}\t{}\t{}\n", entry.offset, entry.length, entry.name))
+            .collect()
+    } else {
+        render(&ListDocument { entries }, format)? + "\n"
+    };
+    fs::write(path, content)?;
+    Ok(())
+}
+
+/// 在输出目录中创建并删除一个临时文件，提前发现只读的输出目录
+fn ensure_writable(dir: &Path) -> Result<()> {
+    let probe = dir.join(format!(".aos_up-write-test-{}", std::process::id()));
+    fs::File::create(&probe)
+        .and_then(|_| fs::remove_file(&probe))
+        .with_context(|| format!("输出目录不可写: {}", dir.display()))
+}
+
+/// 识别对齐填充时考虑的最大对齐值为 `1 << MAX_PAD_SHIFT`
+const MAX_PAD_SHIFT: u32 = 16;
+
+/// 全为空字节的尾部数据短于文件大小能整除的最大 2 的幂 (至多 64 KiB) 时视为 `pack --pad-to` 的填充，
+/// 返回该对齐值；按其补齐得到的文件与原文件相同
+fn padding_alignment(file_len: u64, footer: &[u8]) -> Option<NonZeroU64> {
+    let align = 1u64 << file_len.trailing_zeros().min(MAX_PAD_SHIFT);
+    (footer.iter().all(|&b| b == 0) && (footer.len() as u64) < align)
+        .then(|| NonZeroU64::new(align))
+        .flatten()
+}
+
+/// 在 Windows 上不能出现在文件名中的字符，路径分隔符与控制字符除外
+const ILLEGAL_CHARS: [char; 7] = ['<', '>', ':', '"', '|', '?', '*'];
+
+fn is_illegal(c: char) -> bool {
+    ILLEGAL_CHARS.contains(&c) || c.is_control()
+}
+
+/// 计算条目写入磁盘时使用的名称
+///
+/// 指定 `sanitize` 时将非法字符替换为该字符并给出警告；否则在 Windows 上遇到非法字符时报错。
+fn output_name(name: &str, sanitize: Option<char>) -> Result<String> {
+    let Some(substitute) = sanitize else {
+        if cfg!(windows)
+            && let Some(c) = name.chars().find(|&c| is_illegal(c))
+        {
+            bail!("条目名称 '{name}' 包含当前文件系统不允许的字符 {c:?}，可使用 --sanitize 替换");
+        }
+        return Ok(name.to_owned());
+    };
+    if !name.contains(is_illegal) {
+        return Ok(name.to_owned());
+    }
+    let sanitized: String = name
+        .chars()
+        .map(|c| if is_illegal(c) { substitute } else { c })
+        .collect();
+    events::warn(
+        "sanitize_name",
+        Some(name),
+        &format!("条目名称 '{name}' 包含非法字符，已写入为 '{sanitized}'"),
+    );
+    Ok(sanitized)
+}
+
+/// 将条目名称转换为相对路径，`/` 与 `\\` 均视为目录分隔符
+///
+/// 拒绝绝对路径、`..` 以及转换后为空的名称，防止条目写到输出目录之外。
+fn entry_path(name: &str) -> Result<PathBuf> {
+    let invalid = || anyhow::anyhow!("条目名称 '{name}' 不是有效的相对路径");
+    if name.starts_with(['/', '\\']) {
+        return Err(invalid());
+    }
+    let mut path = PathBuf::new();
+    for part in name.split(['/', '\\']) {
+        if part.is_empty() || part == "." {
+            continue;
+        }
+        let mut components = Path::new(part).components();
+        match (components.next(), components.next()) {
+            (Some(Component::Normal(part)), None) => path.push(part),
+            _ => return Err(invalid()),
+        }
+    }
+    if path.as_os_str().is_empty() {
+        return Err(invalid());
+    }
+    Ok(path)
+}
+
+/// 以 `/` 连接相对路径的各部分，与封包时的条目名称一致
+fn slash_path(path: &Path) -> String {
+    let parts: Vec<_> = path
+        .components()
+        .map(|part| part.as_os_str().to_string_lossy())
+        .collect();
+    parts.join("/")
+}
+
+/// 在名称最后一部分的扩展名之前加上 `~N` 后缀，例如 `d/script~2.txt`
+fn case_suffixed(name: &str, number: usize) -> String {
+    let file_start = name.rfind('/').map_or(0, |i| i + 1);
+    match name[file_start..].rfind('.') {
+        Some(dot) if dot > 0 => {
+            let dot = file_start + dot;
+            format!("{}~{number}{}", &name[..dot], &name[dot..])
+        }
+        _ => format!("{name}~{number}"),
+    }
+}
+
+/// 为嵌套的条目创建父目录，并检查目录与其他条目写入的文件是否冲突
+fn prepare_parent(output_dir: &Path, output_path: &Path, name: &str) -> Result<()> {
+    if output_path.is_dir() {
+        bail!(
+            "无法写入条目 '{name}': '{}' 已是其他条目的目录",
+            output_path.display()
+        );
+    }
+    let Some(parent) = output_path.parent() else {
+        return Ok(());
+    };
+    if let Some(conflict) = parent
+        .ancestors()
+        .take_while(|dir| *dir != output_dir)
+        .find(|dir| dir.is_file())
+    {
+        bail!(
+            "无法为条目 '{name}' 创建目录: '{}' 已是一个文件",
+            conflict.display()
+        );
+    }
+    fs::create_dir_all(parent).with_context(|| format!("无法创建目录: {}", parent.display()))
+}
+
+/// 检查前缀只由普通的路径组成部分构成，防止通过绝对路径或 `..` 逃出输出目录
+fn sanitize_prefix(prefix: &Path) -> Result<PathBuf> {
+    let mut sanitized = PathBuf::new();
+    for component in prefix.components() {
+        match component {
+            Component::Normal(part) => sanitized.push(part),
+            Component::CurDir => {}
+            _ => bail!("前缀 '{}' 不能是绝对路径或包含 '..'", prefix.display()),
+        }
+    }
+    Ok(sanitized)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use super::*;
+    use crate::{
+        format::NameStyle,
+        pack::{PackOptions, pack_directory},
+        testutil::{
+            Defect, GOLDEN_ARCHIVE, TempDir, make_corrupt_archive, make_padded_archive,
+            make_test_archive, set_data_offset,
+        },
+    };
+
+    const ENTRIES: &[(&str, &[u8])] = &[("a", b"abc"), ("b", b"de")];
+
+    fn unpack_bytes(dir: &TempDir, bytes: &[u8], options: UnpackOptions) -> Result<PathBuf> {
+        let archive = dir.write("test.aos", bytes);
+        let output = dir.path().join("out");
+        unpack_archive(
+            &archive,
+            &UnpackOptions {
+                output_dir: Some(output.clone()),
+                ..options
+            },
+        )?;
+        Ok(output)
+    }
+
+    #[test]
+    fn unpack_golden_archive() {
+        let dir = TempDir::new("unpack-golden");
+        let output = unpack_bytes(&dir, GOLDEN_ARCHIVE, UnpackOptions::default()).unwrap();
+        let files: Vec<_> = fs::read_dir(&output)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, ["hello.txt"]);
+        assert_eq!(fs::read(output.join("hello.txt")).unwrap(), b"hi!\n");
+    }
+
+    #[test]
+    fn unpack_reads_from_padded_data_offset() {
+        let dir = TempDir::new("unpack-padded");
+        let bytes = make_padded_archive(&[("a", b"abc"), ("b", b"de")], "t.aos", b"ZZZZ");
+        let output = unpack_bytes(&dir, &bytes, UnpackOptions::default()).unwrap();
+        assert_eq!(fs::read(output.join("a")).unwrap(), b"abc");
+        assert_eq!(fs::read(output.join("b")).unwrap(), b"de");
+    }
+
+    #[test]
+    fn unpack_zero_data_offset_uses_computed_base() {
+        let dir = TempDir::new("unpack-zero");
+        let mut bytes = make_test_archive(&[("a", b"abc")], "t.aos");
+        set_data_offset(&mut bytes, 0);
+        let output = unpack_bytes(&dir, &bytes, UnpackOptions::default()).unwrap();
+        assert_eq!(fs::read(output.join("a")).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn compute_base_ignores_bogus_data_offset() {
+        let dir = TempDir::new("unpack-compute-base");
+        let mut bytes = make_test_archive(&[("a", b"abc")], "t.aos");
+        set_data_offset(&mut bytes, 1);
+        assert!(unpack_bytes(&dir, &bytes, UnpackOptions::default()).is_err());
+        let options = UnpackOptions {
+            compute_base: true,
+            ..Default::default()
+        };
+        let output = unpack_bytes(&dir, &bytes, options).unwrap();
+        assert_eq!(fs::read(output.join("a")).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn compare_with_dir_after_unpacking_padded_archive() {
+        let dir = TempDir::new("compare-padded");
+        let bytes = make_padded_archive(&[("a", b"abc"), ("b", b"de")], "t.aos", b"ZZZZ");
+        let output = unpack_bytes(&dir, &bytes, UnpackOptions::default()).unwrap();
+        let options = UnpackOptions {
+            output_dir: Some(output.clone()),
+            ..Default::default()
+        };
+        let archive = dir.path().join("test.aos");
+        assert!(!compare_with_dir(&archive, &options).unwrap());
+
+        fs::write(output.join("b"), b"xy").unwrap();
+        assert!(compare_with_dir(&archive, &options).unwrap());
+    }
+
+    #[test]
+    fn bad_toc_length_fails_to_unpack() {
+        let dir = TempDir::new("unpack-bad-toc");
+        let bytes = make_corrupt_archive(ENTRIES, "t.aos", Defect::BadTocLength);
+        let err = unpack_bytes(&dir, &bytes, UnpackOptions::default()).unwrap_err();
+        assert!(format!("{err:#}").contains("data_offset"), "{err:#}");
+    }
+
+    #[test]
+    fn out_of_bounds_entry_is_reported_by_name() {
+        let dir = TempDir::new("unpack-out-of-bounds");
+        for (defect, message) in [
+            (Defect::OffsetOutOfBounds, "偏移量 6 超出数据区大小 5"),
+            (Defect::LengthOutOfBounds, "长度 3 超出文件大小"),
+        ] {
+            let bytes = make_corrupt_archive(ENTRIES, "t.aos", defect);
+            let err = unpack_bytes(&dir, &bytes, UnpackOptions::default()).unwrap_err();
+            let err = format!("{err:#}");
+            assert!(
+                err.contains("'b'") && err.contains(message),
+                "{defect:?}: {err}"
+            );
+        }
+    }
+
+    #[test]
+    fn preserved_footer_is_reappended_by_pack() {
+        let dir = TempDir::new("unpack-footer");
+        // 单个条目，打包顺序不受目录读取顺序的影响
+        let mut bytes = make_test_archive(&[("a", b"abc")], "out.aos");
+        bytes.extend_from_slice(&[0xee; 16]);
+        let footer = dir.path().join("footer.bin");
+        let options = UnpackOptions {
+            footer: Some(footer.clone()),
+            ..Default::default()
+        };
+        let output = unpack_bytes(&dir, &bytes, options).unwrap();
+        assert_eq!(fs::read(&footer).unwrap(), [0xee; 16]);
+
+        let packed = dir.path().join("packed.aos");
+        let options = PackOptions {
+            output_archive: Some(packed.clone()),
+            footer: Some(footer),
+            ..Default::default()
+        };
+        pack_directory(&output, &options).unwrap();
+        assert_eq!(fs::read(packed).unwrap(), bytes);
+    }
+
+    #[test]
+    fn pad_to_padding_is_recorded_in_meta_and_restored_by_pack() {
+        let dir = TempDir::new("unpack-pad-to");
+        // 源目录与解包目录同名，内嵌的封包名称相同
+        dir.write("src/out/a", b"abc");
+        let padded = dir.path().join("padded.aos");
+        let options = PackOptions {
+            output_archive: Some(padded.clone()),
+            pad_to: NonZeroU64::new(2048),
+            ..Default::default()
+        };
+        pack_directory(&dir.path().join("src/out"), &options).unwrap();
+        let bytes = fs::read(&padded).unwrap();
+        assert_eq!(bytes.len(), 2048);
+
+        let footer = dir.path().join("footer.bin");
+        let options = UnpackOptions {
+            footer: Some(footer.clone()),
+            ..Default::default()
+        };
+        let output = unpack_bytes(&dir, &bytes, options).unwrap();
+        assert!(fs::read(&footer).unwrap().is_empty());
+        let meta = fs::read_to_string(dir.path().join("out.aosmeta")).unwrap();
+        assert!(meta.contains("pad_to = 2048"), "{meta}");
+
+        let repacked = dir.path().join("repacked.aos");
+        let options = PackOptions {
+            output_archive: Some(repacked.clone()),
+            footer: Some(footer),
+            ..Default::default()
+        };
+        pack_directory(&output, &options).unwrap();
+        assert_eq!(fs::read(repacked).unwrap(), bytes);
+    }
+
+    #[test]
+    fn padding_alignment_requires_zeros_shorter_than_the_alignment() {
+        assert_eq!(padding_alignment(2048, &[0; 100]), NonZeroU64::new(2048));
+        assert_eq!(
+            padding_alignment(1 << 20, &[0; 100]),
+            NonZeroU64::new(1 << 16)
+        );
+        assert_eq!(padding_alignment(2048, &[0, 1]), None);
+        assert_eq!(padding_alignment(2048 + 16, &[0; 16]), None);
+    }
+
+    #[test]
+    fn pack_and_unpack_round_trip_each_name_style() {
+        let dir = TempDir::new("unpack-name-style");
+        dir.write("files/script.txt", b"abc");
+        for style in [NameStyle::Nul, NameStyle::LengthPrefixed] {
+            let packed = dir.path().join(format!("{style:?}.aos"));
+            let options = PackOptions {
+                output_archive: Some(packed.clone()),
+                name_style: style,
+                ..Default::default()
+            };
+            pack_directory(&dir.path().join("files"), &options).unwrap();
+            let archive = Archive::from_reader(&mut fs::File::open(&packed).unwrap()).unwrap();
+            assert_eq!(archive.entries[0].name_bytes(style).unwrap(), b"script.txt");
+
+            let output = dir.path().join(format!("{style:?}"));
+            let options = UnpackOptions {
+                output_dir: Some(output.clone()),
+                names: NameDecoder {
+                    style,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            unpack_archive(&packed, &options).unwrap();
+            assert_eq!(fs::read(output.join("script.txt")).unwrap(), b"abc");
+        }
+    }
+
+    #[test]
+    fn cancel_mid_unpack_keeps_only_finished_entries() {
+        let dir = TempDir::new("unpack-cancel");
+        let archive = dir.write("test.aos", &make_test_archive(ENTRIES, "t.aos"));
+        let output = dir.path().join("out");
+        let cancel = Arc::new(AtomicBool::new(false));
+        let options = UnpackOptions {
+            output_dir: Some(output.clone()),
+            cancel: Some(cancel.clone()),
+            ..Default::default()
+        };
+        let err = unpack_archive_with_progress(&archive, &options, |_| {
+            cancel.store(true, Ordering::Relaxed);
+        })
+        .unwrap_err();
+        assert!(err.is::<crate::Cancelled>(), "{err:#}");
+        let files: Vec<_> = fs::read_dir(&output)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, ["a"]);
+        assert_eq!(
+            fs::read(archive).unwrap(),
+            make_test_archive(ENTRIES, "t.aos")
+        );
+    }
+
+    #[test]
+    fn cancel_with_pipeline_drops_entries_still_queued() {
+        let dir = TempDir::new("unpack-cancel-pipeline");
+        let archive = dir.write("test.aos", &make_test_archive(ENTRIES, "t.aos"));
+        let output = dir.path().join("out");
+        let cancel = Arc::new(AtomicBool::new(false));
+        let options = UnpackOptions {
+            output_dir: Some(output.clone()),
+            cancel: Some(cancel.clone()),
+            pipeline: true,
+            ..Default::default()
+        };
+        let err = unpack_archive_with_progress(&archive, &options, |_| {
+            cancel.store(true, Ordering::Relaxed);
+        })
+        .unwrap_err();
+        assert!(err.is::<crate::Cancelled>(), "{err:#}");
+        // 第一个条目可能仍在队列中而被丢弃，之后的条目不会被提交
+        let files: Vec<_> = fs::read_dir(&output)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert!(files.is_empty() || files == ["a"], "{files:?}");
+    }
+
+    #[test]
+    fn trailing_zero_entries_do_not_become_files() {
+        let dir = TempDir::new("unpack-zero-entries");
+        let bytes = make_corrupt_archive(ENTRIES, "t.aos", Defect::TrailingZeroEntries);
+        let output = unpack_bytes(&dir, &bytes, UnpackOptions::default()).unwrap();
+        let mut files: Vec<_> = fs::read_dir(&output)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        files.sort();
+        assert_eq!(files, ["a", "b"]);
+
+        // 保留的填充条目没有名称，与其他名称为空的条目一样需要 --skip-unnamed
+        let mut options = UnpackOptions::default();
+        options.filter.keep_empty = true;
+        let err = unpack_bytes(&dir, &bytes, options).unwrap_err();
+        assert!(format!("{err:#}").contains("条目 #2 的名称为空"), "{err:#}");
+    }
+
+    #[test]
+    fn unnamed_entry_needs_skip_unnamed() {
+        let dir = TempDir::new("unpack-unnamed");
+        let bytes = make_corrupt_archive(ENTRIES, "t.aos", Defect::UnnamedEntry);
+        assert!(unpack_bytes(&dir, &bytes, UnpackOptions::default()).is_err());
+        let options = UnpackOptions {
+            skip_unnamed: true,
+            ..Default::default()
+        };
+        let output = unpack_bytes(&dir, &bytes, options).unwrap();
+        assert_eq!(fs::read(output.join("a")).unwrap(), b"abc");
+        assert_eq!(fs::read_dir(&output).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn nested_entries_create_parent_directories() {
+        let dir = TempDir::new("unpack-nested");
+        let bytes = make_test_archive(&[("d/sub/x", b"abc"), ("d\\y", b"de")], "t.aos");
+        let output = unpack_bytes(&dir, &bytes, UnpackOptions::default()).unwrap();
+        assert_eq!(fs::read(output.join("d/sub/x")).unwrap(), b"abc");
+        assert_eq!(fs::read(output.join("d/y")).unwrap(), b"de");
+    }
+
+    #[test]
+    fn file_and_directory_conflict_is_reported() {
+        let dir = TempDir::new("unpack-conflict");
+        for (entries, message) in [
+            (
+                [("data", &b"abc"[..]), ("data/x", b"de")],
+                "无法为条目 'data/x' 创建目录",
+            ),
+            (
+                [("data/x", &b"abc"[..]), ("data", b"de")],
+                "无法写入条目 'data'",
+            ),
+        ] {
+            let bytes = make_test_archive(&entries, "t.aos");
+            let err = unpack_bytes(&dir, &bytes, UnpackOptions::default()).unwrap_err();
+            assert!(format!("{err:#}").contains(message), "{err:#}");
+            fs::remove_dir_all(dir.path().join("out")).unwrap();
+        }
+    }
+
+    #[test]
+    fn entry_paths_stay_inside_the_output_directory() {
+        assert_eq!(entry_path("a/./b\\c").unwrap(), Path::new("a/b/c"));
+        for name in ["/etc/passwd", "\\x", "a/../../x", "..", "./"] {
+            assert!(entry_path(name).is_err(), "{name}");
+        }
+    }
+
+    #[test]
+    fn unpack_to_long_multibyte_directory_name() {
+        let dir = TempDir::new("unpack-long-name");
+        let archive = dir.write("test.aos", &make_test_archive(ENTRIES, "t.aos"));
+        let output = dir.path().join("あ".repeat(84));
+        let options = UnpackOptions {
+            output_dir: Some(output.clone()),
+            ..Default::default()
+        };
+        unpack_archive(&archive, &options).unwrap();
+        assert_eq!(fs::read(output.join("a")).unwrap(), b"abc");
+    }
+}
@@ -0,0 +1,598 @@
+use std::{
+    io::{BufReader, Read, Seek, SeekFrom},
+    num::NonZeroU64,
+    path::{Path, PathBuf},
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+use anyhow::Result;
+
+use crate::{
+    archive::{Archive, footer_range},
+    batch::{BatchFailure, print_batch_summary},
+    events::is_silent,
+    format::{AosV2Entry, Layout, NameDecoder, NameEncoding},
+    limit::OpenFileLimit,
+    lock,
+    size::ByteSize,
+    threads::Threads,
+};
+
+/// 推断条目对齐值时考虑的最大值为 `1 << MAX_ALIGN_SHIFT`
+const MAX_ALIGN_SHIFT: u32 = 16;
+
+/// 校验选项
+#[derive(Debug, Default)]
+pub struct ValidateOptions {
+    /// 目录表布局
+    pub layout: Layout,
+    /// 文件名解码方式
+    pub names: NameDecoder,
+    /// 要求数据区起始偏移量为该值的整数倍
+    pub expect_data_align: Option<NonZeroU64>,
+    /// 统计数据区中未被任何条目覆盖的空隙
+    pub check_gaps: bool,
+    /// 要求目录表中条目的偏移量单调不减，即目录表顺序与数据的物理顺序一致
+    pub require_sorted_offsets: bool,
+    /// 名称为空的条目只记为警告，与 `unpack --skip-unnamed` 对应
+    pub skip_unnamed: bool,
+}
+
+/// 校验发现的问题
+#[derive(Debug, Default)]
+pub struct ValidationReport {
+    /// 会导致封包无法被正确读取的问题
+    pub errors: Vec<String>,
+    /// 不影响解包但值得注意的问题
+    pub warnings: Vec<String>,
+    /// 启用 `check_gaps` 时的空隙统计
+    pub gaps: Option<GapReport>,
+}
+
+/// 数据区空隙统计
+#[derive(Debug, Default)]
+pub struct GapReport {
+    /// 所有空隙的总字节数，包括对齐填充
+    pub total: u64,
+    /// 最大的空隙，为数据区内的偏移量与长度
+    pub largest: Option<(u64, u64)>,
+    /// 推断出的条目对齐值，为 1 时表示条目没有对齐
+    pub align: u64,
+    /// 可归因于对齐填充的字节数
+    pub padding: u64,
+}
+
+impl GapReport {
+    /// 按偏移量排序条目后统计 `[0, region_len)` 中未被覆盖的字节
+    ///
+    /// 条目偏移量都是同一个 2 的幂的倍数时将其视为对齐值，紧接在对齐的条目之前且短于
+    /// 对齐值的空隙计为对齐填充，其余空隙 (包括数据区末尾未被使用的字节) 计为浪费。
+    fn compute(entries: &[AosV2Entry], region_len: u64) -> Self {
+        let mut ranges: Vec<(u64, u64)> = entries
+            .iter()
+            .map(|entry| {
+                let start = (entry.offset as u64).min(region_len);
+                (start, (start + entry.length as u64).min(region_len))
+            })
+            .filter(|(start, end)| start < end)
+            .collect();
+        ranges.sort_unstable();
+
+        let align = ranges
+            .iter()
+            .map(|&(start, _)| start)
+            .filter(|&start| start > 0)
+            .map(|start| 1u64 << start.trailing_zeros().min(MAX_ALIGN_SHIFT))
+            .min()
+            .unwrap_or(1);
+
+        let mut report = Self {
+            align,
+            ..Default::default()
+        };
+        let mut covered = 0;
+        for &(start, end) in &ranges {
+            if start > covered {
+                report.add(
+                    covered,
+                    start - covered,
+                    align > 1 && start - covered < align,
+                );
+            }
+            covered = covered.max(end);
+        }
+        if region_len > covered {
+            report.add(covered, region_len - covered, false);
+        }
+        report
+    }
+
+    fn add(&mut self, offset: u64, length: u64, is_padding: bool) {
+        self.total += length;
+        if is_padding {
+            self.padding += length;
+        }
+        if self.largest.is_none_or(|(_, largest)| length > largest) {
+            self.largest = Some((offset, length));
+        }
+    }
+
+    /// 不属于对齐填充的字节数
+    pub fn wasted(&self) -> u64 {
+        self.total - self.padding
+    }
+}
+
+impl ValidationReport {
+    /// 是否没有发现错误
+    pub fn is_ok(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// 以文本列出所有问题，每行一个，行首带有 `indent`
+    pub fn to_text(&self, indent: &str) -> String {
+        let errors = self
+            .errors
+            .iter()
+            .map(|error| format!("{indent}错误: {error}\n"));
+        let warnings = self
+            .warnings
+            .iter()
+            .map(|warning| format!("{indent}警告: {warning}\n"));
+        let mut text: String = errors.chain(warnings).collect();
+        if let Some(gaps) = &self.gaps {
+            text += &format!(
+                "{indent}空隙: 共 {}，其中对齐填充 {} (对齐值 {})，浪费 {}\n",
+                ByteSize(gaps.total),
+                ByteSize(gaps.padding),
+                gaps.align,
+                ByteSize(gaps.wasted())
+            );
+            if let Some((offset, length)) = gaps.largest {
+                text += &format!(
+                    "{indent}最大空隙: 数据区偏移量 {offset} 处的 {}\n",
+                    ByteSize(length)
+                );
+            }
+        }
+        text
+    }
+}
+
+/// 检查 .aos 文件的结构，返回发现的问题
+///
+/// 只有文件无法打开或文件头、目录表无法读取时才返回 `Err`，其余问题都记录在报告中。
+pub fn validate_archive(
+    archive_path: &Path,
+    options: &ValidateOptions,
+) -> Result<ValidationReport> {
+    let mut file = lock::open_shared(archive_path)?;
+    let file_len = file.metadata()?.len();
+    quick_validate_with(&mut BufReader::new(&mut file), file_len, options)
+}
+
+/// 以默认选项检查长度为 `file_len` 的 .aos 数据的结构，只读取文件头与目录表，不读取条目数据
+///
+/// 与 [`validate_archive`] 相同，只有文件头或目录表无法读取时才返回 `Err`。
+pub fn quick_validate<R: Read + Seek>(reader: &mut R, file_len: u64) -> Result<ValidationReport> {
+    quick_validate_with(reader, file_len, &ValidateOptions::default())
+}
+
+/// 按 `options` 检查长度为 `file_len` 的 .aos 数据的结构，只读取文件头与目录表
+pub fn quick_validate_with<R: Read + Seek>(
+    reader: &mut R,
+    file_len: u64,
+    options: &ValidateOptions,
+) -> Result<ValidationReport> {
+    reader.seek(SeekFrom::Start(0))?;
+    let archive = Archive::from_reader_with_layout(reader, options.layout)?;
+    let mut report = ValidationReport::default();
+
+    let toc_length = archive.header.toc_length as usize;
+    let entry_size = options.layout.entry_size();
+    if !toc_length.is_multiple_of(entry_size) {
+        report.errors.push(format!(
+            "toc_length {toc_length} 不是条目大小 {entry_size} 的整数倍"
+        ));
+    }
+
+    if let Some(warning) = check_file_size(file_len) {
+        report.warnings.push(warning);
+    }
+    // data_offset 非零时以其为数据区起始，无效时按文件头与目录表大小检查条目
+    let base_offset = match archive.base_offset() {
+        Ok(base_offset) => base_offset,
+        Err(err) => {
+            report.errors.push(err.to_string());
+            archive.header.toc_end()
+        }
+    };
+    if base_offset > archive.header.toc_end() {
+        report.warnings.push(format!(
+            "data_offset 为 {base_offset}，目录表与数据区之间有 {} 字节填充",
+            base_offset - archive.header.toc_end()
+        ));
+    }
+    if base_offset > file_len {
+        report.errors.push(format!(
+            "数据区起始偏移量 {base_offset} 超出文件大小 {file_len}"
+        ));
+    }
+
+    // 无法自动选择编码时记录错误，并按 UTF-8 逐个报告无效的文件名
+    let names = options
+        .names
+        .resolve(&archive.entries)
+        .unwrap_or_else(|err| {
+            report.errors.push(err.to_string());
+            NameDecoder {
+                encoding: NameEncoding::Utf8,
+                ..options.names
+            }
+        });
+    let mut padding = 0usize;
+    for (index, entry) in archive.entries.iter().enumerate() {
+        if entry.is_padding() {
+            padding += 1;
+            continue;
+        }
+        // 不使用 NameDecoder::decode 以免直接打印警告，所有问题都只记录在报告中
+        let raw = match entry.name_bytes(names.style) {
+            Ok(raw) => raw,
+            Err(err) => {
+                report
+                    .errors
+                    .push(format!("{}: {err}", options.layout.entry_location(index)));
+                continue;
+            }
+        };
+        let name = match names.decode_raw(raw) {
+            Ok(name) => name,
+            Err(lossy) => {
+                let problem = format!(
+                    "{}: 文件名 '{lossy}' 包含无效的 {} 字符",
+                    options.layout.entry_location(index),
+                    names.encoding_label()
+                );
+                if names.lossy {
+                    report.warnings.push(problem);
+                } else {
+                    report.errors.push(problem);
+                }
+                lossy
+            }
+        };
+        if name.is_empty() {
+            let problem = format!(
+                "条目 #{index} 的名称为空 (偏移量 {}，长度 {})，unpack 需要 --skip-unnamed 才能跳过",
+                { entry.offset },
+                { entry.length }
+            );
+            if options.skip_unnamed {
+                report.warnings.push(problem);
+            } else {
+                report.errors.push(problem);
+            }
+        }
+        if let Some(error) = check_entry_bounds(&name, entry, base_offset, file_len) {
+            report
+                .errors
+                .push(format!("{}: {error}", options.layout.entry_location(index)));
+        }
+    }
+
+    report.errors.extend(check_overlaps(&archive.entries));
+
+    if padding > 0 {
+        report.warnings.push(format!(
+            "目录表中有 {padding} 个全为 0 的填充条目，unpack 与 list 默认跳过"
+        ));
+    }
+    if let Some(align) = options.expect_data_align
+        && let Some(warning) = check_data_align(base_offset, align)
+    {
+        report.warnings.push(warning);
+    }
+    if let Some((offset, length)) = footer_range(base_offset + archive.data_end(), file_len) {
+        report.warnings.push(format!(
+            "最后一个条目之后有 {length} 字节的尾部数据 (偏移量 {offset})"
+        ));
+    }
+    if options.require_sorted_offsets
+        && let Some(index) = archive
+            .entries
+            .windows(2)
+            .position(|pair| pair[0].offset > pair[1].offset)
+    {
+        let (current, next) = (&archive.entries[index], &archive.entries[index + 1]);
+        report.errors.push(format!(
+            "条目 #{index} 的偏移量 {} 大于条目 #{} 的偏移量 {}，目录表顺序与数据顺序不一致",
+            { current.offset },
+            index + 1,
+            { next.offset }
+        ));
+    }
+    if options.check_gaps {
+        let region_len = file_len.saturating_sub(base_offset);
+        report.gaps = Some(GapReport::compute(&archive.entries, region_len));
+    }
+    Ok(report)
+}
+
+/// 使用最多 `threads` 个线程校验多个 .aos 文件，按输入顺序返回未通过校验的文件
+///
+/// 同时打开的 .aos 文件不超过 `max_open_files` 个，与线程数无关。
+/// 每个文件的结果在完成后整段打印，不同文件的输出不会交错；最后打印汇总与失败的文件列表。
+pub fn validate_batch(
+    paths: &[PathBuf],
+    options: &ValidateOptions,
+    threads: Threads,
+    max_open_files: usize,
+) -> Vec<BatchFailure> {
+    let limit = OpenFileLimit::new(max_open_files);
+    let next = AtomicUsize::new(0);
+    let failed = Mutex::new(Vec::new());
+    let workers = threads.resolve().clamp(1, paths.len().max(1));
+    let work = || {
+        loop {
+            let index = next.fetch_add(1, Ordering::Relaxed);
+            let Some(path) = paths.get(index) else {
+                break;
+            };
+            let result = {
+                let _permit = limit.acquire();
+                validate_archive(path, options)
+            };
+            let passed = result.as_ref().is_ok_and(ValidationReport::is_ok);
+            let mut text = format!(
+                "{}: {}\n",
+                path.display(),
+                if passed { "通过" } else { "失败" }
+            );
+            match &result {
+                Ok(report) => text += &report.to_text("  "),
+                Err(err) => text += &format!("  错误: {err:#}\n"),
+            }
+            if !passed {
+                let cause = match &result {
+                    Ok(report) => report.errors.first().cloned().unwrap_or_default(),
+                    Err(err) => format!("{err:#}"),
+                };
+                let failure = BatchFailure {
+                    path: path.clone(),
+                    cause,
+                };
+                failed.lock().unwrap().push((index, failure));
+            }
+            // 静默模式下只将未通过的文件输出到标准错误
+            if !is_silent() {
+                print!("{text}");
+            } else if !passed {
+                eprint!("{text}");
+            }
+        }
+    };
+    // 单线程时在当前线程中依次校验
+    if workers == 1 {
+        work();
+    } else {
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(work);
+            }
+        });
+    }
+
+    let mut failed = failed.into_inner().unwrap();
+    failed.sort_by_key(|(index, _)| *index);
+    let failed: Vec<_> = failed.into_iter().map(|(_, failure)| failure).collect();
+    print_batch_summary(paths.len(), &failed);
+    failed
+}
+
+/// 条目的数据不完全位于数据区 `[0, file_len - base_offset]` 之内时返回错误信息
+pub(crate) fn check_entry_bounds(
+    name: &str,
+    entry: &AosV2Entry,
+    base_offset: u64,
+    file_len: u64,
+) -> Option<String> {
+    let region_len = file_len.saturating_sub(base_offset);
+    let (offset, length) = (entry.offset as u64, entry.length as u64);
+    let start = base_offset + offset;
+    if offset > region_len {
+        Some(format!(
+            "'{name}' 位于文件偏移量 {start:#x}: 偏移量 {offset} 超出数据区大小 {region_len} (文件大小 {file_len})"
+        ))
+    } else if offset + length > region_len {
+        Some(format!(
+            "'{name}' 位于文件偏移量 {start:#x}: 长度 {length} 超出文件大小 {file_len} (数据范围 {offset}..{}，数据区大小 {region_len})",
+            offset + length
+        ))
+    } else {
+        None
+    }
+}
+
+/// 数据范围部分重叠的条目对，偏移量与长度都相同的条目视为共享数据 (例如 `pack --dedup`)
+fn check_overlaps(entries: &[AosV2Entry]) -> Vec<String> {
+    let mut ranges: Vec<(usize, u64, u64)> = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.length > 0)
+        .map(|(index, entry)| {
+            let start = entry.offset as u64;
+            (index, start, start + entry.length as u64)
+        })
+        .collect();
+    ranges.sort_by_key(|&(index, start, end)| (start, end, index));
+    let mut problems = Vec::new();
+    // 此前结束位置最靠后的条目
+    let mut furthest: Option<(usize, u64, u64)> = None;
+    for range in ranges {
+        let (index, start, end) = range;
+        if let Some((other, other_start, other_end)) = furthest
+            && start < other_end
+            && (start, end) != (other_start, other_end)
+        {
+            problems.push(format!(
+                "条目 #{index} 的数据范围 {start}..{end} 与条目 #{other} 的 {other_start}..{other_end} 重叠"
+            ));
+        }
+        if furthest.is_none_or(|(_, _, other_end)| end > other_end) {
+            furthest = Some(range);
+        }
+    }
+    problems
+}
+
+/// 文件大小超出 `u32` 偏移量可寻址的范围时返回警告信息
+pub(crate) fn check_file_size(file_len: u64) -> Option<String> {
+    let limit = u32::MAX as u64;
+    (file_len > limit).then(|| {
+        format!(
+            "文件大小 {file_len} 字节超出了 u32 偏移量可寻址的 {limit} 字节，超出部分无法被任何条目引用，\
+             可能带有尾部数据或拼接了多个封包"
+        )
+    })
+}
+
+/// 数据区起始偏移量不满足对齐要求时返回警告信息
+pub(crate) fn check_data_align(base_offset: u64, align: NonZeroU64) -> Option<String> {
+    (!base_offset.is_multiple_of(align.get())).then(|| {
+        format!(
+            "数据区起始偏移量 {base_offset} 不是 {align} 的整数倍，该封包可能不是为目标引擎构建的"
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, num::NonZeroUsize};
+
+    use super::*;
+    use crate::testutil::{
+        Defect, TempDir, make_corrupt_archive, make_padded_archive, make_test_archive,
+    };
+
+    const ENTRIES: &[(&str, &[u8])] = &[("a", b"abc"), ("b", b"de")];
+
+    fn check(bytes: &[u8]) -> ValidationReport {
+        quick_validate(&mut Cursor::new(bytes), bytes.len() as u64).unwrap()
+    }
+
+    #[test]
+    fn padded_data_offset_is_reported_without_a_footer() {
+        let report = check(&make_padded_archive(&[("a", b"abc")], "t.aos", b"ZZZZ"));
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert_eq!(report.warnings.len(), 1, "{:?}", report.warnings);
+        assert!(report.warnings[0].contains("4 字节填充"));
+    }
+
+    fn check_defect(defect: Defect) -> ValidationReport {
+        check(&make_corrupt_archive(ENTRIES, "t.aos", defect))
+    }
+
+    #[test]
+    fn footer_is_reported_as_a_warning() {
+        let mut bytes = make_test_archive(ENTRIES, "t.aos");
+        let offset = bytes.len();
+        bytes.extend_from_slice(&[0xee; 16]);
+        let report = check(&bytes);
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert_eq!(
+            report.warnings,
+            [format!(
+                "最后一个条目之后有 16 字节的尾部数据 (偏移量 {offset})"
+            )]
+        );
+    }
+
+    #[test]
+    fn batch_with_low_open_file_limit_checks_every_archive() {
+        let dir = TempDir::new("validate-batch");
+        let mut paths: Vec<PathBuf> = (0..64)
+            .map(|i| dir.write(&format!("{i}.aos"), &make_test_archive(ENTRIES, "t.aos")))
+            .collect();
+        let corrupt = make_corrupt_archive(ENTRIES, "t.aos", Defect::OverlappingEntries);
+        paths.push(dir.write("corrupt.aos", &corrupt));
+        let threads = Threads::Count(NonZeroUsize::new(8).unwrap());
+        let failed = validate_batch(&paths, &ValidateOptions::default(), threads, 2);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].path, paths[64]);
+    }
+
+    #[test]
+    fn valid_archive_passes() {
+        let report = check(&make_test_archive(ENTRIES, "t.aos"));
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+    }
+
+    #[test]
+    fn bad_toc_length_is_an_error() {
+        let report = check_defect(Defect::BadTocLength);
+        assert!(
+            report
+                .errors
+                .iter()
+                .any(|error| error.contains("toc_length")),
+            "{:?}",
+            report.errors
+        );
+    }
+
+    #[test]
+    fn overlapping_entries_are_an_error() {
+        let report = check_defect(Defect::OverlappingEntries);
+        assert_eq!(
+            report.errors,
+            ["条目 #0 的数据范围 0..3 与条目 #1 的 0..2 重叠"]
+        );
+    }
+
+    #[test]
+    fn unnamed_entry_is_an_error_unless_skipped() {
+        let report = check_defect(Defect::UnnamedEntry);
+        assert_eq!(report.errors.len(), 1, "{:?}", report.errors);
+        assert!(report.errors[0].contains("名称为空"));
+
+        let bytes = make_corrupt_archive(ENTRIES, "t.aos", Defect::UnnamedEntry);
+        let options = ValidateOptions {
+            skip_unnamed: true,
+            ..Default::default()
+        };
+        let report =
+            quick_validate_with(&mut Cursor::new(&bytes), bytes.len() as u64, &options).unwrap();
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert_eq!(report.warnings.len(), 1, "{:?}", report.warnings);
+    }
+
+    #[test]
+    fn trailing_zero_entries_are_a_warning() {
+        let report = check_defect(Defect::TrailingZeroEntries);
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+        assert_eq!(
+            report.warnings,
+            ["目录表中有 2 个全为 0 的填充条目，unpack 与 list 默认跳过"]
+        );
+    }
+
+    #[test]
+    fn out_of_bounds_entries_are_errors() {
+        for (defect, message) in [
+            (Defect::OffsetOutOfBounds, "偏移量 6 超出数据区大小 5"),
+            (Defect::LengthOutOfBounds, "长度 3 超出文件大小"),
+        ] {
+            let report = check_defect(defect);
+            assert_eq!(report.errors.len(), 1, "{defect:?}: {:?}", report.errors);
+            let error = &report.errors[0];
+            assert!(error.contains("条目 #1"), "{error}");
+            assert!(error.contains("'b'") && error.contains(message), "{error}");
+        }
+    }
+}
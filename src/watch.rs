@@ -0,0 +1,81 @@
+//! 监视目录变化并自动重新封包，需要启用 `watch` 特性
+
+use std::{
+    fs,
+    path::Path,
+    sync::mpsc::{self, RecvTimeoutError},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+
+use crate::{
+    pack::{PackOptions, canonicalize_output, pack_directory},
+    size::ByteSize,
+};
+
+/// 监视目录，在文件创建、修改或删除后重新封包
+///
+/// 启动时先封包一次。`debounce` 时间内连续发生的变化合并为一次封包；
+/// 封包失败时只打印错误并继续监视。`options.recursive` 时同时监视子目录。
+/// 该函数只有在监视出错时才会返回。
+pub fn watch_directory(dir_path: &Path, options: &PackOptions, debounce: Duration) -> Result<()> {
+    let output_filename = options.output_filename(dir_path);
+    let output_canonical = canonicalize_output(&output_filename);
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).context("无法创建目录监视器")?;
+    let mode = if options.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    watcher
+        .watch(dir_path, mode)
+        .with_context(|| format!("无法监视目录: {}", dir_path.display()))?;
+
+    // 输出文件位于被监视的目录中时，写入输出文件本身产生的事件不应触发重新封包
+    let is_relevant = |event: &Event| {
+        !matches!(event.kind, EventKind::Access(_))
+            && event.paths.iter().any(|path| {
+                output_canonical.is_none() || canonicalize_output(path) != output_canonical
+            })
+    };
+
+    rebuild(dir_path, options, &output_filename, 0);
+    progress!("正在监视目录: {} (按 Ctrl+C 退出)", dir_path.display());
+    loop {
+        let event = receiver.recv().context("目录监视已停止")?;
+        if !is_relevant(&event.context("监视目录时出错")?) {
+            continue;
+        }
+        let mut changes = 1;
+        loop {
+            match receiver.recv_timeout(debounce) {
+                Ok(event) => {
+                    if is_relevant(&event.context("监视目录时出错")?) {
+                        changes += 1;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => anyhow::bail!("目录监视已停止"),
+            }
+        }
+        rebuild(dir_path, options, &output_filename, changes);
+    }
+}
+
+fn rebuild(dir_path: &Path, options: &PackOptions, output_filename: &Path, changes: usize) {
+    if changes > 0 {
+        progress!("检测到 {changes} 处变化，重新封包");
+    }
+    match pack_directory(dir_path, options) {
+        Ok(()) => {
+            let size = fs::metadata(output_filename).map_or(0, |metadata| metadata.len());
+            progress!("输出文件大小: {}", ByteSize(size));
+        }
+        Err(err) if crate::is_silent() => eprintln!("封包失败: {err:#}"),
+        Err(err) => println!("封包失败: {err:#}"),
+    }
+}
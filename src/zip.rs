@@ -0,0 +1,138 @@
+//! 写入不压缩 (stored) 的 .zip 文件，用于 `unpack --as-zip`
+//!
+//! 只实现了写入所需的最小子集: 不压缩、不使用 ZIP64，所有条目的修改时间固定为
+//! 1980-01-01 00:00:00 (DOS 时间的最小值)，相同的输入总是得到逐字节相同的输出。
+//! 名称以 UTF-8 存储并设置通用标志位的第 11 位 (语言编码标志)。
+
+use std::io::{self, Write};
+
+use anyhow::{Context, Result, bail};
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY_SIGNATURE: u32 = 0x0605_4b50;
+/// 解压所需的最低版本 2.0
+const VERSION: u16 = 20;
+/// 名称以 UTF-8 编码
+const FLAG_UTF8: u16 = 1 << 11;
+/// 1980-01-01 的 DOS 日期，时间部分为 0
+const DOS_DATE: u16 = (1 << 5) | 1;
+
+/// 中央目录中的一项
+struct CentralEntry {
+    name: String,
+    crc: u32,
+    size: u32,
+    offset: u32,
+}
+
+/// 按添加顺序写入条目，[`ZipWriter::finish`] 时写入中央目录
+pub(crate) struct ZipWriter<W: Write> {
+    writer: W,
+    offset: u64,
+    entries: Vec<CentralEntry>,
+}
+
+impl<W: Write> ZipWriter<W> {
+    pub(crate) fn new(writer: W) -> Self {
+        Self {
+            writer,
+            offset: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// 添加一个文件，`name` 以 `/` 分隔路径的各部分
+    pub(crate) fn add(&mut self, name: &str, data: &[u8]) -> Result<()> {
+        if self.entries.len() >= u16::MAX as usize {
+            bail!("条目超过 {} 个，需要 ZIP64，暂不支持", u16::MAX);
+        }
+        let offset =
+            u32::try_from(self.offset).context("zip 文件超出 4 GiB，需要 ZIP64，暂不支持")?;
+        let size = u32::try_from(data.len()).context(format!("条目 '{name}' 过大"))?;
+        let name_len = u16::try_from(name.len()).context(format!("条目名称 '{name}' 过长"))?;
+        let crc = crc32fast::hash(data);
+
+        let mut header = Vec::with_capacity(30 + name.len());
+        put_u32(&mut header, LOCAL_HEADER_SIGNATURE);
+        put_u16(&mut header, VERSION);
+        put_u16(&mut header, FLAG_UTF8);
+        put_u16(&mut header, 0); // 不压缩
+        put_u16(&mut header, 0); // 时间
+        put_u16(&mut header, DOS_DATE);
+        put_u32(&mut header, crc);
+        put_u32(&mut header, size);
+        put_u32(&mut header, size);
+        put_u16(&mut header, name_len);
+        put_u16(&mut header, 0); // 扩展字段长度
+        header.extend_from_slice(name.as_bytes());
+        self.write(&header)?;
+        self.write(data)?;
+
+        self.entries.push(CentralEntry {
+            name: name.to_owned(),
+            crc,
+            size,
+            offset,
+        });
+        Ok(())
+    }
+
+    /// 写入中央目录与结束记录，返回内部的写入器
+    pub(crate) fn finish(mut self) -> Result<W> {
+        let start =
+            u32::try_from(self.offset).context("zip 文件超出 4 GiB，需要 ZIP64，暂不支持")?;
+        let mut directory = Vec::new();
+        for entry in &self.entries {
+            put_u32(&mut directory, CENTRAL_HEADER_SIGNATURE);
+            put_u16(&mut directory, VERSION); // 创建者版本，高字节 0 表示 MS-DOS 属性
+            put_u16(&mut directory, VERSION);
+            put_u16(&mut directory, FLAG_UTF8);
+            put_u16(&mut directory, 0);
+            put_u16(&mut directory, 0);
+            put_u16(&mut directory, DOS_DATE);
+            put_u32(&mut directory, entry.crc);
+            put_u32(&mut directory, entry.size);
+            put_u32(&mut directory, entry.size);
+            put_u16(&mut directory, entry.name.len() as u16);
+            put_u16(&mut directory, 0); // 扩展字段长度
+            put_u16(&mut directory, 0); // 注释长度
+            put_u16(&mut directory, 0); // 起始磁盘号
+            put_u16(&mut directory, 0); // 内部属性
+            put_u32(&mut directory, 0); // 外部属性
+            put_u32(&mut directory, entry.offset);
+            directory.extend_from_slice(entry.name.as_bytes());
+        }
+        let directory_len =
+            u32::try_from(directory.len()).context("zip 中央目录过大，需要 ZIP64，暂不支持")?;
+        if start.checked_add(directory_len).is_none() {
+            bail!("zip 文件超出 4 GiB，需要 ZIP64，暂不支持");
+        }
+        let count = self.entries.len() as u16;
+        put_u32(&mut directory, END_OF_CENTRAL_DIRECTORY_SIGNATURE);
+        put_u16(&mut directory, 0); // 磁盘号
+        put_u16(&mut directory, 0); // 中央目录起始磁盘号
+        put_u16(&mut directory, count);
+        put_u16(&mut directory, count);
+        put_u32(&mut directory, directory_len);
+        put_u32(&mut directory, start);
+        put_u16(&mut directory, 0); // 注释长度
+        self.write(&directory)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.writer.write_all(bytes)?;
+        self.offset += bytes.len() as u64;
+        Ok(())
+    }
+}
+
+fn put_u16(buffer: &mut Vec<u8>, value: u16) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(buffer: &mut Vec<u8>, value: u32) {
+    buffer.extend_from_slice(&value.to_le_bytes());
+}